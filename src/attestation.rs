@@ -0,0 +1,105 @@
+// src/attestation.rs
+
+//! Poll results in a compact form that bridges and oracles can relay.
+//!
+//! A [`ResultAttestation`] binds a closed poll's outcome to this program and
+//! the slot in which the poll was closed. Each attestor named at poll
+//! creation signs [`ResultAttestation::message`] with their own key and
+//! submits the signature with `SubmitAttestation`, which stores it on chain.
+//! Relayers read the stored records and check each signature against the
+//! attestor's key on the destination chain.
+
+use arch_program::pubkey::Pubkey;
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha2::{Digest, Sha256};
+
+use crate::{merkle::Hash, state::Poll};
+
+/// Current attestation format version.
+pub const ATTESTATION_VERSION: u8 = 1;
+
+/// Serialized size of a [`ResultAttestation`].
+pub const RESULT_ATTESTATION_LEN: usize = 1 + 32 + 32 + 32 + 8;
+
+const RESULT_DOMAIN: &[u8] = b"voting-result";
+
+/// The statement an attestor signs about a closed poll.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResultAttestation {
+    /// Format version, `ATTESTATION_VERSION`.
+    pub version: u8,
+    /// Voting program that holds the poll.
+    pub program_id: Pubkey,
+    /// Poll account the result belongs to.
+    pub poll: Pubkey,
+    /// `result_hash` of the closed poll.
+    pub result_hash: Hash,
+    /// Slot in which the poll was closed.
+    pub slot: u64,
+}
+
+impl ResultAttestation {
+    /// Attestation for `poll`, which must have been closed in `slot`.
+    pub fn new(program_id: &Pubkey, poll_key: &Pubkey, poll: &Poll, slot: u64) -> Self {
+        Self {
+            version: ATTESTATION_VERSION,
+            program_id: *program_id,
+            poll: *poll_key,
+            result_hash: result_hash(poll),
+            slot,
+        }
+    }
+
+    /// Bytes the attestor signs.
+    pub fn message(&self) -> Vec<u8> {
+        self.try_to_vec().expect("serializing to a Vec cannot fail")
+    }
+}
+
+/// Hash of a poll's question, options and final counts and weights.
+pub fn result_hash(poll: &Poll) -> Hash {
+    let serialize = "serializing to a Vec cannot fail";
+    Sha256::new()
+        .chain_update(RESULT_DOMAIN)
+        .chain_update(poll.question.try_to_vec().expect(serialize))
+        .chain_update(poll.options.try_to_vec().expect(serialize))
+        .chain_update(poll.vote_counts.try_to_vec().expect(serialize))
+        .chain_update(poll.vote_weights.try_to_vec().expect(serialize))
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PollBuilder;
+
+    #[test]
+    fn test_message_layout() {
+        let poll = PollBuilder::new().vote_counts(&[3, 1]).closed().build();
+        let attestation = ResultAttestation::new(
+            &Pubkey::new_from_array([0xAA; 32]),
+            &Pubkey::new_from_array([7; 32]),
+            &poll,
+            42,
+        );
+
+        let message = attestation.message();
+        assert_eq!(message.len(), RESULT_ATTESTATION_LEN);
+        assert_eq!(message[0], ATTESTATION_VERSION);
+        assert_eq!(&message[message.len() - 8..], &42u64.to_le_bytes());
+        assert_eq!(ResultAttestation::try_from_slice(&message).unwrap(), attestation);
+    }
+
+    #[test]
+    fn test_result_hash_covers_counts() {
+        let poll = PollBuilder::new().vote_counts(&[3, 1]).build();
+        let swapped = PollBuilder::new().vote_counts(&[1, 3]).build();
+        assert_ne!(result_hash(&poll), result_hash(&swapped));
+        // Only the outcome is hashed, not bookkeeping such as the closed flag.
+        assert_eq!(
+            result_hash(&poll),
+            result_hash(&PollBuilder::new().vote_counts(&[3, 1]).closed().build())
+        );
+    }
+}
@@ -0,0 +1,95 @@
+// src/budget.rs
+
+//! Budget allocation for budget polls.
+//!
+//! On a budget poll every voter splits the poll's budget, `points_per_voter`
+//! points, across options with `VoteCumulative`, and must allocate all of it.
+//! Each ballot is normalized to [`BUDGET_SCALE`] units before it is counted,
+//! so a ballot weighs the same whatever the budget, and the units go to each
+//! option in proportion to its points with [`apportion`]. The poll keeps the
+//! per-option sums in `vote_weights`, a whole number of `BUDGET_SCALE` per
+//! ballot, and the result is their average as a share of the budget,
+//! reported in basis points by [`budget_allocation`].
+//!
+//! Apportioning rounds by largest remainder, so a ballot's units and the
+//! result's basis points always add up exactly and every client computes
+//! the same figures.
+
+use crate::state::BPS_DENOMINATOR;
+
+/// Units one ballot's allocation is normalized to: a millionth of a basis
+/// point of the budget each.
+pub const BUDGET_SCALE: u128 = BPS_DENOMINATOR as u128 * 1_000_000;
+
+/// Average allocation of a budget poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetAllocation {
+    /// Ballots counted.
+    pub ballots: u64,
+    /// Share of the budget per option (parallel to `options`), in basis
+    /// points. Adds up to `BPS_DENOMINATOR`, or all zero without ballots.
+    pub allocations_bps: Vec<u16>,
+}
+
+/// Splits `units` among `shares` in proportion, by largest remainder: each
+/// share gets its exact quotient rounded down, and the units left over go
+/// one each to the largest remainders, ties to the lowest index. The parts
+/// add up to `units` unless every share is 0, when every part is 0.
+pub fn apportion(shares: &[u128], units: u128) -> Vec<u128> {
+    let total: u128 = shares.iter().sum();
+    if total == 0 {
+        return vec![0; shares.len()];
+    }
+    let mut parts: Vec<u128> = shares.iter().map(|&share| share * units / total).collect();
+    let mut order: Vec<usize> = (0..shares.len()).collect();
+    order.sort_by_key(|&index| (std::cmp::Reverse(shares[index] * units % total), index));
+    let left = units - parts.iter().sum::<u128>();
+    for &index in order.iter().take(left as usize) {
+        parts[index] += 1;
+    }
+    parts
+}
+
+/// Average allocation of a budget poll with per-option sums `vote_weights`.
+pub fn budget_allocation(vote_weights: &[u128]) -> BudgetAllocation {
+    let total: u128 = vote_weights.iter().sum();
+    BudgetAllocation {
+        ballots: (total / BUDGET_SCALE) as u64,
+        allocations_bps: apportion(vote_weights, u128::from(BPS_DENOMINATOR))
+            .into_iter()
+            .map(|bps| bps as u16)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apportion() {
+        // Thirds of ten: each gets 3 and the first remainder the last unit.
+        assert_eq!(apportion(&[1, 1, 1], 10), vec![4, 3, 3]);
+        // 7/12 and 5/12 of 100 units: 58.33 and 41.67.
+        assert_eq!(apportion(&[7, 5], 100), vec![58, 42]);
+        assert_eq!(apportion(&[0, 3, 0], 5), vec![0, 5, 0]);
+        assert_eq!(apportion(&[0, 0], 5), vec![0, 0]);
+        assert!(apportion(&[], 5).is_empty());
+    }
+
+    #[test]
+    fn test_budget_allocation() {
+        // One ballot giving everything to the first option, one splitting
+        // the budget evenly between the three.
+        let even = apportion(&[1, 1, 1], BUDGET_SCALE);
+        let weights: Vec<u128> =
+            even.iter().zip([BUDGET_SCALE, 0, 0]).map(|(even, all)| even + all).collect();
+        let result = budget_allocation(&weights);
+        assert_eq!(result.ballots, 2);
+        assert_eq!(result.allocations_bps, vec![6_667, 1_667, 1_666]);
+        assert_eq!(result.allocations_bps.iter().map(|&bps| u64::from(bps)).sum::<u64>(), 10_000);
+
+        let empty = budget_allocation(&[0, 0]);
+        assert_eq!(empty, BudgetAllocation { ballots: 0, allocations_bps: vec![0, 0] });
+    }
+}
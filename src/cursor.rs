@@ -0,0 +1,161 @@
+// src/cursor.rs
+
+//! Continuation cursors for work spread over many accounts.
+//!
+//! A single transaction can only pass so many accounts and spend so much
+//! compute, so work over every record of a poll is split into batches. Each
+//! batch advances a [`WorkCursor`] kept at a program address derived from the
+//! poll and the [`CursorTask`], and anyone may submit the next batch. Records
+//! must be passed in ascending address order across all batches: the cursor
+//! keeps the last address counted, so a record cannot be counted twice, and
+//! a pass over the records completes once as many were counted as the poll
+//! expects.
+//!
+//! A cursor keeps only running totals, never the records themselves, so its
+//! size depends on the poll's options rather than its records. Work that
+//! needs the records more than once, like the rounds of a count, takes one
+//! pass per round: the pass that completes a round resets the cursor, and
+//! the records are passed again from the lowest address.
+
+use arch_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    error::VotingError,
+    stv::StvCount,
+    tally::{runoff_choice, runoff_remaining},
+};
+
+/// Seed prefix of work cursor addresses.
+pub const CURSOR_SEED: &[u8] = b"cursor";
+
+/// Size allocated for a work cursor account, the largest a program can create.
+pub const MAX_CURSOR_ACCOUNT_LEN: usize = 10 * 1024;
+
+/// Work a cursor tracks. New tasks are appended, as the variant index is part
+/// of the cursor's address.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorTask {
+    /// Instant-runoff count of a ranked-choice poll's ballots, by `TallyBatch`.
+    Tally,
+}
+
+impl CursorTask {
+    /// Seed byte of this task in cursor addresses.
+    pub fn seed(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Derives the address of `poll`'s cursor for `task`, and its bump seed.
+///
+/// Cursors live at the program address for `["cursor", poll, task]`.
+pub fn find_cursor_address(program_id: &Pubkey, poll: &Pubkey, task: CursorTask) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CURSOR_SEED, poll.as_ref(), &[task.seed()]], program_id)
+}
+
+/// Rounds of a `Tally` task's count decided by earlier passes.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TallyRounds {
+    /// Instant runoff, with the options eliminated so far in order.
+    Runoff { eliminated: Vec<u32> },
+    /// Single transferable vote, for polls with more than one seat.
+    Stv(StvCount),
+}
+
+/// Progress of one task over a poll's records.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkCursor {
+    /// Poll the work is for.
+    pub poll: Pubkey,
+    pub task: CursorTask,
+    /// Records counted so far in this pass.
+    pub processed: u64,
+    /// Address of the last record counted in this pass; the next must sort
+    /// after it.
+    pub last_key: Option<Pubkey>,
+    /// Value counted for each option so far in this pass of a `Tally` task:
+    /// ballots on an instant runoff, `stv::VOTE_SCALE` units on a single
+    /// transferable vote.
+    pub tallies: Vec<u128>,
+    pub rounds: TallyRounds,
+}
+
+impl WorkCursor {
+    /// A cursor with nothing counted yet, for a poll with `option_count`
+    /// options and `seats` seats.
+    pub fn new(poll: Pubkey, task: CursorTask, option_count: usize, seats: u8) -> Self {
+        let rounds = if seats > 1 {
+            TallyRounds::Stv(StvCount::new(option_count))
+        } else {
+            TallyRounds::Runoff { eliminated: Vec::new() }
+        };
+        Self { poll, task, processed: 0, last_key: None, tallies: vec![0; option_count], rounds }
+    }
+
+    /// Moves the cursor past the record at `key`, which must sort after every
+    /// record counted so far.
+    pub fn advance(&mut self, key: &Pubkey) -> Result<(), ProgramError> {
+        if let Some(last_key) = &self.last_key {
+            if key.as_ref() <= last_key.as_ref() {
+                msg!("Record is out of order or already counted.");
+                return Err(VotingError::AccountMismatch.into());
+            }
+        }
+        self.last_key = Some(*key);
+        self.processed = self.processed.checked_add(1).ok_or(VotingError::Overflow)?;
+        Ok(())
+    }
+
+    /// Counts one ballot ranking `ranking` for the option holding it in
+    /// the current round, where `withdrawn` are the poll's withdrawn options.
+    pub fn add_ranking(&mut self, withdrawn: &[u32], ranking: &[u32]) {
+        let counted = match &self.rounds {
+            TallyRounds::Runoff { eliminated } => {
+                let remaining = runoff_remaining(self.tallies.len(), withdrawn, eliminated);
+                runoff_choice(ranking, &remaining).map(|option| (option, 1))
+            },
+            TallyRounds::Stv(count) => count.holder(withdrawn, ranking),
+        };
+        if let Some((option, value)) = counted {
+            self.tallies[option] += value;
+        }
+    }
+
+    /// Starts the next pass over the records, from the lowest address.
+    pub fn next_pass(&mut self) {
+        self.processed = 0;
+        self.last_key = None;
+        self.tallies.iter_mut().for_each(|tally| *tally = 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_advances_in_order() {
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let mut cursor = WorkCursor::new(key(9), CursorTask::Tally, 3, 1);
+        assert!(cursor.advance(&key(1)).is_ok());
+        assert!(cursor.advance(&key(3)).is_ok());
+        for repeated in [key(3), key(2)] {
+            assert_eq!(cursor.advance(&repeated), Err(VotingError::AccountMismatch.into()));
+        }
+        assert_eq!((cursor.processed, cursor.last_key), (2, Some(key(3))));
+
+        // Withdrawn and eliminated options pass ballots to their next choice.
+        cursor.rounds = TallyRounds::Runoff { eliminated: vec![2] };
+        for ranking in [&[0, 1][..], &[2, 1], &[1], &[2]] {
+            cursor.add_ranking(&[0], ranking);
+        }
+        assert_eq!(cursor.tallies, vec![0, 3, 0]);
+
+        // A new pass starts over from the lowest address.
+        cursor.next_pass();
+        assert_eq!((cursor.processed, cursor.last_key), (0, None));
+        assert!(cursor.advance(&key(1)).is_ok());
+        assert_eq!(cursor.tallies, vec![0; 3]);
+    }
+}
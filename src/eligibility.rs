@@ -0,0 +1,741 @@
+// src/eligibility.rs
+
+//! Who may cast ballots on a poll.
+//!
+//! A poll stores one [`EligibilityRule`], and every ballot-casting
+//! instruction runs it through [`check_eligibility`] before the ballot
+//! counts. Leaf rules check one kind of evidence: an allowlist proof carried
+//! in the instruction data, or accounts passed after the ballot's usual
+//! accounts (and after any weight account). Composite rules combine leaves,
+//! such as "token holder and on the allowlist".
+//!
+//! Each leaf that reads accounts takes a fixed number of them
+//! ([`EligibilityRule::account_count`]), and a ballot passes the accounts of
+//! every leaf in depth-first order, including leaves of an `AnyOf` the voter
+//! does not rely on; an unused account can be any account. A token holder
+//! passes the voter's token account for the mint. An NFT holder passes the
+//! voter's token account for the NFT and the NFT's metadata account, which
+//! names the collection the mint belongs to; the metadata program only marks
+//! a collection `verified` once the collection's authority has signed off, so
+//! an unverified entry proves nothing. A credential holder passes a
+//! [`Credential`] owned by the issuing program, and a registered voter their
+//! `VoterRecord`, which the ballot also counts toward the voter's
+//! participation.
+//!
+//! `Not` excludes voters, such as those an issuer has flagged. A failed
+//! check is only evidence of absence where the account checked is the one
+//! place the evidence could be, so `Not` takes only rules read from an
+//! address fixed by the voter: a registration, or a credential kept at the
+//! issuer's address for the voter ([`find_credential_address`]). A ballot
+//! passes that address whether or not an account exists there.
+
+use std::io::{self, Read, Write};
+
+use arch_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    error::VotingError,
+    merkle::{hash_leaf, verify_proof, Hash},
+    state::{find_voter_record_address, TokenBalance, VoterRecord, TOKEN_PROGRAM_ID},
+};
+
+/// Program owning the NFT metadata accounts that gated polls read.
+pub const METADATA_PROGRAM_ID: Pubkey =
+    Pubkey::new_from_array(*b"apl-metadata00000000000000000000");
+
+/// Most levels of nested rules, counting the outermost.
+pub const MAX_RULE_DEPTH: usize = 3;
+
+/// Most leaf rules in one poll's rule.
+pub const MAX_RULE_LEAVES: usize = 8;
+
+/// First bytes of every [`Credential`].
+pub const CREDENTIAL_TAG: [u8; 8] = *b"credntl1";
+
+/// Seed prefix of the credential addresses `Not` rules read.
+pub const CREDENTIAL_SEED: &[u8] = b"credential";
+
+/// Derives the address at which `issuer` keeps `subject`'s credential for
+/// `Not` rules, and its bump seed.
+///
+/// Credentials live at the `issuer` program's address for
+/// `["credential", subject]`.
+pub fn find_credential_address(issuer: &Pubkey, subject: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CREDENTIAL_SEED, subject.as_ref()], issuer)
+}
+
+/// A condition a voter must meet to cast a ballot.
+///
+/// The variant order is part of the poll layout: `Open` and `Allowlist`
+/// encode like the `Option<Hash>` allowlist root polls stored before rules
+/// existed, so those polls read unchanged. New rules are appended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EligibilityRule {
+    /// Anyone may vote.
+    Open,
+    /// The voter's key is under the Merkle root `root`, as shown by the
+    /// ballot's `eligibility_proof` from `merkle::hash_leaf` of the key.
+    Allowlist { root: Hash },
+    /// The voter holds at least `min_amount` of `mint`.
+    TokenHolder { mint: Pubkey, min_amount: u64 },
+    /// The voter holds an NFT verified as a member of `collection`.
+    NftCollection { collection: Pubkey },
+    /// The voter holds an unexpired `Credential` from the `issuer` program.
+    CredentialIssuer { issuer: Pubkey },
+    /// The voter meets every one of `rules`.
+    AllOf { rules: Vec<EligibilityRule> },
+    /// The voter meets at least one of `rules`.
+    AnyOf { rules: Vec<EligibilityRule> },
+    /// The voter has registered with `RegisterVoter`, shown by their
+    /// `VoterRecord`.
+    Registered,
+    /// The voter does not meet `rule`, which is `Registered` or
+    /// `CredentialIssuer`.
+    Not { rule: Box<EligibilityRule> },
+}
+
+// Borsh's derive cannot bound a type that contains itself, so rules encode
+// by hand the way it would: the variant's index as one byte, then its fields.
+impl BorshSerialize for EligibilityRule {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            EligibilityRule::Open => 0u8.serialize(writer),
+            EligibilityRule::Allowlist { root } => {
+                1u8.serialize(writer)?;
+                root.serialize(writer)
+            },
+            EligibilityRule::TokenHolder { mint, min_amount } => {
+                2u8.serialize(writer)?;
+                mint.serialize(writer)?;
+                min_amount.serialize(writer)
+            },
+            EligibilityRule::NftCollection { collection } => {
+                3u8.serialize(writer)?;
+                collection.serialize(writer)
+            },
+            EligibilityRule::CredentialIssuer { issuer } => {
+                4u8.serialize(writer)?;
+                issuer.serialize(writer)
+            },
+            EligibilityRule::AllOf { rules } => {
+                5u8.serialize(writer)?;
+                rules.serialize(writer)
+            },
+            EligibilityRule::AnyOf { rules } => {
+                6u8.serialize(writer)?;
+                rules.serialize(writer)
+            },
+            EligibilityRule::Registered => 7u8.serialize(writer),
+            EligibilityRule::Not { rule } => {
+                8u8.serialize(writer)?;
+                rule.serialize(writer)
+            },
+        }
+    }
+}
+
+impl BorshDeserialize for EligibilityRule {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let rule = match u8::deserialize_reader(reader)? {
+            0 => EligibilityRule::Open,
+            1 => EligibilityRule::Allowlist { root: Hash::deserialize_reader(reader)? },
+            2 => EligibilityRule::TokenHolder {
+                mint: Pubkey::deserialize_reader(reader)?,
+                min_amount: u64::deserialize_reader(reader)?,
+            },
+            3 => EligibilityRule::NftCollection { collection: Pubkey::deserialize_reader(reader)? },
+            4 => EligibilityRule::CredentialIssuer { issuer: Pubkey::deserialize_reader(reader)? },
+            5 => EligibilityRule::AllOf { rules: Vec::deserialize_reader(reader)? },
+            6 => EligibilityRule::AnyOf { rules: Vec::deserialize_reader(reader)? },
+            7 => EligibilityRule::Registered,
+            8 => EligibilityRule::Not { rule: Box::new(Self::deserialize_reader(reader)?) },
+            index => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unexpected variant index: {}", index),
+                ))
+            },
+        };
+        Ok(rule)
+    }
+}
+
+impl EligibilityRule {
+    /// Accounts a ballot passes for this rule.
+    pub fn account_count(&self) -> usize {
+        match self {
+            EligibilityRule::Open | EligibilityRule::Allowlist { .. } => 0,
+            EligibilityRule::TokenHolder { .. }
+            | EligibilityRule::CredentialIssuer { .. }
+            | EligibilityRule::Registered => 1,
+            EligibilityRule::NftCollection { .. } => 2,
+            EligibilityRule::AllOf { rules } | EligibilityRule::AnyOf { rules } => {
+                rules.iter().map(EligibilityRule::account_count).sum()
+            },
+            EligibilityRule::Not { rule } => rule.account_count(),
+        }
+    }
+
+    /// Fails if a composite rule is empty, `Not` negates a rule it does not
+    /// take, or the rule exceeds `MAX_RULE_DEPTH` or `MAX_RULE_LEAVES`.
+    pub fn validate(&self) -> ProgramResult {
+        let mut leaves = 0;
+        self.validate_at(1, &mut leaves)?;
+        if leaves > MAX_RULE_LEAVES {
+            msg!("Eligibility rule has {} parts; at most {} allowed.", leaves, MAX_RULE_LEAVES);
+            return Err(VotingError::InvalidEligibilityRule.into());
+        }
+        Ok(())
+    }
+
+    fn validate_at(&self, depth: usize, leaves: &mut usize) -> ProgramResult {
+        if depth > MAX_RULE_DEPTH {
+            msg!("Eligibility rule nests more than {} levels.", MAX_RULE_DEPTH);
+            return Err(VotingError::InvalidEligibilityRule.into());
+        }
+        match self {
+            EligibilityRule::AllOf { rules } | EligibilityRule::AnyOf { rules } => {
+                if rules.is_empty() {
+                    msg!("Eligibility rule combines no rules.");
+                    return Err(VotingError::InvalidEligibilityRule.into());
+                }
+                rules.iter().try_for_each(|rule| rule.validate_at(depth + 1, leaves))
+            },
+            EligibilityRule::Not { rule } => match **rule {
+                EligibilityRule::Registered | EligibilityRule::CredentialIssuer { .. } => {
+                    rule.validate_at(depth + 1, leaves)
+                },
+                _ => {
+                    msg!("Only registration and credential rules can be negated.");
+                    Err(VotingError::InvalidEligibilityRule.into())
+                },
+            },
+            _ => {
+                *leaves += 1;
+                Ok(())
+            },
+        }
+    }
+}
+
+/// A credential an issuer program grants a voter, such as proof of
+/// membership or of an identity check.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Credential {
+    /// `CREDENTIAL_TAG`.
+    pub tag: [u8; 8],
+    /// Voter the credential was issued to.
+    pub subject: Pubkey,
+    /// Last slot in which the credential is valid; `None` if it does not
+    /// expire.
+    pub expiry_slot: Option<u64>,
+}
+
+/// The collection an NFT claims to belong to.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Collection {
+    /// Whether the collection's authority has confirmed the membership.
+    pub verified: bool,
+    /// Mint of the collection NFT.
+    pub key: Pubkey,
+}
+
+/// The leading fields of an NFT metadata account that a holder check reads.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NftMetadata {
+    /// Mint the metadata describes.
+    pub mint: Pubkey,
+    /// Collection the NFT belongs to, if any.
+    pub collection: Option<Collection>,
+}
+
+/// Fails unless `voter` meets `rule` in `slot`.
+///
+/// `proof` is the ballot's allowlist proof and `accounts` the accounts passed
+/// for the rule, exactly `rule.account_count()` of them. `program_id` is the
+/// voting program, which owns voter records.
+pub fn check_eligibility(
+    program_id: &Pubkey,
+    rule: &EligibilityRule,
+    voter: &Pubkey,
+    proof: &[Hash],
+    accounts: &[AccountInfo],
+    slot: u64,
+) -> ProgramResult {
+    if accounts.len() != rule.account_count() {
+        msg!("Expected {} eligibility accounts, got {}.", rule.account_count(), accounts.len());
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    match rule {
+        EligibilityRule::Open => Ok(()),
+        EligibilityRule::Allowlist { root } => {
+            if verify_proof(root, &hash_leaf(voter.as_ref()), proof) {
+                Ok(())
+            } else {
+                msg!("Voter is not on the poll's allowlist.");
+                Err(VotingError::NotEligible.into())
+            }
+        },
+        EligibilityRule::TokenHolder { mint, min_amount } => {
+            ensure_token_balance(mint, *min_amount, voter, &accounts[0])
+        },
+        EligibilityRule::NftCollection { collection } => {
+            ensure_collection_holder(collection, voter, &accounts[0], &accounts[1])
+        },
+        EligibilityRule::CredentialIssuer { issuer } => {
+            ensure_credential(issuer, voter, &accounts[0], slot)
+        },
+        EligibilityRule::AllOf { rules } => {
+            let mut rest = accounts;
+            for rule in rules {
+                let (own, others) = rest.split_at(rule.account_count());
+                check_eligibility(program_id, rule, voter, proof, own, slot)?;
+                rest = others;
+            }
+            Ok(())
+        },
+        EligibilityRule::AnyOf { rules } => {
+            let mut rest = accounts;
+            for rule in rules {
+                let (own, others) = rest.split_at(rule.account_count());
+                if check_eligibility(program_id, rule, voter, proof, own, slot).is_ok() {
+                    return Ok(());
+                }
+                rest = others;
+            }
+            msg!("Voter meets none of the poll's alternative rules.");
+            Err(VotingError::NotEligible.into())
+        },
+        EligibilityRule::Registered => ensure_registered(program_id, voter, &accounts[0]),
+        EligibilityRule::Not { rule } => {
+            ensure_excluded(program_id, rule, voter, &accounts[0], slot)
+        },
+    }
+}
+
+/// Fails unless `account`, at the address fixed for `voter` by `rule`, shows
+/// that `voter` does not meet `rule`.
+fn ensure_excluded(
+    program_id: &Pubkey,
+    rule: &EligibilityRule,
+    voter: &Pubkey,
+    account: &AccountInfo,
+    slot: u64,
+) -> ProgramResult {
+    let met = match rule {
+        EligibilityRule::Registered => ensure_registered(program_id, voter, account),
+        EligibilityRule::CredentialIssuer { issuer } => {
+            if *account.key != find_credential_address(issuer, voter).0 {
+                msg!("Credential account is not the issuer's address for this voter.");
+                return Err(VotingError::AccountMismatch.into());
+            }
+            if account.owner != issuer {
+                return Ok(());
+            }
+            ensure_credential(issuer, voter, account, slot)
+        },
+        _ => {
+            msg!("Only registration and credential rules can be negated.");
+            return Err(VotingError::InvalidEligibilityRule.into());
+        },
+    };
+    // Only a rule the evidence shows unmet counts; a malformed account fails
+    // the ballot rather than passing as proof of absence.
+    match met {
+        Ok(()) => {
+            msg!("Voter meets a rule that excludes them from the poll.");
+            Err(VotingError::NotEligible.into())
+        },
+        Err(error) if error == VotingError::NotEligible.into() => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Fails unless `token_account` is `voter`'s account for `mint`, holding at
+/// least `min_amount`.
+pub fn ensure_token_balance(
+    mint: &Pubkey,
+    min_amount: u64,
+    voter: &Pubkey,
+    token_account: &AccountInfo,
+) -> ProgramResult {
+    let balance = read_token_account(voter, token_account)?;
+    if balance.mint != *mint {
+        msg!("Token account is not for the poll's gating mint.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if balance.amount < min_amount {
+        msg!("Voter holds {} tokens; {} required.", balance.amount, min_amount);
+        return Err(VotingError::InsufficientTokenBalance.into());
+    }
+    Ok(())
+}
+
+/// Fails unless `token_account` is `voter`'s account holding an NFT whose
+/// `metadata_account` places it in the verified `collection`.
+pub fn ensure_collection_holder(
+    collection: &Pubkey,
+    voter: &Pubkey,
+    token_account: &AccountInfo,
+    metadata_account: &AccountInfo,
+) -> ProgramResult {
+    let balance = read_token_account(voter, token_account)?;
+    if balance.amount == 0 {
+        msg!("Voter does not hold the NFT.");
+        return Err(VotingError::NotEligible.into());
+    }
+
+    if *metadata_account.owner != METADATA_PROGRAM_ID {
+        msg!("Metadata account is not owned by the metadata program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // Metadata accounts carry more fields after these; only the prefix is read.
+    let metadata = NftMetadata::deserialize(&mut &metadata_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if metadata.mint != balance.mint {
+        msg!("Metadata account is not for the held NFT.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    match metadata.collection {
+        Some(Collection { verified: true, key }) if key == *collection => Ok(()),
+        _ => {
+            msg!("NFT is not a verified member of the poll's collection.");
+            Err(VotingError::NotEligible.into())
+        },
+    }
+}
+
+/// Fails unless `credential_account` holds an unexpired credential the
+/// `issuer` program granted `voter`.
+pub fn ensure_credential(
+    issuer: &Pubkey,
+    voter: &Pubkey,
+    credential_account: &AccountInfo,
+    slot: u64,
+) -> ProgramResult {
+    if *credential_account.owner != *issuer {
+        msg!("Credential is not owned by the poll's issuer.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let credential = Credential::deserialize(&mut &credential_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if credential.tag != CREDENTIAL_TAG {
+        msg!("Account is not a credential.");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if credential.subject != *voter {
+        msg!("Credential was issued to someone else.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if let Some(expiry_slot) = credential.expiry_slot.filter(|&expiry_slot| slot > expiry_slot) {
+        msg!("Credential expired in slot {}.", expiry_slot);
+        return Err(VotingError::NotEligible.into());
+    }
+    Ok(())
+}
+
+/// Fails unless `record_account` is `voter`'s `VoterRecord`.
+pub fn ensure_registered(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    record_account: &AccountInfo,
+) -> ProgramResult {
+    if *record_account.key != find_voter_record_address(program_id, voter).0 {
+        msg!("Voter record account is not the address for this voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if record_account.owner != program_id {
+        msg!("Voter has not registered.");
+        return Err(VotingError::NotEligible.into());
+    }
+    VoterRecord::deserialize(&mut &record_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(())
+}
+
+/// Reads `voter`'s token account.
+fn read_token_account(
+    voter: &Pubkey,
+    token_account: &AccountInfo,
+) -> Result<TokenBalance, ProgramError> {
+    if *token_account.owner != TOKEN_PROGRAM_ID {
+        msg!("Token account is not owned by the token program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let balance = TokenBalance::unpack(&token_account.data.borrow())
+        .ok_or(ProgramError::InvalidAccountData)?;
+    if balance.owner != *voter {
+        msg!("Token account is not the voter's.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    Ok(balance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{account_info, nft_metadata_data, token_account_data};
+
+    #[test]
+    fn test_composite_rules() {
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let (voter, mint, issuer) = (key(1), key(2), key(6));
+        let rules = || {
+            vec![
+                EligibilityRule::TokenHolder { mint, min_amount: 10 },
+                EligibilityRule::CredentialIssuer { issuer },
+            ]
+        };
+        let credential = |subject: Pubkey, expiry_slot: Option<u64>| {
+            Credential { tag: CREDENTIAL_TAG, subject, expiry_slot }.try_to_vec().unwrap()
+        };
+        // Checks `rule` in slot 7 against a token account holding `amount` and
+        // the credential, in that order.
+        let check = |rule: &EligibilityRule, amount: u64, credential: Vec<u8>, proof: &[Hash]| {
+            let mut token_data = token_account_data(&mint, &voter, amount);
+            let mut credential_data = credential;
+            let accounts = [
+                account_info(&mint, &TOKEN_PROGRAM_ID, false, &mut token_data),
+                account_info(&issuer, &issuer, false, &mut credential_data),
+            ];
+            let accounts = &accounts[..rule.account_count()];
+            check_eligibility(&key(9), rule, &voter, proof, accounts, 7)
+        };
+
+        let all_of = EligibilityRule::AllOf { rules: rules() };
+        assert!(check(&all_of, 10, credential(voter, Some(7)), &[]).is_ok());
+        assert_eq!(
+            check(&all_of, 9, credential(voter, None), &[]),
+            Err(VotingError::InsufficientTokenBalance.into())
+        );
+        assert_eq!(
+            check(&all_of, 10, credential(voter, Some(6)), &[]),
+            Err(VotingError::NotEligible.into())
+        );
+
+        let any_of = EligibilityRule::AnyOf { rules: rules() };
+        assert!(check(&any_of, 0, credential(voter, None), &[]).is_ok());
+        assert!(check(&any_of, 10, credential(key(3), None), &[]).is_ok());
+        assert_eq!(
+            check(&any_of, 0, credential(key(3), None), &[]),
+            Err(VotingError::NotEligible.into())
+        );
+
+        // Nested rules share the ballot's allowlist proof.
+        let leaves = [hash_leaf(voter.as_ref()), hash_leaf(key(3).as_ref())];
+        let root = crate::merkle::compute_root(&leaves).unwrap();
+        let nested =
+            EligibilityRule::AllOf { rules: vec![EligibilityRule::Allowlist { root }, any_of] };
+        assert_eq!(nested.account_count(), 2);
+        assert!(check(&nested, 0, credential(voter, None), &leaves[1..]).is_ok());
+        assert_eq!(
+            check(&nested, 0, credential(voter, None), &[]),
+            Err(VotingError::NotEligible.into())
+        );
+    }
+
+    #[test]
+    fn test_rules_read_legacy_allowlists() {
+        let root = [7u8; 32];
+        let legacy = |root: Option<Hash>| root.try_to_vec().unwrap();
+        assert_eq!(EligibilityRule::Open.try_to_vec().unwrap(), legacy(None));
+        assert_eq!(EligibilityRule::Allowlist { root }.try_to_vec().unwrap(), legacy(Some(root)));
+    }
+
+    #[test]
+    fn test_rule_limits() {
+        let nest = |rule: EligibilityRule| EligibilityRule::AllOf { rules: vec![rule] };
+        assert!(nest(nest(EligibilityRule::Open)).validate().is_ok());
+        let open =
+            |count: usize| EligibilityRule::AnyOf { rules: vec![EligibilityRule::Open; count] };
+        assert!(open(8).validate().is_ok());
+        for rule in [
+            nest(nest(nest(EligibilityRule::Open))),
+            open(9),
+            nest(EligibilityRule::AnyOf { rules: Vec::new() }),
+        ] {
+            assert_eq!(rule.validate(), Err(VotingError::InvalidEligibilityRule.into()));
+        }
+    }
+
+    #[test]
+    fn test_negated_rules() {
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let (program_id, voter, flagger, mint) = (key(9), key(1), key(6), key(2));
+        let flagged = || EligibilityRule::Not {
+            rule: Box::new(EligibilityRule::CredentialIssuer { issuer: flagger }),
+        };
+        let not = |rule: EligibilityRule| EligibilityRule::Not { rule: Box::new(rule) };
+
+        // "(member OR token >= 100) AND NOT flagged" fits the limits.
+        let rule = EligibilityRule::AllOf {
+            rules: vec![
+                EligibilityRule::AnyOf {
+                    rules: vec![
+                        EligibilityRule::Registered,
+                        EligibilityRule::TokenHolder { mint, min_amount: 100 },
+                    ],
+                },
+                flagged(),
+            ],
+        };
+        assert!(rule.validate().is_ok());
+        assert_eq!(rule.account_count(), 3);
+        for rule in [
+            not(EligibilityRule::Open),
+            not(EligibilityRule::TokenHolder { mint, min_amount: 1 }),
+            not(flagged()),
+            not(EligibilityRule::AnyOf { rules: vec![EligibilityRule::Registered] }),
+        ] {
+            assert_eq!(rule.validate(), Err(VotingError::InvalidEligibilityRule.into()));
+        }
+
+        // Only the issuer's address for the voter shows the voter unflagged.
+        let (credential_key, _) = find_credential_address(&flagger, &voter);
+        let check = |rule: &EligibilityRule, key: &Pubkey, owner: &Pubkey, mut data: Vec<u8>| {
+            let accounts = [account_info(key, owner, false, &mut data)];
+            check_eligibility(&program_id, rule, &voter, &[], &accounts, 7)
+        };
+        let system = Pubkey::default();
+        assert!(check(&flagged(), &credential_key, &system, Vec::new()).is_ok());
+        assert_eq!(
+            check(&flagged(), &key(3), &system, Vec::new()),
+            Err(VotingError::AccountMismatch.into())
+        );
+        let credential = |expiry_slot: Option<u64>| {
+            Credential { tag: CREDENTIAL_TAG, subject: voter, expiry_slot }.try_to_vec().unwrap()
+        };
+        assert_eq!(
+            check(&flagged(), &credential_key, &flagger, credential(None)),
+            Err(VotingError::NotEligible.into())
+        );
+        assert!(check(&flagged(), &credential_key, &flagger, credential(Some(6))).is_ok());
+        assert_eq!(
+            check(&flagged(), &credential_key, &flagger, vec![0u8; 8]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let unregistered = not(EligibilityRule::Registered);
+        let (record_key, _) = find_voter_record_address(&program_id, &voter);
+        let record = VoterRecord {
+            voter,
+            registered_at: 0,
+            registered_slot: 0,
+            polls_participated: 0,
+            delegate: None,
+        };
+        assert!(check(&unregistered, &record_key, &system, Vec::new()).is_ok());
+        assert_eq!(
+            check(&unregistered, &record_key, &program_id, record.try_to_vec().unwrap()),
+            Err(VotingError::NotEligible.into())
+        );
+    }
+
+    #[test]
+    fn test_collection_holder() {
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let (voter, mint, collection) = (key(1), key(2), key(3));
+        let check = |token_data: Vec<u8>, metadata_data: Vec<u8>| {
+            let (mut token_data, mut metadata_data) = (token_data, metadata_data);
+            let token_account = account_info(&mint, &TOKEN_PROGRAM_ID, false, &mut token_data);
+            let metadata_account =
+                account_info(&mint, &METADATA_PROGRAM_ID, false, &mut metadata_data);
+            ensure_collection_holder(&collection, &voter, &token_account, &metadata_account)
+        };
+
+        let holding = || token_account_data(&mint, &voter, 1);
+        assert!(check(holding(), nft_metadata_data(&mint, &collection, true)).is_ok());
+        // Unverified or foreign collections, and NFTs the voter no longer holds.
+        assert_eq!(
+            check(holding(), nft_metadata_data(&mint, &collection, false)),
+            Err(VotingError::NotEligible.into())
+        );
+        assert_eq!(
+            check(holding(), nft_metadata_data(&mint, &key(4), true)),
+            Err(VotingError::NotEligible.into())
+        );
+        assert_eq!(
+            check(
+                token_account_data(&mint, &voter, 0),
+                nft_metadata_data(&mint, &collection, true)
+            ),
+            Err(VotingError::NotEligible.into())
+        );
+        // The metadata must describe the held mint.
+        assert_eq!(
+            check(holding(), nft_metadata_data(&key(5), &collection, true)),
+            Err(VotingError::AccountMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_token_balance() {
+        let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+        let (voter, mint) = (key(1), key(2));
+        let check = |owner: &Pubkey, data: Vec<u8>, min_amount: u64| {
+            let mut data = data;
+            let token_account = account_info(&mint, owner, false, &mut data);
+            ensure_token_balance(&mint, min_amount, &voter, &token_account)
+        };
+
+        assert!(check(&TOKEN_PROGRAM_ID, token_account_data(&mint, &voter, 100), 100).is_ok());
+        assert_eq!(
+            check(&TOKEN_PROGRAM_ID, token_account_data(&mint, &voter, 99), 100),
+            Err(VotingError::InsufficientTokenBalance.into())
+        );
+        // The account must be the voter's, for the gating mint, and a token account.
+        for (owner, data) in [
+            (TOKEN_PROGRAM_ID, token_account_data(&key(3), &voter, 100)),
+            (TOKEN_PROGRAM_ID, token_account_data(&mint, &key(3), 100)),
+        ] {
+            assert_eq!(check(&owner, data, 1), Err(VotingError::AccountMismatch.into()));
+        }
+        assert_eq!(
+            check(&METADATA_PROGRAM_ID, token_account_data(&mint, &voter, 100), 1),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_rule_encoding() {
+        let issuer = Pubkey::new_from_array([6; 32]);
+        let rule = EligibilityRule::AllOf {
+            rules: vec![
+                EligibilityRule::Allowlist { root: [9; 32] },
+                EligibilityRule::AnyOf {
+                    rules: vec![
+                        EligibilityRule::Registered,
+                        EligibilityRule::Not {
+                            rule: Box::new(EligibilityRule::CredentialIssuer { issuer }),
+                        },
+                    ],
+                },
+            ],
+        };
+
+        // Variant index, then fields; vectors carry a u32 length.
+        let mut expected = vec![5, 2, 0, 0, 0, 1];
+        expected.extend_from_slice(&[9; 32]);
+        expected.extend_from_slice(&[6, 2, 0, 0, 0, 7, 8, 4]);
+        expected.extend_from_slice(issuer.as_ref());
+        let data = rule.try_to_vec().unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(EligibilityRule::try_from_slice(&data).unwrap(), rule);
+
+        // `Open` and `Allowlist` still read as the allowlist root polls kept.
+        let root = Some([9u8; 32]).try_to_vec().unwrap();
+        assert_eq!(
+            EligibilityRule::try_from_slice(&root).unwrap(),
+            EligibilityRule::Allowlist { root: [9; 32] }
+        );
+        assert_eq!(None::<Hash>.try_to_vec().unwrap(), EligibilityRule::Open.try_to_vec().unwrap());
+
+        // Unknown variants and truncated rules do not decode.
+        assert!(EligibilityRule::try_from_slice(&[9]).is_err());
+        assert!(EligibilityRule::try_from_slice(&data[..data.len() - 1]).is_err());
+    }
+}
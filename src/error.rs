@@ -0,0 +1,265 @@
+// src/error.rs
+
+use arch_program::program_error::ProgramError;
+
+/// Errors returned by the voting program.
+///
+/// Each variant is returned as `ProgramError::Custom(code)`. The codes are
+/// stable: append new variants instead of reordering existing ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VotingError {
+    /// The voter has already voted in this poll.
+    DoubleVote = 0,
+    /// A vote count would overflow.
+    Overflow = 1,
+    /// The poll question, options or account exceed the size limits.
+    PollTooLarge = 2,
+    /// An account does not match what the instruction expects.
+    AccountMismatch = 3,
+    /// The poll is closed.
+    PollClosed = 4,
+    /// The current time is outside the poll's voting window.
+    VotingNotActive = 5,
+    /// The option index does not exist in the poll.
+    InvalidOption = 6,
+    /// The signer is not allowed to perform this action.
+    Unauthorized = 7,
+    /// The poll cannot be closed before its voting period ends.
+    VotingStillActive = 8,
+    /// Options can no longer change because a ballot has been recorded.
+    OptionsFrozen = 9,
+    /// The voter has no recorded vote that can be changed.
+    NoVoteToChange = 10,
+    /// The poll was cancelled by its creator.
+    PollCancelled = 11,
+    /// The poll already has votes, so it can no longer be cancelled.
+    VotesAlreadyCast = 12,
+    /// The poll account data fails its integrity checks.
+    CorruptPollState = 13,
+    /// A moderator has quarantined the poll.
+    PollQuarantined = 14,
+    /// The poll's voting window has already opened.
+    PollAlreadyStarted = 15,
+    /// The voting window ends before it starts.
+    InvalidVotingWindow = 16,
+    /// The poll does not accept new options.
+    NewOptionsNotAllowed = 17,
+    /// An option with the same label already exists.
+    DuplicateOption = 18,
+    /// The poll has not been closed yet.
+    PollNotFinalized = 19,
+    /// The attestor has already submitted an attestation for this poll.
+    AlreadyAttested = 20,
+    /// The instruction does not apply to the poll's voting mode.
+    WrongPollType = 21,
+    /// The poll's ballots have already been tallied.
+    AlreadyTallied = 22,
+    /// The option has been withdrawn by its candidate.
+    OptionWithdrawn = 23,
+    /// A threshold is outside 0..=10_000 basis points.
+    InvalidThreshold = 24,
+    /// Too many ballots were spoiled and the poll was voided.
+    PollVoid = 25,
+    /// The voter's token account holds no tokens of the poll's weight mint.
+    NoVotingWeight = 26,
+    /// The ballot selects more options than the poll allows.
+    TooManySelections = 27,
+    /// The poll has terms the voter did not accept.
+    TermsNotAccepted = 28,
+    /// The voter weight record has expired.
+    VoterWeightExpired = 29,
+    /// The question is already registered to one of the creator's polls.
+    DuplicateQuestion = 30,
+    /// The poll's outcome has already been recorded.
+    AlreadyFinalized = 31,
+    /// The poll already has a snapshot with this label.
+    SnapshotExists = 32,
+    /// The voter is not on the poll's allowlist or holds no NFT of its collection.
+    NotEligible = 33,
+    /// The voter holds less of the poll's gating mint than it requires.
+    InsufficientTokenBalance = 34,
+    /// The poll's eligibility rule is empty, too deep or has too many parts.
+    InvalidEligibilityRule = 35,
+    /// The voter already has a voter record.
+    AlreadyRegistered = 36,
+    /// A voter named themselves as their own delegate.
+    InvalidDelegate = 37,
+    /// A delegation chain passed with a vote does not lead to the voter.
+    InvalidDelegationChain = 38,
+    /// A poll's weight cap is zero or below its weight floor.
+    InvalidWeightCurve = 39,
+    /// A cumulative ballot allocates more points than the poll gives each voter.
+    PointBudgetExceeded = 40,
+    /// The program config is in read-only mode and only administrative
+    /// instructions signed by its admin run.
+    ReadOnlyMode = 41,
+    /// The instruction was reached in a way its `CallPolicy` forbids: through
+    /// CPI when it must be top-level, or other than through CPI from an
+    /// allowlisted program.
+    CallerNotAllowed = 42,
+    /// A budget ballot allocates less than the whole budget.
+    BudgetNotAllocated = 43,
+    /// A vote that also cast delegators' ballots cannot be changed.
+    DelegatedVoteLocked = 44,
+}
+
+impl VotingError {
+    /// Every variant, in code order.
+    pub const ALL: [VotingError; 45] = [
+        VotingError::DoubleVote,
+        VotingError::Overflow,
+        VotingError::PollTooLarge,
+        VotingError::AccountMismatch,
+        VotingError::PollClosed,
+        VotingError::VotingNotActive,
+        VotingError::InvalidOption,
+        VotingError::Unauthorized,
+        VotingError::VotingStillActive,
+        VotingError::OptionsFrozen,
+        VotingError::NoVoteToChange,
+        VotingError::PollCancelled,
+        VotingError::VotesAlreadyCast,
+        VotingError::CorruptPollState,
+        VotingError::PollQuarantined,
+        VotingError::PollAlreadyStarted,
+        VotingError::InvalidVotingWindow,
+        VotingError::NewOptionsNotAllowed,
+        VotingError::DuplicateOption,
+        VotingError::PollNotFinalized,
+        VotingError::AlreadyAttested,
+        VotingError::WrongPollType,
+        VotingError::AlreadyTallied,
+        VotingError::OptionWithdrawn,
+        VotingError::InvalidThreshold,
+        VotingError::PollVoid,
+        VotingError::NoVotingWeight,
+        VotingError::TooManySelections,
+        VotingError::TermsNotAccepted,
+        VotingError::VoterWeightExpired,
+        VotingError::DuplicateQuestion,
+        VotingError::AlreadyFinalized,
+        VotingError::SnapshotExists,
+        VotingError::NotEligible,
+        VotingError::InsufficientTokenBalance,
+        VotingError::InvalidEligibilityRule,
+        VotingError::AlreadyRegistered,
+        VotingError::InvalidDelegate,
+        VotingError::InvalidDelegationChain,
+        VotingError::InvalidWeightCurve,
+        VotingError::PointBudgetExceeded,
+        VotingError::ReadOnlyMode,
+        VotingError::CallerNotAllowed,
+        VotingError::BudgetNotAllocated,
+        VotingError::DelegatedVoteLocked,
+    ];
+
+    /// The stable numeric code carried in `ProgramError::Custom`.
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Looks up the error with the given code.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    /// Decodes an error returned by this program, for use by clients.
+    pub fn decode(error: &ProgramError) -> Option<Self> {
+        match error {
+            ProgramError::Custom(code) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+
+    /// Short description suitable for end users.
+    pub fn message(self) -> &'static str {
+        match self {
+            VotingError::DoubleVote => "You already voted in this poll",
+            VotingError::Overflow => "Vote count overflow",
+            VotingError::PollTooLarge => "Poll exceeds the size limits",
+            VotingError::AccountMismatch => "Unexpected account passed to the instruction",
+            VotingError::PollClosed => "This poll is closed",
+            VotingError::VotingNotActive => "Voting is not open for this poll",
+            VotingError::InvalidOption => "That option does not exist in this poll",
+            VotingError::Unauthorized => "You are not allowed to do this",
+            VotingError::VotingStillActive => "Voting is still in progress",
+            VotingError::OptionsFrozen => "Options cannot change once voting has begun",
+            VotingError::NoVoteToChange => "You have no vote in this poll to change",
+            VotingError::PollCancelled => "This poll was cancelled",
+            VotingError::VotesAlreadyCast => "Votes have already been cast in this poll",
+            VotingError::CorruptPollState => "This poll's data is corrupt",
+            VotingError::PollQuarantined => "This poll has been quarantined by a moderator",
+            VotingError::PollAlreadyStarted => "Voting has already started for this poll",
+            VotingError::InvalidVotingWindow => "The poll must end after it starts",
+            VotingError::NewOptionsNotAllowed => "This poll does not accept new options",
+            VotingError::DuplicateOption => "That option already exists in this poll",
+            VotingError::PollNotFinalized => "This poll has not been finalized yet",
+            VotingError::AlreadyAttested => "This attestor has already attested the poll's result",
+            VotingError::WrongPollType => "This poll uses a different voting mode",
+            VotingError::AlreadyTallied => "This poll has already been tallied",
+            VotingError::OptionWithdrawn => "That option has been withdrawn",
+            VotingError::InvalidThreshold => {
+                "The threshold must be between 0 and 10000 basis points"
+            },
+            VotingError::PollVoid => "This poll was voided by spoiled ballots",
+            VotingError::NoVotingWeight => "You hold no tokens of this poll's voting mint",
+            VotingError::TooManySelections => {
+                "This ballot selects more options than the poll allows"
+            },
+            VotingError::TermsNotAccepted => "You must accept this poll's terms to vote",
+            VotingError::VoterWeightExpired => "The voter weight record has expired",
+            VotingError::DuplicateQuestion => "The creator already has a poll with this question",
+            VotingError::AlreadyFinalized => "This poll has already been finalized",
+            VotingError::SnapshotExists => "This poll already has a snapshot with this label",
+            VotingError::NotEligible => "You are not eligible to vote on this poll",
+            VotingError::InsufficientTokenBalance => {
+                "You hold fewer tokens than this poll requires to vote"
+            },
+            VotingError::InvalidEligibilityRule => "This poll's eligibility rule is malformed",
+            VotingError::AlreadyRegistered => "You are already registered as a voter",
+            VotingError::InvalidDelegate => "You cannot delegate your vote to yourself",
+            VotingError::InvalidDelegationChain => {
+                "A delegation chain passed with this vote does not lead to you"
+            },
+            VotingError::InvalidWeightCurve => {
+                "This poll's weight cap must be above zero and its weight floor"
+            },
+            VotingError::PointBudgetExceeded => "Your ballot allocates more points than you have",
+            VotingError::ReadOnlyMode => "Voting is not open on this deployment yet",
+            VotingError::CallerNotAllowed => "This action cannot be called from here",
+            VotingError::BudgetNotAllocated => "Your ballot must allocate the whole budget",
+            VotingError::DelegatedVoteLocked => {
+                "Your vote also cast delegated ballots and cannot be changed"
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for VotingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<VotingError> for ProgramError {
+    fn from(error: VotingError) -> Self {
+        ProgramError::Custom(error.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        for (code, error) in VotingError::ALL.iter().enumerate() {
+            assert_eq!(error.code(), code as u32);
+            assert_eq!(VotingError::decode(&(*error).into()), Some(*error));
+        }
+        assert_eq!(ProgramError::from(VotingError::DoubleVote), ProgramError::Custom(0));
+        assert_eq!(VotingError::from_code(VotingError::ALL.len() as u32), None);
+        assert_eq!(VotingError::decode(&ProgramError::InvalidArgument), None);
+        assert_eq!(VotingError::DoubleVote.to_string(), "You already voted in this poll");
+    }
+}
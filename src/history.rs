@@ -0,0 +1,183 @@
+// src/history.rs
+
+//! Comparison of results across recurring polls.
+//!
+//! A poll that is run again, or recreated with its options reworded, added
+//! or reordered, gets a new account and new option indices. Clients fetch
+//! both polls and pass them to [`compare_polls`], which lines their options
+//! up by label so a shift in sentiment can be read per option. Fetching the
+//! accounts is up to the client.
+
+use crate::state::{Poll, BPS_DENOMINATOR};
+
+/// One option's result in one of the compared polls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionResult {
+    /// Index of the option in its poll.
+    pub option_index: u32,
+    /// Votes for the option: its weight on weighted polls, its points on
+    /// cumulative polls, its score on Borda polls, its count otherwise.
+    pub votes: u128,
+    /// The option's share of the votes for all options, in basis points,
+    /// rounded down; 0 if no votes were cast for any option.
+    pub share_bps: u16,
+}
+
+/// How one option fared in both polls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionDiff {
+    pub label: String,
+    /// Result in the earlier poll, `None` if it did not offer the option.
+    pub before: Option<OptionResult>,
+    /// Result in the later poll, `None` if it does not offer the option.
+    pub after: Option<OptionResult>,
+}
+
+impl OptionDiff {
+    /// Change in votes, counting a poll without the option as zero.
+    pub fn vote_change(&self) -> i128 {
+        let votes =
+            |result: &Option<OptionResult>| result.as_ref().map_or(0, |result| result.votes);
+        votes(&self.after) as i128 - votes(&self.before) as i128
+    }
+
+    /// Change in share, in basis points, counting a poll without the option
+    /// as zero.
+    pub fn share_change_bps(&self) -> i32 {
+        let share = |result: &Option<OptionResult>| {
+            result.as_ref().map_or(0, |result| i32::from(result.share_bps))
+        };
+        share(&self.after) - share(&self.before)
+    }
+}
+
+/// Results of two polls lined up option by option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollComparison {
+    /// The earlier poll's options in index order, followed by those only the
+    /// later poll offers, in index order.
+    pub options: Vec<OptionDiff>,
+    /// Turnout of the earlier and the later poll.
+    pub turnout: (u64, u64),
+    /// Spoiled ballots in the earlier and the later poll.
+    pub spoiled: (u64, u64),
+    /// Abstentions in the earlier and the later poll.
+    pub abstained: (u64, u64),
+}
+
+impl PollComparison {
+    /// Change in turnout from the earlier poll to the later one.
+    pub fn turnout_change(&self) -> i128 {
+        i128::from(self.turnout.1) - i128::from(self.turnout.0)
+    }
+}
+
+/// Compares the results of `before` with those of a later run, `after`.
+///
+/// Options are matched by exact label. Votes are weights on weighted polls
+/// and points on cumulative ones, so compare polls of the same kind; shares
+/// are comparable either way.
+pub fn compare_polls(before: &Poll, after: &Poll) -> PollComparison {
+    let before_results = results(before);
+    let after_results = results(after);
+
+    let mut options: Vec<OptionDiff> = before
+        .options
+        .iter()
+        .zip(before_results)
+        .map(|(label, result)| OptionDiff {
+            label: label.clone(),
+            before: Some(result),
+            after: None,
+        })
+        .collect();
+    for (label, result) in after.options.iter().zip(after_results) {
+        match options.iter_mut().find(|diff| diff.label == *label) {
+            Some(diff) => diff.after = Some(result),
+            None => {
+                options.push(OptionDiff { label: label.clone(), before: None, after: Some(result) })
+            },
+        }
+    }
+
+    PollComparison {
+        options,
+        turnout: (before.turnout(), after.turnout()),
+        spoiled: (before.spoiled_count, after.spoiled_count),
+        abstained: (before.abstain_count, after.abstain_count),
+    }
+}
+
+/// Each option's result in `poll`, by index.
+fn results(poll: &Poll) -> Vec<OptionResult> {
+    let scores: Vec<u128> = (0..poll.options.len()).map(|index| poll.option_score(index)).collect();
+    let total: u128 = scores.iter().sum();
+    scores
+        .into_iter()
+        .enumerate()
+        .map(|(option_index, votes)| {
+            let share_bps = (votes * u128::from(BPS_DENOMINATOR)).checked_div(total).unwrap_or(0);
+            OptionResult { option_index: option_index as u32, votes, share_bps: share_bps as u16 }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arch_program::pubkey::Pubkey;
+
+    use crate::test_utils::PollBuilder;
+
+    #[test]
+    fn test_compare_polls() {
+        let before = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .vote_counts(&[6, 3, 1])
+            .abstain_count(2)
+            .build();
+        // Rerun with the options reordered, "Zig" dropped and "C" added.
+        let after = PollBuilder::new()
+            .options(&["Go", "C", "Rust"])
+            .vote_counts(&[5, 1, 4])
+            .spoiled_count(1)
+            .build();
+
+        let comparison = compare_polls(&before, &after);
+        let labels: Vec<&str> = comparison.options.iter().map(|diff| diff.label.as_str()).collect();
+        assert_eq!(labels, ["Rust", "Go", "Zig", "C"]);
+
+        let rust = &comparison.options[0];
+        assert_eq!(
+            (rust.before.as_ref().unwrap().option_index, rust.after.as_ref().unwrap().option_index),
+            (0, 2)
+        );
+        assert_eq!((rust.vote_change(), rust.share_change_bps()), (-2, -2_000));
+        assert_eq!(comparison.options[1].share_change_bps(), 2_000);
+        assert_eq!(comparison.options[2].after, None);
+        assert_eq!(comparison.options[2].vote_change(), -1);
+        assert_eq!(comparison.options[3].before, None);
+        assert_eq!(comparison.options[3].share_change_bps(), 1_000);
+
+        assert_eq!(comparison.turnout, (12, 11));
+        assert_eq!(comparison.turnout_change(), -1);
+        assert_eq!((comparison.spoiled, comparison.abstained), ((0, 1), (2, 0)));
+    }
+
+    #[test]
+    fn test_compare_weighted_polls() {
+        let mint = Pubkey::new_from_array([7u8; 32]);
+        let before = PollBuilder::new().weight_mint(mint).vote_weights(&[300, 100]).build();
+        let after = PollBuilder::new().weight_mint(mint).vote_weights(&[0, 0]).build();
+
+        let comparison = compare_polls(&before, &after);
+        let go = &comparison.options[1];
+        assert_eq!(
+            go.before.as_ref().map(|result| (result.votes, result.share_bps)),
+            Some((100, 2_500))
+        );
+        // No votes cast yet gives every option a zero share.
+        assert_eq!(go.after.as_ref().map(|result| result.share_bps), Some(0));
+        assert_eq!(go.vote_change(), -100);
+    }
+}
@@ -0,0 +1,1342 @@
+// src/instruction.rs
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use arch_program::pubkey::Pubkey;
+
+use crate::{
+    eligibility::EligibilityRule,
+    merkle::Hash,
+    state::{CallPolicy, Poll, PollType, TieBreak},
+    weight::WeightCurve,
+};
+
+/// Instructions the voting program accepts.
+// Instructions are decoded once per call, so `CreatePoll`'s size is not worth boxing.
+#[allow(clippy::large_enum_variant)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub enum VotingInstruction {
+    /// Create a new poll.
+    ///
+    /// The poll account must be the address returned by `find_poll_address`
+    /// for the creator and `poll_id`. If it is still owned by the system
+    /// program, it is created here with the creator paying, and the system
+    /// program must be passed as the fourth account. Otherwise it must be an
+    /// empty account already owned by this program.
+    ///
+    /// With `unique_question` set, the question is also registered in a
+    /// `QuestionRecord` at the address returned by `find_question_address`,
+    /// and the instruction fails if the creator already registered it for
+    /// another poll.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account to be created.
+    ///   1. [signer, writable] Poll creator account, pays for the poll account.
+    ///   2. [] Clock sysvar.
+    ///   3. [] System program (only when the poll account or a question record
+    ///      is created here).
+    ///   4. [writable] Question record account (only with `unique_question`).
+    CreatePoll {
+        poll_id: u64,
+        question: String,
+        options: Vec<String>,
+        start_time: u64,
+        end_time: u64,
+        /// Wallet allowed to quarantine the poll with `QuarantinePoll`.
+        moderator: Pubkey,
+        /// Whether anyone may append options with `AddOption` while the poll is live.
+        allow_new_options: bool,
+        /// Wallets that may attest the result with `SubmitAttestation`, at most
+        /// `MAX_ATTESTORS`.
+        attestors: Vec<Pubkey>,
+        /// How ballots are cast and counted.
+        poll_type: PollType,
+        /// Key that may withdraw each option with `WithdrawOption`, by option
+        /// index; at most one per option.
+        candidates: Vec<Option<Pubkey>>,
+        /// Share of turnout, in basis points, that spoiled ballots must exceed
+        /// to void the poll at close; 0 never voids.
+        max_spoiled_bps: u16,
+        /// Mint whose balance weighs each vote (plurality polls only); `None`
+        /// for one vote per wallet.
+        weight_mint: Option<Pubkey>,
+        /// Seconds before `end_time` during which new ballots are held out of
+        /// the readable tallies until close; 0 for none.
+        blackout_secs: u64,
+        /// Most options a ballot may select, up to the number of options.
+        /// Above 1 the poll takes `VoteMulti` instead of `Vote` (plurality
+        /// polls without a weight mint only); 0 and 1 mean one.
+        max_selections: u8,
+        /// Hash of terms every voter must accept with their ballot, such as a
+        /// SHA-256 of the rules document; `None` for no terms.
+        terms_hash: Option<Hash>,
+        /// Weight plugin program whose `VoterWeightRecord`s weigh each vote
+        /// (plurality polls without a `weight_mint` only); `None` for none.
+        weight_program: Option<Pubkey>,
+        /// Whether to reject the poll if the creator has already created one
+        /// with the same question.
+        unique_question: bool,
+        /// Least participation for `FinalizePoll` to count the poll: total
+        /// weight cast on weighted polls, total points on cumulative polls,
+        /// ballots cast otherwise. `None` for no quorum.
+        quorum: Option<u64>,
+        /// Share of the votes for options, in basis points and rounded up,
+        /// that the leading option must reach for `FinalizePoll` to pass the
+        /// poll, e.g. 6667 for two-thirds (plurality and Borda polls only); 0
+        /// for any outright lead.
+        approval_threshold_bps: u16,
+        /// How `FinalizePoll` settles a tie for first (plurality, Borda and
+        /// Condorcet polls only; others take `TieBreak::Fail`).
+        tie_break: TieBreak,
+        /// Who may cast ballots; `EligibilityRule::Open` for anyone. At most
+        /// `MAX_RULE_DEPTH` levels deep with `MAX_RULE_LEAVES` leaf rules.
+        eligibility: EligibilityRule,
+        /// Longest delegation chain, up to `MAX_DELEGATION_DEPTH` steps, along
+        /// which `Vote` also casts delegators' ballots; 0 for none. Only
+        /// unweighted single-choice plurality polls without terms, open or
+        /// for registered voters, may count delegated ballots.
+        max_delegation_depth: u8,
+        /// Floor, cap and transform applied to every vote's weight (weighted
+        /// polls only; others take `WeightCurve::LINEAR`).
+        weight_curve: WeightCurve,
+        /// Points each voter distributes across options with
+        /// `VoteCumulative` (unweighted single-choice plurality polls only),
+        /// or the budget each voter allocates in full on budget polls; 0 for
+        /// polls that take other ballots.
+        points_per_voter: u64,
+        /// Number of options a ranked-choice poll elects by single
+        /// transferable vote; 0 or 1 for a single winner by instant runoff.
+        seats: u8,
+        /// Seconds of clock drift tolerated at each voting-window boundary, up
+        /// to `MAX_CLOCK_TOLERANCE_SECS`; 0 for exact boundaries.
+        clock_tolerance_secs: u64,
+    },
+    /// Vote on a poll option.
+    ///
+    /// Creates the voter's `VoteReceipt` at the address returned by
+    /// `find_receipt_address`. If the receipt already exists the voter has
+    /// already voted and the instruction fails. On token-weighted polls the
+    /// vote weighs the voter's balance of the poll's `weight_mint`; on polls
+    /// with a `weight_program`, the weight in the voter's record from it.
+    /// Multi-select polls take `VoteMulti` instead, and cumulative polls
+    /// `VoteCumulative`.
+    ///
+    /// A voter who has named a delegate with `SetDelegate` need not sign: the
+    /// delegate signs instead, passing the voter's `VoterRecord` and then
+    /// themselves after every other account, and pays for the receipts. The
+    /// voter's receipt records the delegate.
+    ///
+    /// On polls with a `max_delegation_depth`, the vote also casts a ballot
+    /// for the same option for each delegator passed whose chain of delegates
+    /// leads to the voter, through delegators also passed, within that many
+    /// steps. Each gets a receipt naming the voter. Delegators who have voted
+    /// are skipped, along with those whose chain runs through them.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the receipts. Need not
+    ///      sign when the voter's delegate does.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Vote receipt account.
+    ///   4. [] System program.
+    ///   5. [] Voter's token account for the weight mint, or voter weight record
+    ///      of the weight program (weighted polls only).
+    ///   6. [] Accounts read by the poll's `eligibility` rule, as many as
+    ///      `EligibilityRule::account_count` (numbered from 5 on unweighted
+    ///      polls).
+    ///   7. [] Per delegator whose ballot the vote casts: their `VoterRecord`,
+    ///      then [writable] their vote receipt account.
+    ///   8. [] Voter's `VoterRecord` naming the delegate (delegated votes only).
+    ///   9. [signer, writable] Delegate, pays for the receipts (delegated votes
+    ///      only).
+    Vote {
+        option_index: u32,
+        /// Accepts the poll's `terms_hash`, as recorded on the receipt.
+        /// Required on polls with terms.
+        accept_terms: bool,
+        /// Merkle proof that the voter is on the poll's allowlist, from
+        /// `merkle::hash_leaf` of the voter key up to the root of the
+        /// `EligibilityRule::Allowlist`. Empty on polls without an allowlist.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Close a poll.
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Caller account (must be poll creator).
+    ///   2. [] Clock sysvar.
+    ClosePoll,
+    /// Report the program version and supported features.
+    ///
+    /// Sets the return data to a Borsh-encoded `ProgramVersion` and changes
+    /// nothing, so clients can check compatibility with a simulated call.
+    /// Accounts: none.
+    GetVersion,
+    /// Move the voter's ballot to another option while the poll is open.
+    ///
+    /// The choice is read from and written back to the voter's `VoteReceipt`,
+    /// so only votes recorded in a receipt can be changed. A vote that also
+    /// cast delegators' ballots cannot be changed either; each delegator may
+    /// still move their own.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Voter account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Vote receipt account, owned by this program.
+    ChangeVote { new_option_index: u32 },
+    /// Cancel a poll that was set up wrongly.
+    ///
+    /// Only the creator can cancel, and only before voting starts or while no
+    /// vote has been cast. A cancelled poll rejects votes and cannot be closed.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Caller account (must be poll creator).
+    ///   2. [] Clock sysvar.
+    CancelPoll,
+    /// Take a poll whose data looks corrupt or tampered with out of service.
+    ///
+    /// Only the poll's moderator can quarantine it. Every later instruction on
+    /// the poll fails with `PollQuarantined`. Integrity checks are skipped
+    /// here so a poll that fails them can still be quarantined.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Moderator account.
+    QuarantinePoll,
+    /// Edit a poll's question, options and voting window.
+    ///
+    /// Only the creator can update, and only before voting starts and while
+    /// no vote has been cast. Vote counts are reset to zero for the new
+    /// options.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Caller account (must be poll creator).
+    ///   2. [] Clock sysvar.
+    UpdatePoll { question: String, options: Vec<String>, start_time: u64, end_time: u64 },
+    /// Append an option to a live poll created with `allow_new_options`.
+    ///
+    /// Anyone can propose an option while the poll accepts votes. The label
+    /// must be new, and the poll must stay within `MAX_OPTIONS` and fit in its
+    /// account.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Proposer account.
+    ///   2. [] Clock sysvar.
+    AddOption { label: String },
+    /// Push out the end of an open poll's voting window.
+    ///
+    /// Only the creator can extend, and only before the current window ends.
+    /// The new end must be later than both the current end and the current
+    /// time, and the window may not exceed `MAX_POLL_DURATION`.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Caller account (must be poll creator).
+    ///   2. [] Clock sysvar.
+    ExtendPoll { new_end_time: u64 },
+    /// Store an attestor's signed result for a closed poll.
+    ///
+    /// The attestor must be one of the poll's `attestors` and signs
+    /// `ResultAttestation::message()` for the poll off-chain. The signature
+    /// is kept in an `AttestationRecord` at the address returned by
+    /// `find_attestation_address`; each attestor can submit once per poll.
+    ///
+    /// Accounts:
+    ///   0. [] Poll account, owned by this program.
+    ///   1. [signer, writable] Attestor account, pays for the record.
+    ///   2. [writable] Attestation record account.
+    ///   3. [] System program.
+    SubmitAttestation { signature: [u8; 64] },
+    /// Rank options of a ranked-choice, Borda or Condorcet poll.
+    ///
+    /// `ranking` lists distinct option indices from most to least preferred;
+    /// unranked options are allowed on ranked-choice and Condorcet polls,
+    /// while Borda ballots must rank every option not withdrawn and add each
+    /// one's points to the running scores. On Condorcet polls the ballot
+    /// updates the pairwise preference matrix, with ranked options preferred
+    /// to unranked ones. The ballot is stored as a `RankedBallot`
+    /// at the address returned by `find_ballot_address`, and its existence
+    /// blocks a second vote. Plurality polls take `Vote` instead.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the ballot account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Ballot account.
+    ///   4. [] System program.
+    ///   5. [] Accounts read by the poll's `eligibility` rule, as for `Vote`.
+    RankedVote {
+        ranking: Vec<u32>,
+        /// As for `Vote`.
+        accept_terms: bool,
+        /// As for `Vote`.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Count a closed ranked-choice poll by instant runoff and record the winner.
+    ///
+    /// A poll with more than one seat is counted by single transferable vote
+    /// instead (see `stv`), recording the options elected in `Poll::elected`.
+    /// Anyone can tally, once. Every ballot cast on the poll must be passed,
+    /// each exactly once.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1.. [] Ballot accounts, owned by this program.
+    Tally,
+    /// Withdraw an option, signed by its candidate key.
+    ///
+    /// Allowed before and during voting. Later votes and rankings naming the
+    /// option fail; votes it already has stay in `vote_counts` and are reported
+    /// by `Poll::withdrawn_vote_count`, and `Tally` skips it.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer] Candidate account of the option.
+    ///   2. [] Clock sysvar.
+    WithdrawOption { option_index: u32 },
+    /// Cast a deliberately spoiled ballot.
+    ///
+    /// The ballot counts toward turnout but toward no option, and blocks a
+    /// later vote like any other. On single-choice plurality polls it is
+    /// recorded in the voter's receipt; on ranked-choice and multi-select
+    /// polls, as a ballot with no options.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the receipt account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Receipt account (ballot account for ranked-choice,
+    ///      multi-select and cumulative polls).
+    ///   4. [] System program.
+    ///   5. [] Accounts read by the poll's `eligibility` rule, as for `Vote`.
+    SpoilBallot {
+        /// As for `Vote`.
+        accept_terms: bool,
+        /// As for `Vote`.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Select several options of a multi-select poll in one ballot.
+    ///
+    /// `option_indices` lists distinct options, at least one and at most the
+    /// poll's `max_selections`; each gains a vote. The ballot is stored as a
+    /// `MultiBallot` at the address returned by `find_ballot_address`, and its
+    /// existence blocks a second vote. Multi-select ballots cannot be changed.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the ballot account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Ballot account.
+    ///   4. [] System program.
+    ///   5. [] Accounts read by the poll's `eligibility` rule, as for `Vote`.
+    VoteMulti {
+        option_indices: Vec<u32>,
+        /// As for `Vote`.
+        accept_terms: bool,
+        /// As for `Vote`.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Take part in a poll without choosing an option.
+    ///
+    /// The abstention counts toward turnout and the poll's `abstain_count`
+    /// but toward no option, and blocks a later vote like any other ballot.
+    /// It is recorded like a spoiled ballot, with `ABSTAINED_BALLOT` in place
+    /// of an option, and can be changed into a vote with `ChangeVote` where
+    /// votes can be changed.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the receipt account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Receipt account (ballot account for ranked-choice,
+    ///      multi-select and cumulative polls).
+    ///   4. [] System program.
+    ///   5. [] Accounts read by the poll's `eligibility` rule, as for `Vote`.
+    Abstain {
+        /// As for `Vote`.
+        accept_terms: bool,
+        /// As for `Vote`.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Record the outcome of a closed poll.
+    ///
+    /// Anyone can finalize, once. Sets the poll's `outcome` to
+    /// `QuorumNotMet` if participation fell short of its `quorum`, otherwise
+    /// `Passed` if an option won with at least the poll's
+    /// `approval_threshold_bps` share of the votes and `Failed` if not. On
+    /// plurality polls it also records the `winner`, settling a tie for first
+    /// by the poll's `tie_break` and listing the tied options in
+    /// `tied_options`; under `TieBreak::Rerun` a tie gives `Tied`.
+    /// Ranked-choice polls must be tallied first.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    FinalizePoll,
+    /// Copy the poll's current tallies into a `ResultSnapshot`, signed by the
+    /// creator.
+    ///
+    /// The snapshot is stored at the address returned by
+    /// `find_snapshot_address` for `label` (at most `MAX_SNAPSHOT_LABEL_LEN`
+    /// bytes) and is never changed, so each label can be used once per poll.
+    ///
+    /// Accounts:
+    ///   0. [] Poll account, owned by this program.
+    ///   1. [signer, writable] Poll creator account, pays for the snapshot account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Snapshot account.
+    ///   4. [] System program.
+    SnapshotResult { label: String },
+    /// Count a batch of a closed ranked-choice poll's ballots, for polls with
+    /// too many ballots to pass to one `Tally`.
+    ///
+    /// Anyone can submit a batch. Progress is kept in a `cursor::WorkCursor`
+    /// at the address returned by `find_cursor_address` for
+    /// `CursorTask::Tally`, created by the first batch. Each round of the
+    /// count takes one pass over the ballots, passed in ascending address
+    /// order across the pass's batches, each exactly once. The batch that
+    /// brings a pass to the poll's turnout counts the round: it records the
+    /// result, as `Tally` does, once the count is over, and otherwise starts
+    /// the next pass.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Caller account, pays for the cursor account.
+    ///   2. [writable] Cursor account.
+    ///   3. [] System program.
+    ///   4. [] Ballot accounts of the batch, any number, owned by this program.
+    TallyBatch,
+    /// Register the signing wallet as a voter.
+    ///
+    /// Creates the voter's `VoterRecord` at the address returned by
+    /// `find_voter_record_address`, recording when they registered and an
+    /// optional `delegate`. Polls whose eligibility rule includes
+    /// `EligibilityRule::Registered` only take ballots from registered voters.
+    /// Each wallet registers once.
+    ///
+    /// Accounts:
+    ///   0. [writable] Voter record account.
+    ///   1. [signer, writable] Voter account, pays for the voter record.
+    ///   2. [] Clock sysvar.
+    ///   3. [] System program.
+    RegisterVoter {
+        /// Wallet the voter names to act for them.
+        delegate: Option<Pubkey>,
+    },
+    /// Name the wallet that may vote for the signing voter.
+    ///
+    /// Replaces any delegate already named. The delegate may then cast `Vote`
+    /// for the voter, until the voter names another or calls
+    /// `RevokeDelegate`. The voter can still vote themselves.
+    ///
+    /// Accounts:
+    ///   0. [writable] Voter record account, owned by this program.
+    ///   1. [signer] Voter account.
+    SetDelegate { delegate: Pubkey },
+    /// Stop the signing voter's delegate from voting for them.
+    ///
+    /// Accounts:
+    ///   0. [writable] Voter record account, owned by this program.
+    ///   1. [signer] Voter account.
+    RevokeDelegate,
+    /// Distribute points across the options of a cumulative or budget poll
+    /// in one ballot.
+    ///
+    /// `allocations` pairs distinct options with the points given to each,
+    /// at least one point apiece and no more than the poll's
+    /// `points_per_voter` in all. Each option gains its points in the poll's
+    /// `vote_weights` and one ballot in `vote_counts`. A budget poll's
+    /// ballot must allocate exactly `points_per_voter`, and each option
+    /// gains its share of `budget::BUDGET_SCALE` instead of its points. The ballot is stored
+    /// as a `CumulativeBallot` at the address returned by
+    /// `find_ballot_address`, and its existence blocks a second vote.
+    /// Cumulative ballots cannot be changed.
+    ///
+    /// Accounts:
+    ///   0. [writable] Poll account, owned by this program.
+    ///   1. [signer, writable] Voter account, pays for the ballot account.
+    ///   2. [] Clock sysvar.
+    ///   3. [writable] Ballot account.
+    ///   4. [] System program.
+    ///   5. [] Accounts read by the poll's `eligibility` rule, as for `Vote`.
+    VoteCumulative {
+        /// `(option_index, points)` pairs.
+        allocations: Vec<(u32, u64)>,
+        /// As for `Vote`.
+        accept_terms: bool,
+        /// As for `Vote`.
+        eligibility_proof: Vec<Hash>,
+    },
+    /// Create a yes/no proposal: a plurality poll with the options
+    /// `state::PROPOSAL_OPTIONS`, marked `is_proposal`.
+    ///
+    /// Voters choose Yes or No with `Vote`, or `Abstain`, which counts
+    /// toward the quorum but not toward either option. `FinalizePoll` passes
+    /// the proposal only if Yes leads and holds at least
+    /// `approval_threshold_bps` of the Yes and No votes; a tie fails. The
+    /// creator moderates the proposal, and everything else takes the
+    /// `CreatePoll` defaults: open to every voter, unweighted, without
+    /// attestors, terms or a blackout. The options cannot be changed.
+    ///
+    /// Accounts: as for `CreatePoll` without `unique_question`.
+    CreateProposal {
+        poll_id: u64,
+        question: String,
+        start_time: u64,
+        end_time: u64,
+        /// Yes, No and abstention ballots needed for a valid result; `None`
+        /// for no quorum.
+        quorum: Option<u64>,
+        /// Share of the Yes and No votes, in basis points, Yes needs to
+        /// pass; 0 for a simple majority.
+        approval_threshold_bps: u16,
+    },
+    /// Create the program's `ProgramConfig` at the address returned by
+    /// `find_config_address`.
+    ///
+    /// The program's own key must sign, so only whoever deployed the program
+    /// can create its config. Until then the program runs with the default
+    /// settings.
+    ///
+    /// Accounts:
+    ///   0. [writable] Config account.
+    ///   1. [signer, writable] Payer account, pays for the config account.
+    ///   2. [signer] This program's account.
+    ///   3. [] System program.
+    InitializeConfig {
+        /// Wallet that may change the settings.
+        admin: Pubkey,
+        /// Whether the program starts read-only.
+        read_only_mode: bool,
+    },
+    /// Turn read-only mode on or off.
+    ///
+    /// In read-only mode an instruction fails with
+    /// `VotingError::ReadOnlyMode` unless it `runs_when_read_only` and the
+    /// config's admin signs it, so operators can deploy, set up polls and
+    /// wire up clients before anyone else can write, then open voting
+    /// without redeploying.
+    ///
+    /// Accounts:
+    ///   0. [writable] Config account, owned by this program.
+    ///   1. [signer] The config's admin.
+    SetReadOnlyMode { read_only_mode: bool },
+    /// Set how one instruction may be reached, as a `CallPolicy` checked
+    /// against the instructions sysvar before it runs.
+    ///
+    /// Only instructions that `takes_config` can be guarded, so the admin
+    /// cannot lock themselves out of the config; naming any other fails with
+    /// `ProgramError::InvalidInstructionData`.
+    ///
+    /// Accounts: as for `SetReadOnlyMode`.
+    SetCallPolicy {
+        /// Tag of the instruction, the first byte of its data; below
+        /// `INSTRUCTION_COUNT` and not in `CONFIG_FREE_TAGS`.
+        instruction: u8,
+        /// `CallPolicy::Any` lifts any restriction.
+        policy: CallPolicy,
+    },
+    /// Replace the programs that may call instructions under
+    /// `CallPolicy::AllowlistedCpi`, at most `MAX_CPI_ALLOWLIST` of them.
+    ///
+    /// Accounts: as for `SetReadOnlyMode`.
+    SetCpiAllowlist { programs: Vec<Pubkey> },
+}
+
+/// Number of `VotingInstruction` variants. Their tags, the first byte of
+/// their data, run from 0 up to this.
+pub const INSTRUCTION_COUNT: u8 = 29;
+
+/// Tags of the instructions that do not `takes_config`: `GetVersion`,
+/// `InitializeConfig`, `SetReadOnlyMode`, `SetCallPolicy` and
+/// `SetCpiAllowlist`.
+pub const CONFIG_FREE_TAGS: [u8; 5] = [3, 25, 26, 27, 28];
+
+/// Version of the account lists below. Bumped whenever an instruction's
+/// expected accounts change, so clients can detect a stale layout.
+///
+/// Every instruction that `takes_config` also takes the config account
+/// after all of its other accounts, including any it takes a variable
+/// number of. The lists below leave it out.
+pub const ACCOUNTS_SCHEMA_VERSION: u8 = 6;
+
+/// Feature bit: polls live at creator-scoped program addresses.
+pub const FEATURE_PDA_POLLS: u64 = 1 << 0;
+/// Feature bit: votes are recorded in per-voter receipt accounts.
+pub const FEATURE_VOTE_RECEIPTS: u64 = 1 << 1;
+/// Feature bit: voters can move their ballot with `ChangeVote`.
+pub const FEATURE_CHANGE_VOTE: u64 = 1 << 2;
+/// Feature bit: creators can cancel polls with `CancelPoll`.
+pub const FEATURE_CANCEL_POLL: u64 = 1 << 3;
+/// Feature bit: polls are integrity-checked on load and can be quarantined.
+pub const FEATURE_QUARANTINE: u64 = 1 << 4;
+/// Feature bit: creators can edit upcoming polls with `UpdatePoll`.
+pub const FEATURE_UPDATE_POLL: u64 = 1 << 5;
+/// Feature bit: polls can accept new options with `AddOption`.
+pub const FEATURE_ADD_OPTION: u64 = 1 << 6;
+/// Feature bit: creators can lengthen open polls with `ExtendPoll`.
+pub const FEATURE_EXTEND_POLL: u64 = 1 << 7;
+/// Feature bit: closed polls can carry attestor-signed results.
+pub const FEATURE_ATTESTATIONS: u64 = 1 << 8;
+/// Feature bit: ranked-choice polls with `RankedVote` and `Tally`.
+pub const FEATURE_RANKED_CHOICE: u64 = 1 << 9;
+/// Feature bit: candidates can withdraw their options with `WithdrawOption`.
+pub const FEATURE_WITHDRAW_OPTION: u64 = 1 << 10;
+/// Feature bit: voters can spoil ballots with `SpoilBallot`.
+pub const FEATURE_SPOIL_BALLOT: u64 = 1 << 11;
+/// Feature bit: votes weighted by token balance.
+pub const FEATURE_TOKEN_WEIGHT: u64 = 1 << 12;
+/// Feature bit: tallies can freeze for a blackout before the poll ends.
+pub const FEATURE_BLACKOUT: u64 = 1 << 13;
+/// Feature bit: multi-select polls with `VoteMulti`.
+pub const FEATURE_MULTI_SELECT: u64 = 1 << 14;
+/// Feature bit: voters can abstain with `Abstain`.
+pub const FEATURE_ABSTAIN: u64 = 1 << 15;
+/// Feature bit: polls can require voters to accept terms.
+pub const FEATURE_TERMS: u64 = 1 << 16;
+/// Feature bit: votes weighted by records of a weight plugin program.
+pub const FEATURE_WEIGHT_PLUGIN: u64 = 1 << 17;
+/// Feature bit: polls can reject duplicate questions from the same creator.
+pub const FEATURE_UNIQUE_QUESTION: u64 = 1 << 18;
+/// Feature bit: polls can set a quorum and record an outcome with `FinalizePoll`.
+pub const FEATURE_QUORUM: u64 = 1 << 19;
+/// Feature bit: creators can snapshot tallies with `SnapshotResult`.
+pub const FEATURE_SNAPSHOTS: u64 = 1 << 20;
+/// Feature bit: polls can restrict voters to a Merkle allowlist.
+pub const FEATURE_ELIGIBILITY_ROOT: u64 = 1 << 21;
+/// Feature bit: polls can be restricted to holders of an NFT collection.
+pub const FEATURE_NFT_GATE: u64 = 1 << 22;
+/// Feature bit: ranked-choice polls can be tallied in batches with `TallyBatch`.
+pub const FEATURE_TALLY_BATCH: u64 = 1 << 23;
+/// Feature bit: polls can require a minimum balance of a token mint.
+pub const FEATURE_MIN_TOKEN_BALANCE: u64 = 1 << 24;
+/// Feature bit: eligibility rules can be combined and can require credentials.
+pub const FEATURE_ELIGIBILITY_RULES: u64 = 1 << 25;
+/// Feature bit: voters can register with `RegisterVoter`.
+pub const FEATURE_VOTER_REGISTRATION: u64 = 1 << 26;
+/// Feature bit: eligibility rules can exclude voters with `Not`.
+pub const FEATURE_NEGATED_ELIGIBILITY: u64 = 1 << 27;
+/// Feature bit: voters can name a delegate to vote for them.
+pub const FEATURE_DELEGATION: u64 = 1 << 28;
+/// Feature bit: votes can cast the ballots of delegation chains leading to
+/// the voter.
+pub const FEATURE_DELEGATION_CHAINS: u64 = 1 << 29;
+/// Feature bit: weighted polls can cap, floor and square-root vote weights.
+pub const FEATURE_WEIGHT_CURVE: u64 = 1 << 30;
+/// Feature bit: cumulative polls with `VoteCumulative`.
+pub const FEATURE_CUMULATIVE_VOTING: u64 = 1 << 31;
+/// Feature bit: Borda-count polls.
+pub const FEATURE_BORDA: u64 = 1 << 32;
+/// Feature bit: the program config, whose `read_only_mode` the admin can
+/// turn on and off.
+pub const FEATURE_READ_ONLY_MODE: u64 = 1 << 33;
+/// Feature bit: Condorcet polls, with a Schulze fallback.
+pub const FEATURE_CONDORCET: u64 = 1 << 34;
+/// Feature bit: the config's admin can guard instructions with a
+/// `CallPolicy`, after which callers pass the instructions sysvar.
+pub const FEATURE_CALL_POLICY: u64 = 1 << 35;
+/// Feature bit: ranked-choice polls with more than one seat, elected by single
+/// transferable vote.
+pub const FEATURE_STV: u64 = 1 << 36;
+/// Feature bit: budget polls, reporting the average allocation.
+pub const FEATURE_BUDGET: u64 = 1 << 37;
+/// Feature bit: yes/no proposals with `CreateProposal`.
+pub const FEATURE_PROPOSALS: u64 = 1 << 38;
+/// Features supported by this build.
+pub const SUPPORTED_FEATURES: u64 = FEATURE_PDA_POLLS
+    | FEATURE_VOTE_RECEIPTS
+    | FEATURE_CHANGE_VOTE
+    | FEATURE_CANCEL_POLL
+    | FEATURE_QUARANTINE
+    | FEATURE_UPDATE_POLL
+    | FEATURE_ADD_OPTION
+    | FEATURE_EXTEND_POLL
+    | FEATURE_ATTESTATIONS
+    | FEATURE_RANKED_CHOICE
+    | FEATURE_WITHDRAW_OPTION
+    | FEATURE_SPOIL_BALLOT
+    | FEATURE_TOKEN_WEIGHT
+    | FEATURE_BLACKOUT
+    | FEATURE_MULTI_SELECT
+    | FEATURE_ABSTAIN
+    | FEATURE_TERMS
+    | FEATURE_WEIGHT_PLUGIN
+    | FEATURE_UNIQUE_QUESTION
+    | FEATURE_QUORUM
+    | FEATURE_SNAPSHOTS
+    | FEATURE_ELIGIBILITY_ROOT
+    | FEATURE_NFT_GATE
+    | FEATURE_TALLY_BATCH
+    | FEATURE_MIN_TOKEN_BALANCE
+    | FEATURE_ELIGIBILITY_RULES
+    | FEATURE_VOTER_REGISTRATION
+    | FEATURE_NEGATED_ELIGIBILITY
+    | FEATURE_DELEGATION
+    | FEATURE_DELEGATION_CHAINS
+    | FEATURE_WEIGHT_CURVE
+    | FEATURE_CUMULATIVE_VOTING
+    | FEATURE_BORDA
+    | FEATURE_CONDORCET
+    | FEATURE_STV
+    | FEATURE_BUDGET
+    | FEATURE_PROPOSALS
+    | FEATURE_READ_ONLY_MODE
+    | FEATURE_CALL_POLICY;
+
+/// Reply to `GetVersion`, returned as Borsh-encoded return data.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct ProgramVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    /// `ACCOUNTS_SCHEMA_VERSION` of the deployed program.
+    pub accounts_schema: u8,
+    /// Bitmask of `FEATURE_*` flags.
+    pub features: u64,
+}
+
+impl ProgramVersion {
+    /// Version of this build, taken from the crate version.
+    pub fn current() -> Self {
+        Self {
+            major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+            minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+            patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+            accounts_schema: ACCOUNTS_SCHEMA_VERSION,
+            features: SUPPORTED_FEATURES,
+        }
+    }
+
+    /// Whether every flag in `features` is supported.
+    pub fn supports(&self, features: u64) -> bool {
+        self.features & features == features
+    }
+}
+
+/// Role an account plays in an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountRole {
+    /// The poll state account.
+    Poll,
+    /// The wallet creating a poll.
+    Creator,
+    /// The wallet casting a vote.
+    Voter,
+    /// The wallet performing an administrative action on a poll.
+    Caller,
+    /// The poll's moderator.
+    Moderator,
+    /// The wallet proposing a new option.
+    Proposer,
+    /// The candidate key of a poll option.
+    Candidate,
+    /// A wallet attesting a poll's result.
+    Attestor,
+    /// The clock sysvar.
+    Clock,
+    /// A voter's receipt for a poll.
+    Receipt,
+    /// A voter's ranked ballot for a poll.
+    Ballot,
+    /// An attestor's record for a poll.
+    Attestation,
+    /// The system program.
+    SystemProgram,
+    /// A point-in-time copy of a poll's tallies.
+    Snapshot,
+    /// Progress of work split across transactions.
+    Cursor,
+    /// A voter's registration record.
+    VoterRecord,
+    /// The program config.
+    Config,
+    /// The wallet paying for a new account.
+    Payer,
+    /// This program's own account.
+    Program,
+    /// The program config's admin.
+    Admin,
+}
+
+/// Expected shape of one entry in an instruction's account list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountSpec {
+    pub role: AccountRole,
+    pub signer: bool,
+    pub writable: bool,
+    /// Whether the account must be owned by the voting program.
+    pub program_owned: bool,
+}
+
+impl AccountSpec {
+    const fn new(role: AccountRole, signer: bool, writable: bool, program_owned: bool) -> Self {
+        Self { role, signer, writable, program_owned }
+    }
+}
+
+/// Accounts expected by `CreatePoll`, in order.
+///
+/// The poll account's owner is checked by the handler, since it may still
+/// belong to the system program. The optional system program is not listed.
+pub const CREATE_POLL_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, false),
+    AccountSpec::new(AccountRole::Creator, true, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `Vote`, in order.
+///
+/// The voter's signature is checked by the handler, since a delegate may sign
+/// instead.
+pub const VOTE_ACCOUNTS: [AccountSpec; 5] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Voter, false, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::Receipt, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `ClosePoll`, in order.
+pub const CLOSE_POLL_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Caller, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `CancelPoll`, in order.
+pub const CANCEL_POLL_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Caller, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `UpdatePoll`, in order.
+pub const UPDATE_POLL_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Caller, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `ExtendPoll`, in order.
+pub const EXTEND_POLL_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Caller, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `RankedVote`, in order.
+pub const RANKED_VOTE_ACCOUNTS: [AccountSpec; 5] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Voter, true, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::Ballot, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `Tally`, in order. The poll's ballots follow.
+pub const TALLY_ACCOUNTS: [AccountSpec; 1] =
+    [AccountSpec::new(AccountRole::Poll, false, true, true)];
+
+/// Accounts expected by `TallyBatch`, in order. The batch's ballots follow.
+///
+/// The cursor's owner is checked by the handler, since the first batch
+/// creates it.
+pub const TALLY_BATCH_ACCOUNTS: [AccountSpec; 4] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Caller, true, true, false),
+    AccountSpec::new(AccountRole::Cursor, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `RegisterVoter`, in order.
+///
+/// The voter record's owner is checked by the handler, since it is created
+/// here.
+pub const REGISTER_VOTER_ACCOUNTS: [AccountSpec; 4] = [
+    AccountSpec::new(AccountRole::VoterRecord, false, true, false),
+    AccountSpec::new(AccountRole::Voter, true, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `SetDelegate` and `RevokeDelegate`, in order.
+pub const SET_DELEGATE_ACCOUNTS: [AccountSpec; 2] = [
+    AccountSpec::new(AccountRole::VoterRecord, false, true, true),
+    AccountSpec::new(AccountRole::Voter, true, false, false),
+];
+
+/// Accounts expected by `FinalizePoll`, in order.
+pub const FINALIZE_POLL_ACCOUNTS: [AccountSpec; 1] =
+    [AccountSpec::new(AccountRole::Poll, false, true, true)];
+
+/// Accounts expected by `SnapshotResult`, in order.
+pub const SNAPSHOT_RESULT_ACCOUNTS: [AccountSpec; 5] = [
+    AccountSpec::new(AccountRole::Poll, false, false, true),
+    AccountSpec::new(AccountRole::Creator, true, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::Snapshot, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `WithdrawOption`, in order.
+pub const WITHDRAW_OPTION_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Candidate, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `SpoilBallot`, in order.
+///
+/// Unlike `Vote`, the voter must sign: no delegate casts blank ballots.
+pub const SPOIL_BALLOT_ACCOUNTS: [AccountSpec; 5] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Voter, true, true, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::Receipt, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `VoteMulti`, in order.
+pub const VOTE_MULTI_ACCOUNTS: [AccountSpec; 5] = RANKED_VOTE_ACCOUNTS;
+
+/// Accounts expected by `VoteCumulative`, in order.
+pub const VOTE_CUMULATIVE_ACCOUNTS: [AccountSpec; 5] = RANKED_VOTE_ACCOUNTS;
+
+/// Accounts expected by `Abstain`, in order.
+pub const ABSTAIN_ACCOUNTS: [AccountSpec; 5] = SPOIL_BALLOT_ACCOUNTS;
+
+/// Accounts expected by `SubmitAttestation`, in order.
+pub const SUBMIT_ATTESTATION_ACCOUNTS: [AccountSpec; 4] = [
+    AccountSpec::new(AccountRole::Poll, false, false, true),
+    AccountSpec::new(AccountRole::Attestor, true, true, false),
+    AccountSpec::new(AccountRole::Attestation, false, true, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `AddOption`, in order.
+pub const ADD_OPTION_ACCOUNTS: [AccountSpec; 3] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Proposer, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+];
+
+/// Accounts expected by `QuarantinePoll`, in order.
+pub const QUARANTINE_POLL_ACCOUNTS: [AccountSpec; 2] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Moderator, true, false, false),
+];
+
+/// Accounts expected by `ChangeVote`, in order.
+///
+/// The receipt's owner is checked by the handler, which reports a missing
+/// receipt as `NoVoteToChange`.
+pub const CHANGE_VOTE_ACCOUNTS: [AccountSpec; 4] = [
+    AccountSpec::new(AccountRole::Poll, false, true, true),
+    AccountSpec::new(AccountRole::Voter, true, false, false),
+    AccountSpec::new(AccountRole::Clock, false, false, false),
+    AccountSpec::new(AccountRole::Receipt, false, true, false),
+];
+
+/// Accounts expected by `InitializeConfig`, in order.
+///
+/// The config account's owner is checked by the handler, since it is
+/// created here.
+pub const INITIALIZE_CONFIG_ACCOUNTS: [AccountSpec; 4] = [
+    AccountSpec::new(AccountRole::Config, false, true, false),
+    AccountSpec::new(AccountRole::Payer, true, true, false),
+    AccountSpec::new(AccountRole::Program, true, false, false),
+    AccountSpec::new(AccountRole::SystemProgram, false, false, false),
+];
+
+/// Accounts expected by `SetReadOnlyMode`, `SetCallPolicy` and
+/// `SetCpiAllowlist`, in order.
+pub const SET_CONFIG_ACCOUNTS: [AccountSpec; 2] = [
+    AccountSpec::new(AccountRole::Config, false, true, true),
+    AccountSpec::new(AccountRole::Admin, true, false, false),
+];
+
+impl VotingInstruction {
+    /// The accounts this instruction expects, in order.
+    pub fn accounts(&self) -> &'static [AccountSpec] {
+        match self {
+            VotingInstruction::CreatePoll { .. } | VotingInstruction::CreateProposal { .. } => {
+                &CREATE_POLL_ACCOUNTS
+            },
+            VotingInstruction::Vote { .. } => &VOTE_ACCOUNTS,
+            VotingInstruction::ClosePoll => &CLOSE_POLL_ACCOUNTS,
+            VotingInstruction::GetVersion => &[],
+            VotingInstruction::ChangeVote { .. } => &CHANGE_VOTE_ACCOUNTS,
+            VotingInstruction::CancelPoll => &CANCEL_POLL_ACCOUNTS,
+            VotingInstruction::QuarantinePoll => &QUARANTINE_POLL_ACCOUNTS,
+            VotingInstruction::UpdatePoll { .. } => &UPDATE_POLL_ACCOUNTS,
+            VotingInstruction::AddOption { .. } => &ADD_OPTION_ACCOUNTS,
+            VotingInstruction::ExtendPoll { .. } => &EXTEND_POLL_ACCOUNTS,
+            VotingInstruction::SubmitAttestation { .. } => &SUBMIT_ATTESTATION_ACCOUNTS,
+            VotingInstruction::RankedVote { .. } => &RANKED_VOTE_ACCOUNTS,
+            VotingInstruction::Tally => &TALLY_ACCOUNTS,
+            VotingInstruction::WithdrawOption { .. } => &WITHDRAW_OPTION_ACCOUNTS,
+            VotingInstruction::SpoilBallot { .. } => &SPOIL_BALLOT_ACCOUNTS,
+            VotingInstruction::VoteMulti { .. } => &VOTE_MULTI_ACCOUNTS,
+            VotingInstruction::Abstain { .. } => &ABSTAIN_ACCOUNTS,
+            VotingInstruction::FinalizePoll => &FINALIZE_POLL_ACCOUNTS,
+            VotingInstruction::SnapshotResult { .. } => &SNAPSHOT_RESULT_ACCOUNTS,
+            VotingInstruction::TallyBatch => &TALLY_BATCH_ACCOUNTS,
+            VotingInstruction::RegisterVoter { .. } => &REGISTER_VOTER_ACCOUNTS,
+            VotingInstruction::SetDelegate { .. } | VotingInstruction::RevokeDelegate => {
+                &SET_DELEGATE_ACCOUNTS
+            },
+            VotingInstruction::VoteCumulative { .. } => &VOTE_CUMULATIVE_ACCOUNTS,
+            VotingInstruction::InitializeConfig { .. } => &INITIALIZE_CONFIG_ACCOUNTS,
+            VotingInstruction::SetReadOnlyMode { .. }
+            | VotingInstruction::SetCallPolicy { .. }
+            | VotingInstruction::SetCpiAllowlist { .. } => &SET_CONFIG_ACCOUNTS,
+        }
+    }
+
+    /// Whether this instruction takes the program config after its other
+    /// accounts: all do but `GetVersion`, which changes nothing, and the
+    /// instructions that manage the config itself.
+    pub fn takes_config(&self) -> bool {
+        !matches!(
+            self,
+            VotingInstruction::GetVersion
+                | VotingInstruction::InitializeConfig { .. }
+                | VotingInstruction::SetReadOnlyMode { .. }
+                | VotingInstruction::SetCallPolicy { .. }
+                | VotingInstruction::SetCpiAllowlist { .. }
+        )
+    }
+
+    /// Whether this instruction is an administrative one, run by a poll's
+    /// creator or moderator to set up and manage the poll rather than to
+    /// vote, register or count.
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            VotingInstruction::CreatePoll { .. }
+                | VotingInstruction::CreateProposal { .. }
+                | VotingInstruction::ClosePoll
+                | VotingInstruction::CancelPoll
+                | VotingInstruction::QuarantinePoll
+                | VotingInstruction::UpdatePoll { .. }
+                | VotingInstruction::ExtendPoll { .. }
+                | VotingInstruction::SnapshotResult { .. }
+        )
+    }
+
+    /// Whether this instruction runs in read-only mode, when signed by the
+    /// config's admin: administrative instructions and those that do not
+    /// `takes_config`.
+    pub fn runs_when_read_only(&self) -> bool {
+        self.is_admin() || !self.takes_config()
+    }
+}
+
+/// Renders raw instruction data as a one-line description for wallet signing
+/// prompts, e.g. `Vote for option 0 ('Rust') in poll 'Best language?' ending 2021-05-03`.
+///
+/// The instruction itself only carries indices, so pass the target `poll` to
+/// include its question, option labels and end date.
+pub fn summarize(instruction_data: &[u8], poll: Option<&Poll>) -> String {
+    let instruction = match VotingInstruction::try_from_slice(instruction_data) {
+        Ok(instruction) => instruction,
+        Err(_) => return "Unrecognized voting instruction".to_string(),
+    };
+
+    match instruction {
+        VotingInstruction::CreatePoll { question, options, end_time, .. } => format!(
+            "Create poll '{}' with {} options ending {}",
+            question,
+            options.len(),
+            format_date(end_time)
+        ),
+        VotingInstruction::Vote { option_index, accept_terms, .. } => {
+            format!("Vote for {}{}", describe_option(option_index, poll), terms_note(accept_terms))
+        },
+        VotingInstruction::ClosePoll => match poll {
+            Some(poll) => format!("Close poll '{}'", poll.question),
+            None => "Close poll".to_string(),
+        },
+        VotingInstruction::GetVersion => "Check the voting program version".to_string(),
+        VotingInstruction::ChangeVote { new_option_index } => {
+            format!("Change vote to {}", describe_option(new_option_index, poll))
+        },
+        VotingInstruction::CancelPoll => match poll {
+            Some(poll) => format!("Cancel poll '{}'", poll.question),
+            None => "Cancel poll".to_string(),
+        },
+        VotingInstruction::QuarantinePoll => match poll {
+            Some(poll) => format!("Quarantine poll '{}'", poll.question),
+            None => "Quarantine poll".to_string(),
+        },
+        VotingInstruction::UpdatePoll { question, options, end_time, .. } => format!(
+            "Update poll to '{}' with {} options ending {}",
+            question,
+            options.len(),
+            format_date(end_time)
+        ),
+        VotingInstruction::AddOption { label } => match poll {
+            Some(poll) => format!("Add option '{}' to poll '{}'", label, poll.question),
+            None => format!("Add option '{}'", label),
+        },
+        VotingInstruction::ExtendPoll { new_end_time } => match poll {
+            Some(poll) => {
+                format!("Extend poll '{}' to end {}", poll.question, format_date(new_end_time))
+            },
+            None => format!("Extend poll to end {}", format_date(new_end_time)),
+        },
+        VotingInstruction::SubmitAttestation { .. } => match poll {
+            Some(poll) => format!("Attest the result of poll '{}'", poll.question),
+            None => "Attest a poll result".to_string(),
+        },
+        VotingInstruction::RankedVote { ranking, accept_terms, .. } => {
+            let ranked: Vec<String> =
+                ranking.iter().map(|&option_index| option_label(option_index, poll)).collect();
+            format!("Rank {}{}{}", ranked.join(" > "), poll_context(poll), terms_note(accept_terms))
+        },
+        VotingInstruction::Tally => match poll {
+            Some(poll) => format!("Tally ranked ballots of poll '{}'", poll.question),
+            None => "Tally ranked ballots".to_string(),
+        },
+        VotingInstruction::WithdrawOption { option_index } => {
+            format!("Withdraw {}", describe_option(option_index, poll))
+        },
+        VotingInstruction::SpoilBallot { accept_terms, .. } => {
+            format!("Spoil your ballot{}{}", poll_context(poll), terms_note(accept_terms))
+        },
+        VotingInstruction::VoteMulti { option_indices, accept_terms, .. } => {
+            let selected: Vec<String> = option_indices
+                .iter()
+                .map(|&option_index| option_label(option_index, poll))
+                .collect();
+            let context = poll_context(poll);
+            format!("Vote for {}{}{}", selected.join(", "), context, terms_note(accept_terms))
+        },
+        VotingInstruction::Abstain { accept_terms, .. } => {
+            format!("Abstain{}{}", poll_context(poll), terms_note(accept_terms))
+        },
+        VotingInstruction::FinalizePoll => match poll {
+            Some(poll) => format!("Record the outcome of poll '{}'", poll.question),
+            None => "Record a poll outcome".to_string(),
+        },
+        VotingInstruction::SnapshotResult { label } => match poll {
+            Some(poll) => {
+                format!("Snapshot the results of poll '{}' as '{}'", poll.question, label)
+            },
+            None => format!("Snapshot poll results as '{}'", label),
+        },
+        VotingInstruction::TallyBatch => match poll {
+            Some(poll) => format!("Tally a batch of ranked ballots of poll '{}'", poll.question),
+            None => "Tally a batch of ranked ballots".to_string(),
+        },
+        VotingInstruction::RegisterVoter { delegate } => match delegate {
+            Some(_) => "Register as a voter and name a delegate".to_string(),
+            None => "Register as a voter".to_string(),
+        },
+        VotingInstruction::SetDelegate { .. } => "Name a delegate to vote for you".to_string(),
+        VotingInstruction::RevokeDelegate => "Revoke your delegate".to_string(),
+        VotingInstruction::VoteCumulative { allocations, accept_terms, .. } => {
+            let given: Vec<String> = allocations
+                .iter()
+                .map(|&(option_index, points)| {
+                    format!("{} points to {}", points, option_label(option_index, poll))
+                })
+                .collect();
+            let context = poll_context(poll);
+            format!("Give {}{}{}", given.join(", "), context, terms_note(accept_terms))
+        },
+        VotingInstruction::CreateProposal { question, end_time, .. } => {
+            format!("Create yes/no proposal '{}' ending {}", question, format_date(end_time))
+        },
+        VotingInstruction::InitializeConfig { read_only_mode, .. } => match read_only_mode {
+            true => "Set up the voting program, read-only".to_string(),
+            false => "Set up the voting program".to_string(),
+        },
+        VotingInstruction::SetReadOnlyMode { read_only_mode } => match read_only_mode {
+            true => "Make the voting program read-only".to_string(),
+            false => "Open the voting program to every instruction".to_string(),
+        },
+        VotingInstruction::SetCallPolicy { instruction, policy } => match policy {
+            CallPolicy::Any => format!("Let any program call voting instruction {}", instruction),
+            CallPolicy::TopLevelOnly => {
+                format!("Allow voting instruction {} only at the top level", instruction)
+            },
+            CallPolicy::AllowlistedCpi => {
+                format!("Allow voting instruction {} only from allowlisted programs", instruction)
+            },
+        },
+        VotingInstruction::SetCpiAllowlist { programs } => {
+            format!("Allowlist {} program(s) to call the voting program", programs.len())
+        },
+    }
+}
+
+/// Describes an option, with its label and poll context when `poll` is known.
+fn describe_option(option_index: u32, poll: Option<&Poll>) -> String {
+    format!("{}{}", option_label(option_index, poll), poll_context(poll))
+}
+
+/// Names an option, with its label when `poll` is known.
+fn option_label(option_index: u32, poll: Option<&Poll>) -> String {
+    let label = poll
+        .and_then(|poll| poll.options.get(option_index as usize))
+        .map(|label| format!(" ('{}')", label))
+        .unwrap_or_default();
+    format!("option {}{}", option_index, label)
+}
+
+/// Names the poll and its end date, when `poll` is known.
+fn poll_context(poll: Option<&Poll>) -> String {
+    poll.map(|poll| format!(" in poll '{}' ending {}", poll.question, format_date(poll.end_time)))
+        .unwrap_or_default()
+}
+
+/// Notes that a ballot accepts the poll's terms.
+fn terms_note(accept_terms: bool) -> &'static str {
+    if accept_terms {
+        ", accepting its terms"
+    } else {
+        ""
+    }
+}
+
+/// Formats a unix timestamp (seconds) as a UTC `YYYY-MM-DD` date.
+fn format_date(timestamp: u64) -> String {
+    // Civil-from-days conversion for the proleptic Gregorian calendar.
+    let z = (timestamp / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PollBuilder;
+
+    #[test]
+    fn test_summarize() {
+        let poll = PollBuilder::new().build();
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+
+        assert_eq!(
+            summarize(&vote, Some(&poll)),
+            "Vote for option 0 ('Rust') in poll 'Best programming language?' ending 2021-05-03"
+        );
+        assert_eq!(summarize(&vote, None), "Vote for option 0");
+        let accepting = VotingInstruction::Vote {
+            option_index: 1,
+            accept_terms: true,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            summarize(&accepting.try_to_vec().unwrap(), None),
+            "Vote for option 1, accepting its terms"
+        );
+        let ranked = VotingInstruction::RankedVote {
+            ranking: vec![1, 0],
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(
+            summarize(&ranked, Some(&poll)),
+            "Rank option 1 ('Go') > option 0 ('Rust') in poll 'Best programming language?' \
+             ending 2021-05-03"
+        );
+        assert_eq!(summarize(&ranked, None), "Rank option 1 > option 0");
+        let change = VotingInstruction::ChangeVote { new_option_index: 1 }.try_to_vec().unwrap();
+        assert_eq!(
+            summarize(&change, Some(&poll)),
+            "Change vote to option 1 ('Go') in poll 'Best programming language?' ending 2021-05-03"
+        );
+
+        let create = VotingInstruction::CreatePoll {
+            poll_id: 0,
+            question: poll.question.clone(),
+            options: poll.options.clone(),
+            start_time: poll.start_time,
+            end_time: poll.end_time,
+            moderator: poll.moderator,
+            allow_new_options: poll.allow_new_options,
+            attestors: poll.attestors.clone(),
+            poll_type: poll.poll_type,
+            candidates: poll.candidates.clone(),
+            max_spoiled_bps: poll.max_spoiled_bps,
+            weight_mint: poll.weight_mint,
+            blackout_secs: poll.blackout_secs,
+            max_selections: poll.max_selections,
+            terms_hash: poll.terms_hash,
+            weight_program: poll.weight_program,
+            unique_question: poll.unique_question,
+            quorum: poll.quorum,
+            approval_threshold_bps: poll.approval_threshold_bps,
+            tie_break: poll.tie_break,
+            eligibility: poll.eligibility.clone(),
+            max_delegation_depth: 0,
+            weight_curve: poll.weight_curve,
+            points_per_voter: poll.points_per_voter,
+            seats: poll.seats,
+            clock_tolerance_secs: poll.clock_tolerance_secs,
+        };
+        assert_eq!(
+            summarize(&create.try_to_vec().unwrap(), None),
+            "Create poll 'Best programming language?' with 2 options ending 2021-05-03"
+        );
+        let proposal = VotingInstruction::CreateProposal {
+            poll_id: 0,
+            question: "Fund the audit?".to_string(),
+            start_time: poll.start_time,
+            end_time: poll.end_time,
+            quorum: Some(10),
+            approval_threshold_bps: 0,
+        };
+        assert_eq!(
+            summarize(&proposal.try_to_vec().unwrap(), None),
+            "Create yes/no proposal 'Fund the audit?' ending 2021-05-03"
+        );
+        let cumulative = VotingInstruction::VoteCumulative {
+            allocations: vec![(0, 7), (1, 3)],
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            summarize(&cumulative.try_to_vec().unwrap(), None),
+            "Give 7 points to option 0, 3 points to option 1"
+        );
+        assert_eq!(
+            summarize(&VotingInstruction::GetVersion.try_to_vec().unwrap(), None),
+            "Check the voting program version"
+        );
+        let read_only = VotingInstruction::SetReadOnlyMode { read_only_mode: true };
+        assert_eq!(
+            summarize(&read_only.try_to_vec().unwrap(), None),
+            "Make the voting program read-only"
+        );
+        let policy =
+            VotingInstruction::SetCallPolicy { instruction: 20, policy: CallPolicy::TopLevelOnly };
+        assert_eq!(
+            summarize(&policy.try_to_vec().unwrap(), None),
+            "Allow voting instruction 20 only at the top level"
+        );
+        assert_eq!(summarize(&[0xff], None), "Unrecognized voting instruction");
+
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+    }
+}
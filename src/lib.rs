@@ -0,0 +1,36 @@
+// src/lib.rs
+
+//! Decentralized voting program for the Arch network.
+//!
+//! Build with the `no-entrypoint` feature to use the instruction, state and
+//! error types from another program or a client without linking the
+//! program entrypoint. The `test-utils` feature adds fixtures for writing
+//! program tests against this crate.
+
+pub mod attestation;
+pub mod budget;
+pub mod cursor;
+pub mod eligibility;
+pub mod error;
+pub mod history;
+pub mod instruction;
+pub mod merkle;
+pub mod payload;
+pub mod processor;
+pub mod state;
+pub mod stv;
+pub mod tally;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod vectors;
+pub mod verify;
+pub mod weight;
+
+#[cfg(not(feature = "no-entrypoint"))]
+mod entrypoint {
+    use arch_program::entrypoint;
+
+    use crate::processor::process_instruction;
+
+    entrypoint!(process_instruction);
+}
@@ -0,0 +1,262 @@
+// src/merkle.rs
+
+//! Merkle proof verification shared by allowlists, snapshots and nullifier sets.
+//!
+//! All hashes are SHA-256 with a one-byte domain tag so a leaf can never be
+//! passed off as an interior node:
+//!
+//! * leaf: `H(0x00 || data)`
+//! * node: `H(0x01 || left || right)`
+//!
+//! Standard trees hash each pair in sorted order, so proofs are a plain list of
+//! siblings with no direction bits. An odd node at the end of a level is
+//! carried up unchanged. Sparse trees are positional: the bits of the 32-byte
+//! key, most significant first, choose the path from the root.
+
+use sha2::{Digest, Sha256};
+
+/// A 32-byte SHA-256 digest.
+pub type Hash = [u8; 32];
+
+/// Depth of a sparse Merkle tree keyed by 32-byte keys.
+pub const SPARSE_TREE_DEPTH: usize = 256;
+
+/// Value of an empty sparse-tree leaf.
+pub const EMPTY_LEAF: Hash = [0u8; 32];
+
+const LEAF_TAG: u8 = 0x00;
+const NODE_TAG: u8 = 0x01;
+
+/// Hashes raw leaf data.
+pub fn hash_leaf(data: &[u8]) -> Hash {
+    Sha256::new().chain_update([LEAF_TAG]).chain_update(data).finalize().into()
+}
+
+/// Hashes two children in the given order.
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new().chain_update([NODE_TAG]).chain_update(left).chain_update(right).finalize().into()
+}
+
+/// Hashes two children of a standard tree, independent of their order.
+pub fn hash_node(a: &Hash, b: &Hash) -> Hash {
+    if a <= b {
+        hash_pair(a, b)
+    } else {
+        hash_pair(b, a)
+    }
+}
+
+/// Computes the root of a standard tree over already-hashed leaves.
+///
+/// Returns `None` for an empty leaf set.
+pub fn compute_root(leaves: &[Hash]) -> Option<Hash> {
+    if leaves.is_empty() {
+        return None;
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [odd] => *odd,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    Some(level[0])
+}
+
+/// Verifies that `leaf` is in the standard tree with the given `root`.
+pub fn verify_proof(root: &Hash, leaf: &Hash, proof: &[Hash]) -> bool {
+    let computed = proof.iter().fold(*leaf, |node, sibling| hash_node(&node, sibling));
+    computed == *root
+}
+
+/// Compressed proof for a sparse Merkle tree.
+///
+/// Most siblings along a sparse path are roots of empty subtrees. Only the
+/// non-empty ones are stored. Bit `i` of `bitmap` (byte `i / 8`, least
+/// significant bit first) is set when the sibling at height `i` above the
+/// leaf is non-empty. The entries of `siblings` are the non-empty siblings
+/// in order of increasing height.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseProof {
+    pub bitmap: [u8; 32],
+    pub siblings: Vec<Hash>,
+}
+
+/// Hashes a sparse-tree leaf binding `key` to `value`.
+pub fn hash_sparse_leaf(key: &Hash, value: &Hash) -> Hash {
+    Sha256::new().chain_update([LEAF_TAG]).chain_update(key).chain_update(value).finalize().into()
+}
+
+/// Roots of empty sparse subtrees, indexed by height (0 = leaf).
+fn empty_subtree_roots() -> Vec<Hash> {
+    let mut roots = Vec::with_capacity(SPARSE_TREE_DEPTH + 1);
+    roots.push(EMPTY_LEAF);
+    for height in 0..SPARSE_TREE_DEPTH {
+        let below = roots[height];
+        roots.push(hash_pair(&below, &below));
+    }
+    roots
+}
+
+/// Root of a sparse tree with no entries.
+pub fn empty_sparse_root() -> Hash {
+    empty_subtree_roots()[SPARSE_TREE_DEPTH]
+}
+
+/// Verifies a sparse-tree proof.
+///
+/// With `Some(value)` this proves `key` maps to `value`. With `None` it proves
+/// `key` is absent, which is how nullifier sets show a nullifier is unused.
+pub fn verify_sparse_proof(
+    root: &Hash,
+    key: &Hash,
+    value: Option<&Hash>,
+    proof: &SparseProof,
+) -> bool {
+    let empty = empty_subtree_roots();
+    let mut siblings = proof.siblings.iter();
+    let mut node = match value {
+        Some(value) => hash_sparse_leaf(key, value),
+        None => EMPTY_LEAF,
+    };
+
+    for (height, empty_sibling) in empty.iter().take(SPARSE_TREE_DEPTH).enumerate() {
+        let sibling = if proof.bitmap[height / 8] & (1 << (height % 8)) != 0 {
+            match siblings.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            }
+        } else {
+            empty_sibling
+        };
+
+        // The key bit at tree depth `d` decides the side; depth counts from the root.
+        let depth = SPARSE_TREE_DEPTH - 1 - height;
+        let goes_right = key[depth / 8] & (0x80 >> (depth % 8)) != 0;
+        node = if goes_right { hash_pair(sibling, &node) } else { hash_pair(&node, sibling) };
+    }
+
+    siblings.next().is_none() && node == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex32(hex: &str) -> Hash {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    fn names() -> Vec<Hash> {
+        ["alice", "bob", "carol", "dave", "erin"]
+            .iter()
+            .map(|name| hash_leaf(name.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_standard_vectors() {
+        assert_eq!(
+            hash_leaf(b"alice"),
+            hex32("1255dacaa637f70cb606763d3035c7ef6b52eb2983c5cd906f714c3818d0dfaa")
+        );
+        assert_eq!(
+            compute_root(&names()[..4]),
+            Some(hex32("559c8e726262e509065de92d8ad3a30878b49d00517d530f873457fa550a7fa7"))
+        );
+        assert_eq!(
+            compute_root(&names()),
+            Some(hex32("5d92e1a57ecd743bab8c7132afad8a9038eb05188c8f0ef4eeed2b2c0f0cf576"))
+        );
+        assert_eq!(compute_root(&[]), None);
+    }
+
+    #[test]
+    fn test_standard_proof() {
+        let leaves = names();
+        let root = compute_root(&leaves).unwrap();
+        let proof = [
+            leaves[1],
+            hex32("445601a4a41e04a744bbe21f63b917bc517017d0e34f4ad28c66576021bdc635"),
+            leaves[4],
+        ];
+
+        assert!(verify_proof(&root, &leaves[0], &proof));
+        // Wrong leaf or truncated proof.
+        assert!(!verify_proof(&root, &leaves[2], &proof));
+        assert!(!verify_proof(&root, &leaves[0], &proof[..2]));
+        // The odd leaf carried up to the top needs only one sibling.
+        assert!(verify_proof(&root, &leaves[4], &[compute_root(&leaves[..4]).unwrap()]));
+    }
+
+    fn sparse_fixture() -> (Hash, Hash, Hash, Hash) {
+        let mut k1 = [0u8; 32];
+        k1[0] = 0x80;
+        let mut k2 = [0u8; 32];
+        k2[31] = 0x01;
+        let root = hex32("d3885b53c6b64c20064b9cb8a0e80280cf6d14b6c164d22accbab71af26cbfe5");
+        (root, k1, k2, Sha256::digest(b"one").into())
+    }
+
+    #[test]
+    fn test_sparse_empty_root() {
+        assert_eq!(
+            empty_sparse_root(),
+            hex32("6155289130893872355eac98042d22aefa2c2e708bea169402760e3b55f9a2dc")
+        );
+        let proof = SparseProof { bitmap: [0u8; 32], siblings: vec![] };
+        assert!(verify_sparse_proof(&empty_sparse_root(), &[7u8; 32], None, &proof));
+    }
+
+    #[test]
+    fn test_sparse_inclusion() {
+        let (root, k1, k2, v1) = sparse_fixture();
+        let mut bitmap = [0u8; 32];
+        bitmap[31] = 0x80; // only the top-level sibling is non-empty
+        let proof = SparseProof {
+            bitmap,
+            siblings: vec![hex32(
+                "d540e4b3145011248c7817e2b180aa32df0792a170460c0eed233dca1965887a",
+            )],
+        };
+
+        assert!(verify_sparse_proof(&root, &k1, Some(&v1), &proof));
+        assert!(!verify_sparse_proof(&root, &k1, Some(&[0u8; 32]), &proof));
+        assert!(!verify_sparse_proof(&root, &k1, None, &proof));
+        assert!(!verify_sparse_proof(&root, &k2, Some(&v1), &proof));
+
+        // Extra or missing siblings are rejected.
+        let mut padded = proof.clone();
+        padded.siblings.push([0u8; 32]);
+        assert!(!verify_sparse_proof(&root, &k1, Some(&v1), &padded));
+        let short = SparseProof { bitmap, siblings: vec![] };
+        assert!(!verify_sparse_proof(&root, &k1, Some(&v1), &short));
+    }
+
+    #[test]
+    fn test_sparse_non_membership() {
+        let (root, _, _, _) = sparse_fixture();
+        let mut k3 = [0u8; 32];
+        k3[0] = 0xC0;
+        let mut bitmap = [0u8; 32];
+        bitmap[31] = 0xC0; // siblings at depth 1 (k1's subtree) and depth 0 (k2's)
+        let proof = SparseProof {
+            bitmap,
+            siblings: vec![
+                hex32("dc9056387e188ab0c770d18dd3cea531e4acbe729ad17dda1d72892275e26d24"),
+                hex32("d540e4b3145011248c7817e2b180aa32df0792a170460c0eed233dca1965887a"),
+            ],
+        };
+
+        assert!(verify_sparse_proof(&root, &k3, None, &proof));
+        assert!(!verify_sparse_proof(&root, &k3, Some(&[1u8; 32]), &proof));
+    }
+}
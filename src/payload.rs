@@ -0,0 +1,138 @@
+// src/payload.rs
+
+//! Compact vote payloads for QR codes.
+//!
+//! A payload is encoded as `VOTE<version>:<HEX>`, where `HEX` is the upper-case
+//! hex encoding of the Borsh-serialized [`VotePayload`]. Every character is in
+//! the QR alphanumeric set, which packs denser than byte mode.
+
+use arch_program::pubkey::Pubkey;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{instruction::VotingInstruction, merkle::Hash};
+
+/// Current payload format version.
+pub const PAYLOAD_VERSION: u8 = 1;
+
+const PREFIX: &str = "VOTE";
+
+/// A request to vote for one option of a poll.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VotePayload {
+    /// Poll account to vote on.
+    pub poll: Pubkey,
+    /// Option index to vote for.
+    pub option_index: u32,
+    /// Unix timestamp (seconds) after which the payload must not be used.
+    pub expiry: u64,
+}
+
+/// Reasons a payload string could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadError {
+    /// The string does not start with `VOTE<version>:`.
+    InvalidPrefix,
+    /// The payload was produced by an unknown format version.
+    UnsupportedVersion(u8),
+    /// The body is not valid hex or does not decode to a payload.
+    InvalidEncoding,
+}
+
+impl VotePayload {
+    /// Encodes the payload as a QR-friendly string.
+    pub fn encode(&self) -> String {
+        let body = self.try_to_vec().expect("serializing to a Vec cannot fail");
+        let mut encoded = format!("{}{}:", PREFIX, PAYLOAD_VERSION);
+        for byte in body {
+            encoded.push_str(&format!("{:02X}", byte));
+        }
+        encoded
+    }
+
+    /// Decodes a string produced by [`VotePayload::encode`].
+    pub fn decode(encoded: &str) -> Result<Self, PayloadError> {
+        let rest = encoded.strip_prefix(PREFIX).ok_or(PayloadError::InvalidPrefix)?;
+        let (version, body) = rest.split_once(':').ok_or(PayloadError::InvalidPrefix)?;
+        let version: u8 = version.parse().map_err(|_| PayloadError::InvalidPrefix)?;
+        if version != PAYLOAD_VERSION {
+            return Err(PayloadError::UnsupportedVersion(version));
+        }
+
+        if body.len() % 2 != 0 || !body.is_ascii() {
+            return Err(PayloadError::InvalidEncoding);
+        }
+        let bytes = (0..body.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&body[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| PayloadError::InvalidEncoding)?;
+
+        Self::try_from_slice(&bytes).map_err(|_| PayloadError::InvalidEncoding)
+    }
+
+    /// Whether the payload has expired at unix time `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expiry
+    }
+
+    /// Instruction data for the `Vote` this payload asks for. Set
+    /// `accept_terms` once the voter has accepted the poll's terms, if any,
+    /// and pass the voter's `eligibility_proof` on polls with an allowlist.
+    pub fn instruction_data(&self, accept_terms: bool, eligibility_proof: Vec<Hash>) -> Vec<u8> {
+        VotingInstruction::Vote { option_index: self.option_index, accept_terms, eligibility_proof }
+            .try_to_vec()
+            .expect("serializing to a Vec cannot fail")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> VotePayload {
+        let mut bytes = [0u8; 32];
+        bytes[0] = 7;
+        VotePayload { poll: Pubkey::new_from_array(bytes), option_index: 2, expiry: 1_620_000_000 }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let encoded = payload().encode();
+        assert!(encoded.starts_with("VOTE1:07"));
+        assert!(encoded.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || c == ':'));
+        assert_eq!(VotePayload::decode(&encoded), Ok(payload()));
+        // 32-byte poll + u32 option + u64 expiry, hex encoded.
+        assert_eq!(encoded.len(), "VOTE1:".len() + 2 * 44);
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_input() {
+        let encoded = payload().encode();
+        assert_eq!(VotePayload::decode("POLL1:00"), Err(PayloadError::InvalidPrefix));
+        assert_eq!(
+            VotePayload::decode(&encoded.replacen("VOTE1", "VOTE9", 1)),
+            Err(PayloadError::UnsupportedVersion(9))
+        );
+        assert_eq!(
+            VotePayload::decode(&encoded[..encoded.len() - 2]),
+            Err(PayloadError::InvalidEncoding)
+        );
+        assert_eq!(VotePayload::decode("VOTE1:ZZ"), Err(PayloadError::InvalidEncoding));
+    }
+
+    #[test]
+    fn test_expiry_and_instruction() {
+        let payload = payload();
+        assert!(!payload.is_expired(1_620_000_000));
+        assert!(payload.is_expired(1_620_000_001));
+        let data = payload.instruction_data(true, vec![[5u8; 32]]);
+        assert_eq!(
+            VotingInstruction::try_from_slice(&data).unwrap(),
+            VotingInstruction::Vote {
+                option_index: 2,
+                accept_terms: true,
+                eligibility_proof: vec![[5u8; 32]],
+            }
+        );
+    }
+}
@@ -0,0 +1,6421 @@
+// src/processor.rs
+
+use arch_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::{get_stack_height, invoke_signed, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::{self, Sysvar},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    attestation::ResultAttestation,
+    budget::{apportion, BUDGET_SCALE},
+    cursor::{
+        find_cursor_address, CursorTask, TallyRounds, WorkCursor, CURSOR_SEED,
+        MAX_CURSOR_ACCOUNT_LEN,
+    },
+    eligibility::{check_eligibility, EligibilityRule},
+    error::VotingError,
+    instruction::{
+        AccountSpec, ProgramVersion, VotingInstruction, CONFIG_FREE_TAGS, INSTRUCTION_COUNT,
+    },
+    merkle::Hash,
+    state::{
+        cumulative_ballot_len, find_attestation_address, find_ballot_address, find_config_address,
+        find_poll_address, find_question_address, find_receipt_address, find_snapshot_address,
+        find_voter_record_address, multi_ballot_len, question_hash, ranked_ballot_len,
+        unpack_record, AttestationRecord, CallPolicy, CumulativeBallot, MultiBallot, Poll,
+        PollType, ProgramConfig, QuestionRecord, RankedBallot, ResultSnapshot, TieBreak,
+        VoteReceipt, VoterRecord, ABSTAINED_BALLOT, ATTESTATION_RECORD_LEN, ATTESTATION_SEED,
+        BALLOT_SEED, BPS_DENOMINATOR, CONFIG_SEED, MAX_ATTESTORS, MAX_CLOCK_TOLERANCE_SECS,
+        MAX_CONDORCET_OPTIONS, MAX_CPI_ALLOWLIST, MAX_DELEGATION_DEPTH, MAX_OPTIONS,
+        MAX_OPTION_LEN, MAX_POLL_ACCOUNT_LEN, MAX_POLL_DURATION, MAX_QUESTION_LEN,
+        MAX_SNAPSHOT_LABEL_LEN, POLL_SEED, PROGRAM_CONFIG_LEN, PROPOSAL_OPTIONS,
+        QUESTION_RECORD_LEN, QUESTION_SEED, RECEIPT_SEED, SNAPSHOT_SEED, SPOILED_BALLOT,
+        VOTER_RECORD_LEN, VOTER_SEED, VOTE_RECEIPT_LEN,
+    },
+    stv::{single_transferable_vote, StvEvent, StvRound},
+    tally::{
+        add_pairwise, borda_points, instant_runoff_grouped, runoff_remaining, runoff_step,
+        RunoffStep,
+    },
+    weight::{weight_source, WeightCurve},
+};
+
+/// Source of the current on-chain time.
+///
+/// On chain the clock comes from the clock sysvar account passed with each
+/// instruction. Tests supply a fixed clock through
+/// `process_instruction_with_clock` instead.
+pub trait TimeProvider {
+    /// Returns the current clock, given the clock account passed to the instruction.
+    fn clock(&self, clock_account: &AccountInfo) -> Result<Clock, ProgramError>;
+}
+
+/// Reads the clock from the clock sysvar account.
+pub struct SysvarClock;
+
+impl TimeProvider for SysvarClock {
+    fn clock(&self, clock_account: &AccountInfo) -> Result<Clock, ProgramError> {
+        if *clock_account.key != sysvar::clock::ID {
+            msg!("Expected the clock sysvar account.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        Clock::from_account_info(clock_account)
+    }
+}
+
+/// Entry point of the program.
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    process_instruction_with_clock(program_id, accounts, instruction_data, &SysvarClock)
+}
+
+/// Processes an instruction, reading the current time from `time`.
+pub fn process_instruction_with_clock(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+    time: &dyn TimeProvider,
+) -> ProgramResult {
+    let instruction = VotingInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let (accounts, config) = split_config(program_id, accounts, &instruction)?;
+    ensure_runs(&instruction, &config, accounts)?;
+    let policy = config.call_policy(instruction_data[0]);
+    ensure_caller_allowed(program_id, accounts, policy, &config.cpi_allowlist, get_stack_height())?;
+    check_accounts(program_id, accounts, instruction.accounts())?;
+
+    match instruction {
+        VotingInstruction::CreatePoll {
+            poll_id,
+            question,
+            options,
+            start_time,
+            end_time,
+            moderator,
+            allow_new_options,
+            attestors,
+            poll_type,
+            candidates,
+            max_spoiled_bps,
+            weight_mint,
+            blackout_secs,
+            max_selections,
+            terms_hash,
+            weight_program,
+            unique_question,
+            quorum,
+            approval_threshold_bps,
+            tie_break,
+            eligibility,
+            max_delegation_depth,
+            weight_curve,
+            points_per_voter,
+            seats,
+            clock_tolerance_secs,
+        } => process_create_poll(
+            program_id,
+            accounts,
+            time,
+            poll_id,
+            question,
+            options,
+            start_time,
+            end_time,
+            moderator,
+            allow_new_options,
+            attestors,
+            poll_type,
+            candidates,
+            max_spoiled_bps,
+            weight_mint,
+            blackout_secs,
+            max_selections,
+            terms_hash,
+            weight_program,
+            unique_question,
+            quorum,
+            approval_threshold_bps,
+            tie_break,
+            eligibility,
+            max_delegation_depth,
+            weight_curve,
+            points_per_voter,
+            seats,
+            clock_tolerance_secs,
+            false,
+        ),
+        VotingInstruction::Vote { option_index, accept_terms, eligibility_proof } => {
+            process_vote(program_id, accounts, time, option_index, accept_terms, eligibility_proof)
+        },
+        VotingInstruction::ClosePoll => process_close_poll(program_id, accounts, time),
+        VotingInstruction::GetVersion => process_get_version(),
+        VotingInstruction::ChangeVote { new_option_index } => {
+            process_change_vote(program_id, accounts, time, new_option_index)
+        },
+        VotingInstruction::CancelPoll => process_cancel_poll(program_id, accounts, time),
+        VotingInstruction::QuarantinePoll => process_quarantine_poll(accounts),
+        VotingInstruction::UpdatePoll { question, options, start_time, end_time } => {
+            process_update_poll(program_id, accounts, time, question, options, start_time, end_time)
+        },
+        VotingInstruction::AddOption { label } => process_add_option(accounts, time, label),
+        VotingInstruction::ExtendPoll { new_end_time } => {
+            process_extend_poll(accounts, time, new_end_time)
+        },
+        VotingInstruction::SubmitAttestation { signature } => {
+            process_submit_attestation(program_id, accounts, signature)
+        },
+        VotingInstruction::RankedVote { ranking, accept_terms, eligibility_proof } => {
+            process_ranked_vote(
+                program_id,
+                accounts,
+                time,
+                ranking,
+                accept_terms,
+                eligibility_proof,
+            )
+        },
+        VotingInstruction::Tally => process_tally(program_id, accounts),
+        VotingInstruction::WithdrawOption { option_index } => {
+            process_withdraw_option(accounts, time, option_index)
+        },
+        VotingInstruction::SpoilBallot { accept_terms, eligibility_proof } => process_blank_ballot(
+            program_id,
+            accounts,
+            time,
+            SPOILED_BALLOT,
+            accept_terms,
+            eligibility_proof,
+        ),
+        VotingInstruction::VoteMulti { option_indices, accept_terms, eligibility_proof } => {
+            process_vote_multi(
+                program_id,
+                accounts,
+                time,
+                option_indices,
+                accept_terms,
+                eligibility_proof,
+            )
+        },
+        VotingInstruction::Abstain { accept_terms, eligibility_proof } => process_blank_ballot(
+            program_id,
+            accounts,
+            time,
+            ABSTAINED_BALLOT,
+            accept_terms,
+            eligibility_proof,
+        ),
+        VotingInstruction::FinalizePoll => process_finalize_poll(accounts),
+        VotingInstruction::SnapshotResult { label } => {
+            process_snapshot_result(program_id, accounts, time, label)
+        },
+        VotingInstruction::TallyBatch => process_tally_batch(program_id, accounts),
+        VotingInstruction::RegisterVoter { delegate } => {
+            process_register_voter(program_id, accounts, time, delegate)
+        },
+        VotingInstruction::SetDelegate { delegate } => {
+            process_set_delegate(program_id, accounts, Some(delegate))
+        },
+        VotingInstruction::RevokeDelegate => process_set_delegate(program_id, accounts, None),
+        VotingInstruction::VoteCumulative { allocations, accept_terms, eligibility_proof } => {
+            process_vote_cumulative(
+                program_id,
+                accounts,
+                time,
+                allocations,
+                accept_terms,
+                eligibility_proof,
+            )
+        },
+        VotingInstruction::CreateProposal {
+            poll_id,
+            question,
+            start_time,
+            end_time,
+            quorum,
+            approval_threshold_bps,
+        } => process_create_proposal(
+            program_id,
+            accounts,
+            time,
+            poll_id,
+            question,
+            start_time,
+            end_time,
+            quorum,
+            approval_threshold_bps,
+        ),
+        VotingInstruction::InitializeConfig { admin, read_only_mode } => {
+            process_initialize_config(program_id, accounts, admin, read_only_mode)
+        },
+        VotingInstruction::SetReadOnlyMode { read_only_mode } => {
+            process_set_read_only_mode(program_id, accounts, read_only_mode)
+        },
+        VotingInstruction::SetCallPolicy { instruction, policy } => {
+            process_set_call_policy(program_id, accounts, instruction, policy)
+        },
+        VotingInstruction::SetCpiAllowlist { programs } => {
+            process_set_cpi_allowlist(program_id, accounts, programs)
+        },
+    }
+}
+
+/// Creates a new poll. Initializes the poll account with the provided data.
+#[allow(clippy::too_many_arguments)]
+fn process_create_poll(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    poll_id: u64,
+    question: String,
+    options: Vec<String>,
+    start_time: u64,
+    end_time: u64,
+    moderator: Pubkey,
+    allow_new_options: bool,
+    attestors: Vec<Pubkey>,
+    poll_type: PollType,
+    candidates: Vec<Option<Pubkey>>,
+    max_spoiled_bps: u16,
+    weight_mint: Option<Pubkey>,
+    blackout_secs: u64,
+    max_selections: u8,
+    terms_hash: Option<Hash>,
+    weight_program: Option<Pubkey>,
+    unique_question: bool,
+    quorum: Option<u64>,
+    approval_threshold_bps: u16,
+    tie_break: TieBreak,
+    eligibility: EligibilityRule,
+    max_delegation_depth: u8,
+    weight_curve: WeightCurve,
+    points_per_voter: u64,
+    seats: u8,
+    clock_tolerance_secs: u64,
+    is_proposal: bool,
+) -> ProgramResult {
+    // Poll account (writable), creator (signer, pays), clock sysvar, then the system
+    // program if the poll account or a question record is created here, and the
+    // question record with `unique_question`. Checked against
+    // `CREATE_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let creator_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let creates_poll_account = *poll_account.owner == system_program::ID;
+    let system_program_account = if creates_poll_account || unique_question {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+
+    validate_poll_params(&question, &options, start_time, end_time)?;
+    if clock_tolerance_secs > MAX_CLOCK_TOLERANCE_SECS {
+        msg!("Clock tolerance exceeds {} seconds.", MAX_CLOCK_TOLERANCE_SECS);
+        return Err(VotingError::InvalidVotingWindow.into());
+    }
+    if attestors.len() > MAX_ATTESTORS {
+        msg!("Poll names more than {} attestors.", MAX_ATTESTORS);
+        return Err(VotingError::PollTooLarge.into());
+    }
+    if candidates.len() > options.len() {
+        msg!("Poll names more candidates than options.");
+        return Err(VotingError::PollTooLarge.into());
+    }
+    if u64::from(max_spoiled_bps) > BPS_DENOMINATOR {
+        msg!("Spoiled ballot threshold exceeds {} basis points.", BPS_DENOMINATOR);
+        return Err(VotingError::InvalidThreshold.into());
+    }
+    if u64::from(approval_threshold_bps) > BPS_DENOMINATOR {
+        msg!("Approval threshold exceeds {} basis points.", BPS_DENOMINATOR);
+        return Err(VotingError::InvalidThreshold.into());
+    }
+    // Ranked-choice and Condorcet winners come from the runoff or the
+    // head-to-head contests, not the standings, so only plurality and Borda
+    // polls are judged by their leading option's share.
+    let by_standings = matches!(poll_type, PollType::Plurality | PollType::Borda);
+    if approval_threshold_bps > 0 && !by_standings {
+        msg!("Only plurality and Borda polls can set an approval threshold.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if tie_break != TieBreak::Fail && !by_standings && poll_type != PollType::Condorcet {
+        msg!("Only plurality, Borda and Condorcet polls can set a tie-break policy.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    eligibility.validate()?;
+    let weighted = weight_mint.is_some() || weight_program.is_some();
+    if weight_mint.is_some() && weight_program.is_some() {
+        msg!("Poll names both a weight mint and a weight program.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if weighted && poll_type != PollType::Plurality {
+        msg!("Only plurality polls can be weighted.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if !weighted && weight_curve != WeightCurve::LINEAR {
+        msg!("Only weighted polls can bound or transform weights.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    weight_curve.validate()?;
+    if max_selections as usize > options.len() {
+        msg!("Ballots cannot select more than the {} options.", options.len());
+        return Err(VotingError::TooManySelections.into());
+    }
+    if max_selections > 1 && (poll_type != PollType::Plurality || weighted) {
+        msg!("Only unweighted plurality polls can be multi-select.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    let budget = poll_type == PollType::Budget;
+    if points_per_voter > 0
+        && ((poll_type != PollType::Plurality && !budget) || weighted || max_selections > 1)
+    {
+        msg!("Only unweighted single-choice plurality polls can be cumulative.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if budget && points_per_voter == 0 {
+        msg!("Budget polls need a budget of points per voter.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    // The pairwise matrix grows with the square of the options and is not
+    // held back in a blackout, so Condorcet polls keep a fixed, small slate.
+    if poll_type == PollType::Condorcet {
+        if options.len() > MAX_CONDORCET_OPTIONS {
+            msg!("Condorcet polls are limited to {} options.", MAX_CONDORCET_OPTIONS);
+            return Err(VotingError::PollTooLarge.into());
+        }
+        if allow_new_options || blackout_secs > 0 {
+            msg!("Condorcet polls cannot add options or hold back ballots.");
+            return Err(VotingError::WrongPollType.into());
+        }
+    }
+    // A Borda ballot gives out points by the size of the slate it was cast
+    // on, so the slate cannot grow under ballots already cast.
+    if poll_type == PollType::Borda && allow_new_options {
+        msg!("Borda polls cannot add options.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if seats > 1 && poll_type != PollType::RankedChoice {
+        msg!("Only ranked-choice polls can elect more than one option.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if seats as usize > options.len() {
+        msg!("Poll cannot elect more than its {} options.", options.len());
+        return Err(VotingError::PollTooLarge.into());
+    }
+    if max_delegation_depth > MAX_DELEGATION_DEPTH {
+        msg!("Delegation chains are limited to {} steps.", MAX_DELEGATION_DEPTH);
+        return Err(VotingError::PollTooLarge.into());
+    }
+    // Delegators' ballots are cast without their weight, eligibility accounts
+    // or acceptance of terms, so only polls needing none of these count them;
+    // a registration requirement is met by the records passed for them.
+    let delegation_compatible = poll_type == PollType::Plurality
+        && !weighted
+        && max_selections <= 1
+        && points_per_voter == 0
+        && terms_hash.is_none()
+        && matches!(eligibility, EligibilityRule::Open | EligibilityRule::Registered);
+    if max_delegation_depth > 0 && !delegation_compatible {
+        msg!("Delegated ballots need an unweighted single-choice poll without terms.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if poll_account.data_len() > MAX_POLL_ACCOUNT_LEN {
+        msg!("Poll account exceeds {} bytes.", MAX_POLL_ACCOUNT_LEN);
+        return Err(VotingError::PollTooLarge.into());
+    }
+
+    // The poll must live at the address derived from its creator and id.
+    let (poll_address, bump) = find_poll_address(program_id, creator_account.key, poll_id);
+    if *poll_account.key != poll_address {
+        msg!("Poll account is not the address of poll {} for this creator.", poll_id);
+        return Err(VotingError::AccountMismatch.into());
+    }
+
+    // A registered question must not belong to another of the creator's polls.
+    let question_record = if unique_question {
+        let record_account = next_account_info(account_info_iter)?;
+        let hash = question_hash(&question);
+        let (record_address, bump) = find_question_address(program_id, creator_account.key, &hash);
+        if *record_account.key != record_address {
+            msg!("Question record is not the address for this creator and question.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        if record_account.owner == program_id {
+            let existing = QuestionRecord::try_from_slice(&record_account.data.borrow())
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            msg!("Creator already asked this question in poll {:?}.", existing.poll);
+            return Err(VotingError::DuplicateQuestion.into());
+        }
+        if *record_account.owner != system_program::ID {
+            msg!("Question record is owned by another program.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Some((record_account, hash, bump))
+    } else {
+        None
+    };
+
+    if let (true, Some(system_program_account)) = (creates_poll_account, system_program_account) {
+        // Fresh address: allocate the poll account, signing for the PDA.
+        create_pda_account(
+            program_id,
+            creator_account,
+            poll_account,
+            system_program_account,
+            MAX_POLL_ACCOUNT_LEN,
+            &[POLL_SEED, creator_account.key.as_ref(), &poll_id.to_le_bytes(), &[bump]],
+        )?;
+    } else if poll_account.owner != program_id {
+        msg!("Poll account is not owned by the program.");
+        return Err(ProgramError::IncorrectProgramId);
+    } else if poll_account.data.borrow().iter().any(|&byte| byte != 0) {
+        msg!("Poll account is already initialized.");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    // Initialize vote counts (and weights, points or scores, if weighted,
+    // cumulative or Borda) for each option.
+    let vote_counts = vec![0; options.len()];
+    let vote_weights = if weighted || points_per_voter > 0 || poll_type == PollType::Borda {
+        vec![0; options.len()]
+    } else {
+        Vec::new()
+    };
+    let pairwise = if poll_type == PollType::Condorcet {
+        vec![vec![0; options.len()]; options.len()]
+    } else {
+        Vec::new()
+    };
+    let poll = Poll {
+        creator: *creator_account.key,
+        question,
+        options,
+        vote_counts,
+        start_time,
+        end_time,
+        is_closed: false,
+        voters: Vec::new(),
+        created_at_slot: clock.slot,
+        created_at_time: clock.unix_timestamp as u64,
+        first_vote_slot: None,
+        poll_id,
+        is_cancelled: false,
+        moderator,
+        is_quarantined: false,
+        allow_new_options,
+        attestors,
+        closed_at_slot: None,
+        poll_type,
+        is_tallied: false,
+        winner: None,
+        candidates,
+        withdrawn_options: Vec::new(),
+        spoiled_count: 0,
+        max_spoiled_bps,
+        is_void: false,
+        weight_mint,
+        vote_weights,
+        blackout_secs,
+        pending_counts: Vec::new(),
+        pending_weights: Vec::new(),
+        max_selections,
+        abstain_count: 0,
+        terms_hash,
+        weight_program,
+        unique_question,
+        quorum,
+        outcome: None,
+        approval_threshold_bps,
+        tied_options: Vec::new(),
+        tie_break,
+        eligibility,
+        max_delegation_depth,
+        weight_curve,
+        points_per_voter,
+        pairwise,
+        seats,
+        elected: Vec::new(),
+        is_proposal,
+        clock_tolerance_secs,
+    };
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if let (Some((record_account, hash, bump)), Some(system_program_account)) =
+        (question_record, system_program_account)
+    {
+        create_pda_account(
+            program_id,
+            creator_account,
+            record_account,
+            system_program_account,
+            QUESTION_RECORD_LEN,
+            &[QUESTION_SEED, creator_account.key.as_ref(), &hash, &[bump]],
+        )?;
+        QuestionRecord { poll: *poll_account.key }
+            .serialize(&mut &mut record_account.data.borrow_mut()[..])
+            .map_err(|_| ProgramError::AccountDataTooSmall)?;
+    }
+
+    msg!("Poll created successfully.");
+    Ok(())
+}
+
+/// Creates a yes/no proposal: a poll with the options `PROPOSAL_OPTIONS`,
+/// moderated by its creator, with `CreatePoll`'s defaults for everything but
+/// the quorum and approval threshold.
+#[allow(clippy::too_many_arguments)]
+fn process_create_proposal(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    poll_id: u64,
+    question: String,
+    start_time: u64,
+    end_time: u64,
+    quorum: Option<u64>,
+    approval_threshold_bps: u16,
+) -> ProgramResult {
+    // Same accounts as `CreatePoll`; the creator, second, moderates.
+    let creator = *accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?.key;
+    let options = PROPOSAL_OPTIONS.iter().map(|option| option.to_string()).collect();
+    process_create_poll(
+        program_id,
+        accounts,
+        time,
+        poll_id,
+        question,
+        options,
+        start_time,
+        end_time,
+        creator,
+        false,
+        Vec::new(),
+        PollType::Plurality,
+        Vec::new(),
+        0,
+        None,
+        0,
+        0,
+        None,
+        None,
+        false,
+        quorum,
+        approval_threshold_bps,
+        TieBreak::Fail,
+        EligibilityRule::Open,
+        0,
+        WeightCurve::LINEAR,
+        0,
+        0,
+        0,
+        true,
+    )
+}
+
+/// Casts a vote on a given poll.
+fn process_vote(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    option_index: u32,
+    accept_terms: bool,
+    eligibility_proof: Vec<Hash>,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (signer unless a delegate
+    // signs, pays), clock sysvar, receipt account (writable), system program.
+    // Checked against `VOTE_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let receipt_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    // A voter who has not signed votes through their delegate, who signs and
+    // pays instead.
+    let delegate_account = if voter_account.is_signer {
+        None
+    } else {
+        Some(delegate_signer(program_id, voter_account.key, accounts)?)
+    };
+    let payer_account = delegate_account.unwrap_or(voter_account);
+
+    // Load and deserialize the poll.
+    let mut poll = load_poll(poll_account)?;
+    poll.ensure_poll_type(PollType::Plurality)?;
+    if poll.is_multi_select() {
+        msg!("Multi-select polls take VoteMulti.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if poll.is_cumulative() {
+        msg!("Cumulative polls take VoteCumulative.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+    poll.ensure_terms_accepted(accept_terms)?;
+
+    // The receipt address is fixed by the poll and voter, so an existing receipt
+    // (or, for older polls, an entry in `voters`) means the voter has voted.
+    let (receipt_address, bump) =
+        find_receipt_address(program_id, poll_account.key, voter_account.key);
+    if *receipt_account.key != receipt_address {
+        msg!("Receipt account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if receipt_account.owner == program_id || poll.voters.contains(voter_account.key) {
+        msg!("Voter has already voted.");
+        return Err(VotingError::DoubleVote.into());
+    }
+    if *receipt_account.owner != system_program::ID {
+        msg!("Receipt account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Validate the option index.
+    let idx = option_index as usize;
+    if idx >= poll.options.len() {
+        msg!("Invalid option index.");
+        return Err(VotingError::InvalidOption.into());
+    }
+    poll.ensure_not_withdrawn(idx)?;
+
+    // Increment the vote count for the selected option.
+    let now = clock.unix_timestamp as u64;
+    poll.add_ballots(idx, 1, now)?;
+
+    // On weighted polls the vote also carries the weight its source reports.
+    if let Some(source) = weight_source(&poll) {
+        let weight_account = next_account_info(account_info_iter)?;
+        let weight =
+            source.voter_weight(poll_account.key, voter_account.key, weight_account, clock.slot)?;
+        poll.add_weight(idx, u128::from(poll.weight_curve.apply(weight)), now)?;
+    }
+    ensure_eligible(
+        program_id,
+        &poll,
+        voter_account.key,
+        &eligibility_proof,
+        clock.slot,
+        account_info_iter,
+    )?;
+
+    // The accounts left before any delegate pair are the delegators whose
+    // ballots this vote also casts.
+    let rest = account_info_iter.as_slice();
+    let delegator_accounts = match delegate_account {
+        Some(_) => &rest[..rest.len().checked_sub(2).ok_or(ProgramError::NotEnoughAccountKeys)?],
+        None => rest,
+    };
+    let delegated_ballots = cast_delegated_ballots(
+        program_id,
+        poll_account.key,
+        &mut poll,
+        voter_account.key,
+        payer_account,
+        system_program_account,
+        delegator_accounts,
+        option_index,
+        &clock,
+    )?;
+
+    // Record this voter's participation in a receipt account.
+    create_pda_account(
+        program_id,
+        payer_account,
+        receipt_account,
+        system_program_account,
+        VOTE_RECEIPT_LEN,
+        &[RECEIPT_SEED, poll_account.key.as_ref(), voter_account.key.as_ref(), &[bump]],
+    )?;
+    let receipt = VoteReceipt {
+        poll: *poll_account.key,
+        voter: *voter_account.key,
+        option_index,
+        slot: clock.slot,
+        accepted_terms: accept_terms,
+        delegate: delegate_account.map(|account| *account.key),
+        delegated_ballots,
+    };
+    receipt
+        .serialize(&mut &mut receipt_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if poll.first_vote_slot.is_none() {
+        poll.first_vote_slot = Some(clock.slot);
+    }
+
+    // Write the updated poll state back to the account.
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Vote cast successfully.");
+    Ok(())
+}
+
+/// Casts a ranked ballot on a ranked-choice, Borda or Condorcet poll.
+fn process_ranked_vote(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    ranking: Vec<u32>,
+    accept_terms: bool,
+    eligibility_proof: Vec<Hash>,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (signer, pays), clock sysvar,
+    // ballot account (writable), system program. Checked against
+    // `RANKED_VOTE_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let ballot_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    let borda = poll.poll_type == PollType::Borda;
+    let condorcet = poll.poll_type == PollType::Condorcet;
+    if !borda && !condorcet {
+        poll.ensure_poll_type(PollType::RankedChoice)?;
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+    poll.ensure_terms_accepted(accept_terms)?;
+    ensure_eligible(
+        program_id,
+        &poll,
+        voter_account.key,
+        &eligibility_proof,
+        clock.slot,
+        account_info_iter,
+    )?;
+
+    // The ballot address is fixed by the poll and voter, so an existing ballot
+    // means the voter has voted.
+    let (ballot_address, bump) =
+        find_ballot_address(program_id, poll_account.key, voter_account.key);
+    if *ballot_account.key != ballot_address {
+        msg!("Ballot account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if ballot_account.owner == program_id {
+        msg!("Voter has already voted.");
+        return Err(VotingError::DoubleVote.into());
+    }
+    if *ballot_account.owner != system_program::ID {
+        msg!("Ballot account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Each ranked option must exist and appear once.
+    let first = match ranking.first() {
+        Some(&first) => first as usize,
+        None => {
+            msg!("Ranking is empty.");
+            return Err(VotingError::InvalidOption.into());
+        },
+    };
+    for (position, option_index) in ranking.iter().enumerate() {
+        let repeated = ranking[..position].contains(option_index);
+        if *option_index as usize >= poll.options.len() || repeated {
+            msg!("Invalid or repeated option {} in ranking.", option_index);
+            return Err(VotingError::InvalidOption.into());
+        }
+        poll.ensure_not_withdrawn(*option_index as usize)?;
+    }
+    // Borda ballots rank every option still standing, so every ballot gives
+    // out the same points.
+    let standing = poll.options.len() - poll.withdrawn_options.len();
+    if borda && ranking.len() != standing {
+        msg!("Borda ballots must rank all {} options; got {}.", standing, ranking.len());
+        return Err(VotingError::InvalidOption.into());
+    }
+
+    // Vote counts of a ranked poll hold first preferences, a Borda poll's
+    // weights its running scores, and a Condorcet poll's matrix the
+    // head-to-head preferences.
+    let now = clock.unix_timestamp as u64;
+    poll.add_ballots(first, 1, now)?;
+    if borda {
+        for (position, &option_index) in ranking.iter().enumerate() {
+            poll.add_weight(option_index as usize, borda_points(ranking.len(), position), now)?;
+        }
+    }
+    if condorcet {
+        add_pairwise(&mut poll.pairwise, &ranking);
+    }
+
+    create_pda_account(
+        program_id,
+        voter_account,
+        ballot_account,
+        system_program_account,
+        ranked_ballot_len(ranking.len()),
+        &[BALLOT_SEED, poll_account.key.as_ref(), voter_account.key.as_ref(), &[bump]],
+    )?;
+    let ballot = RankedBallot {
+        poll: *poll_account.key,
+        voter: *voter_account.key,
+        ranking,
+        slot: clock.slot,
+        accepted_terms: accept_terms,
+    };
+    ballot
+        .serialize(&mut &mut ballot_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if poll.first_vote_slot.is_none() {
+        poll.first_vote_slot = Some(clock.slot);
+    }
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Ranked vote cast successfully.");
+    Ok(())
+}
+
+/// Casts one ballot selecting several options of a multi-select poll.
+fn process_vote_multi(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    option_indices: Vec<u32>,
+    accept_terms: bool,
+    eligibility_proof: Vec<Hash>,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (signer, pays), clock sysvar,
+    // ballot account (writable), system program. Checked against
+    // `VOTE_MULTI_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let ballot_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    poll.ensure_poll_type(PollType::Plurality)?;
+    if !poll.is_multi_select() {
+        msg!("Single-choice polls take Vote.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+    poll.ensure_terms_accepted(accept_terms)?;
+    ensure_eligible(
+        program_id,
+        &poll,
+        voter_account.key,
+        &eligibility_proof,
+        clock.slot,
+        account_info_iter,
+    )?;
+
+    // The ballot address is fixed by the poll and voter, so an existing ballot
+    // means the voter has voted.
+    let (ballot_address, bump) =
+        find_ballot_address(program_id, poll_account.key, voter_account.key);
+    if *ballot_account.key != ballot_address {
+        msg!("Ballot account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if ballot_account.owner == program_id {
+        msg!("Voter has already voted.");
+        return Err(VotingError::DoubleVote.into());
+    }
+    if *ballot_account.owner != system_program::ID {
+        msg!("Ballot account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // At least one option and no more than the cap, each existing and distinct.
+    if option_indices.is_empty() {
+        msg!("No options selected.");
+        return Err(VotingError::InvalidOption.into());
+    }
+    if option_indices.len() > poll.max_selections as usize {
+        let (selected, allowed) = (option_indices.len(), poll.max_selections);
+        msg!("Ballot selects {} options; at most {} allowed.", selected, allowed);
+        return Err(VotingError::TooManySelections.into());
+    }
+    let now = clock.unix_timestamp as u64;
+    for (position, option_index) in option_indices.iter().enumerate() {
+        let repeated = option_indices[..position].contains(option_index);
+        if *option_index as usize >= poll.options.len() || repeated {
+            msg!("Invalid or repeated option {} in selection.", option_index);
+            return Err(VotingError::InvalidOption.into());
+        }
+        poll.ensure_not_withdrawn(*option_index as usize)?;
+        poll.add_ballots(*option_index as usize, 1, now)?;
+    }
+
+    create_pda_account(
+        program_id,
+        voter_account,
+        ballot_account,
+        system_program_account,
+        multi_ballot_len(option_indices.len()),
+        &[BALLOT_SEED, poll_account.key.as_ref(), voter_account.key.as_ref(), &[bump]],
+    )?;
+    let ballot = MultiBallot {
+        poll: *poll_account.key,
+        voter: *voter_account.key,
+        selections: option_indices,
+        slot: clock.slot,
+        accepted_terms: accept_terms,
+    };
+    ballot
+        .serialize(&mut &mut ballot_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if poll.first_vote_slot.is_none() {
+        poll.first_vote_slot = Some(clock.slot);
+    }
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Multi-select vote cast successfully.");
+    Ok(())
+}
+
+/// Casts a cumulative poll ballot distributing the voter's points.
+fn process_vote_cumulative(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    allocations: Vec<(u32, u64)>,
+    accept_terms: bool,
+    eligibility_proof: Vec<Hash>,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (signer, pays), clock sysvar,
+    // ballot account (writable), system program. Checked against
+    // `VOTE_CUMULATIVE_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let ballot_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    let budget = poll.poll_type == PollType::Budget;
+    if !budget {
+        poll.ensure_poll_type(PollType::Plurality)?;
+    }
+    if !poll.is_cumulative() {
+        msg!("Poll does not take cumulative ballots.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+    poll.ensure_terms_accepted(accept_terms)?;
+    ensure_eligible(
+        program_id,
+        &poll,
+        voter_account.key,
+        &eligibility_proof,
+        clock.slot,
+        account_info_iter,
+    )?;
+
+    // The ballot address is fixed by the poll and voter, so an existing ballot
+    // means the voter has voted.
+    let (ballot_address, bump) =
+        find_ballot_address(program_id, poll_account.key, voter_account.key);
+    if *ballot_account.key != ballot_address {
+        msg!("Ballot account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if ballot_account.owner == program_id {
+        msg!("Voter has already voted.");
+        return Err(VotingError::DoubleVote.into());
+    }
+    if *ballot_account.owner != system_program::ID {
+        msg!("Ballot account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // At least one option, each existing, distinct and given points, and no
+    // more points in all than the voter has; on budget polls, exactly that.
+    if allocations.is_empty() {
+        msg!("No options given points.");
+        return Err(VotingError::InvalidOption.into());
+    }
+    let mut spent = 0u64;
+    for (position, &(option_index, points)) in allocations.iter().enumerate() {
+        let repeated = allocations[..position].iter().any(|&(other, _)| other == option_index);
+        if option_index as usize >= poll.options.len() || repeated || points == 0 {
+            msg!("Invalid, repeated or pointless option {} in allocation.", option_index);
+            return Err(VotingError::InvalidOption.into());
+        }
+        spent = spent.checked_add(points).ok_or(VotingError::Overflow)?;
+        poll.ensure_not_withdrawn(option_index as usize)?;
+    }
+    if spent > poll.points_per_voter {
+        msg!("Ballot allocates {} points; each voter has {}.", spent, poll.points_per_voter);
+        return Err(VotingError::PointBudgetExceeded.into());
+    }
+    if budget && spent < poll.points_per_voter {
+        msg!("Ballot allocates {} of the {} point budget.", spent, poll.points_per_voter);
+        return Err(VotingError::BudgetNotAllocated.into());
+    }
+
+    // Budget ballots count as their share of the budget, normalized to
+    // `BUDGET_SCALE` units in all.
+    let points: Vec<u128> = allocations.iter().map(|&(_, points)| u128::from(points)).collect();
+    let weights = if budget { apportion(&points, BUDGET_SCALE) } else { points };
+    let now = clock.unix_timestamp as u64;
+    for (&(option_index, _), weight) in allocations.iter().zip(weights) {
+        poll.add_ballots(option_index as usize, 1, now)?;
+        poll.add_weight(option_index as usize, weight, now)?;
+    }
+
+    create_pda_account(
+        program_id,
+        voter_account,
+        ballot_account,
+        system_program_account,
+        cumulative_ballot_len(allocations.len()),
+        &[BALLOT_SEED, poll_account.key.as_ref(), voter_account.key.as_ref(), &[bump]],
+    )?;
+    let ballot = CumulativeBallot {
+        poll: *poll_account.key,
+        voter: *voter_account.key,
+        allocations,
+        slot: clock.slot,
+        accepted_terms: accept_terms,
+    };
+    ballot
+        .serialize(&mut &mut ballot_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if poll.first_vote_slot.is_none() {
+        poll.first_vote_slot = Some(clock.slot);
+    }
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Cumulative vote cast successfully.");
+    Ok(())
+}
+
+/// Counts a closed ranked-choice poll's ballots by instant runoff, or by single
+/// transferable vote if it has more than one seat.
+fn process_tally(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Poll account (writable, program-owned), then every ballot cast on it.
+    // The poll is checked against `TALLY_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let ballot_accounts = account_info_iter.as_slice();
+
+    let mut poll = load_poll(poll_account)?;
+    ensure_ready_to_tally(&poll)?;
+
+    // Every ballot added one first preference, spoiled ballot or abstention, so
+    // turnout says how many to expect.
+    let expected = poll.turnout();
+    if ballot_accounts.len() as u64 != expected {
+        msg!("Expected {} ballots, got {}.", expected, ballot_accounts.len());
+        return Err(VotingError::AccountMismatch.into());
+    }
+
+    let mut rankings = Vec::with_capacity(ballot_accounts.len());
+    for (index, ballot_account) in ballot_accounts.iter().enumerate() {
+        let ballot = read_ranked_ballot(program_id, poll_account.key, ballot_account, index)?;
+        if ballot_accounts[..index].iter().any(|other| other.key == ballot_account.key) {
+            msg!("Ballot {} is repeated.", index);
+            return Err(VotingError::AccountMismatch.into());
+        }
+        rankings.push((ballot.ranking, 1));
+    }
+    record_count(&mut poll, &rankings);
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll tallied successfully.");
+    Ok(())
+}
+
+/// Counts one batch of a closed ranked-choice poll's ballots, and tallies the
+/// poll once every ballot has been counted.
+fn process_tally_batch(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Poll account (writable, program-owned), caller (signer, pays), cursor
+    // account (writable), system program, then the batch's ballots. Checked
+    // against `TALLY_BATCH_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let caller_account = next_account_info(account_info_iter)?;
+    let cursor_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+    let ballot_accounts = account_info_iter.as_slice();
+
+    let mut poll = load_poll(poll_account)?;
+    ensure_ready_to_tally(&poll)?;
+
+    // The first batch creates the cursor; later ones pick up where it stopped.
+    let task = CursorTask::Tally;
+    let (cursor_address, bump) = find_cursor_address(program_id, poll_account.key, task);
+    if *cursor_account.key != cursor_address {
+        msg!("Cursor account is not the tally cursor for this poll.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    let mut cursor = if cursor_account.owner == program_id {
+        WorkCursor::deserialize(&mut &cursor_account.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?
+    } else if *cursor_account.owner == system_program::ID {
+        create_pda_account(
+            program_id,
+            caller_account,
+            cursor_account,
+            system_program_account,
+            MAX_CURSOR_ACCOUNT_LEN,
+            &[CURSOR_SEED, poll_account.key.as_ref(), &[task.seed()], &[bump]],
+        )?;
+        WorkCursor::new(*poll_account.key, task, poll.options.len(), poll.seats)
+    } else {
+        msg!("Cursor account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    };
+
+    // Ascending addresses keep any ballot from being counted twice, so once
+    // turnout is reached every ballot has been counted this pass.
+    for (index, ballot_account) in ballot_accounts.iter().enumerate() {
+        let ballot = read_ranked_ballot(program_id, poll_account.key, ballot_account, index)?;
+        cursor.advance(ballot_account.key)?;
+        cursor.add_ranking(&poll.withdrawn_options, &ballot.ranking);
+    }
+    let expected = poll.turnout();
+    if cursor.processed > expected {
+        msg!("Counted {} ballots, but only {} were cast.", cursor.processed, expected);
+        return Err(VotingError::AccountMismatch.into());
+    }
+    msg!("Counted {} of {} ballots.", cursor.processed, expected);
+
+    // A complete pass decides one round. Until the count is over, the
+    // ballots are passed again for the next.
+    if cursor.processed == expected {
+        let finished = match &mut cursor.rounds {
+            TallyRounds::Runoff { eliminated } => {
+                let counts: Vec<u64> = cursor.tallies.iter().map(|&tally| tally as u64).collect();
+                msg!("Round {}: {:?}", eliminated.len() + 1, counts);
+                let remaining =
+                    runoff_remaining(poll.options.len(), &poll.withdrawn_options, eliminated);
+                match runoff_step(&counts, &remaining) {
+                    RunoffStep::Decided(winner) => {
+                        poll.winner = winner;
+                        true
+                    },
+                    RunoffStep::Eliminate(option) => {
+                        eliminated.push(option);
+                        false
+                    },
+                }
+            },
+            TallyRounds::Stv(count) => {
+                let seats = usize::from(poll.seats);
+                if let Some(round) =
+                    count.count_round(seats, &poll.withdrawn_options, &cursor.tallies)
+                {
+                    if count.events.len() == 1 {
+                        msg!("Quota: {}", count.quota);
+                    }
+                    log_stv_round(count.events.len(), &round);
+                }
+                if count.finished {
+                    poll.elected = count.elected();
+                }
+                count.finished
+            },
+        };
+        if finished {
+            poll.is_tallied = true;
+            poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+                .map_err(|_| ProgramError::AccountDataTooSmall)?;
+            msg!("Poll tallied successfully.");
+        } else {
+            cursor.next_pass();
+            msg!("Round counted; pass every ballot again for the next.");
+        }
+    }
+
+    cursor
+        .serialize(&mut &mut cursor_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+    Ok(())
+}
+
+/// Fails unless `poll` is a closed ranked-choice poll still to be tallied.
+fn ensure_ready_to_tally(poll: &Poll) -> ProgramResult {
+    poll.ensure_poll_type(PollType::RankedChoice)?;
+    if !poll.is_closed {
+        msg!("Poll has not been closed.");
+        return Err(VotingError::PollNotFinalized.into());
+    }
+    if poll.is_void {
+        msg!("Poll was voided by spoiled ballots.");
+        return Err(VotingError::PollVoid.into());
+    }
+    if poll.is_tallied {
+        msg!("Poll has already been tallied.");
+        return Err(VotingError::AlreadyTallied.into());
+    }
+    Ok(())
+}
+
+/// Reads the ranked ballot at position `index` of a tally, checking that it is
+/// a ballot of the poll at `poll_key`.
+fn read_ranked_ballot(
+    program_id: &Pubkey,
+    poll_key: &Pubkey,
+    ballot_account: &AccountInfo,
+    index: usize,
+) -> Result<RankedBallot, ProgramError> {
+    if ballot_account.owner != program_id {
+        msg!("Ballot {} is not owned by the program.", index);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let ballot: RankedBallot = unpack_record(&ballot_account.data.borrow())?;
+    let (ballot_address, _) = find_ballot_address(program_id, poll_key, &ballot.voter);
+    if ballot.poll != *poll_key || *ballot_account.key != ballot_address {
+        msg!("Ballot {} does not belong to this poll.", index);
+        return Err(VotingError::AccountMismatch.into());
+    }
+    Ok(ballot)
+}
+
+/// Counts every ranking of a poll, each paired with the number of ballots that
+/// cast it, then logs the rounds and records the winner, or the options elected
+/// on a poll with more than one seat.
+fn record_count<B: AsRef<[u32]>>(poll: &mut Poll, groups: &[(B, u64)]) {
+    if poll.seats > 1 {
+        let seats = usize::from(poll.seats);
+        let result =
+            single_transferable_vote(poll.options.len(), seats, &poll.withdrawn_options, groups);
+        msg!("Quota: {}", result.quota);
+        for (round, count) in result.rounds.iter().enumerate() {
+            log_stv_round(round + 1, count);
+        }
+        poll.elected = result.elected;
+    } else {
+        let result = instant_runoff_grouped(poll.options.len(), &poll.withdrawn_options, groups);
+        for (round, counts) in result.rounds.iter().enumerate() {
+            msg!("Round {}: {:?}", round + 1, counts);
+        }
+        poll.winner = result.winner;
+    }
+    poll.is_tallied = true;
+}
+
+/// Logs round `round` of a single transferable vote count.
+fn log_stv_round(round: usize, count: &StvRound) {
+    match count.event {
+        StvEvent::Elected { option, .. } => {
+            msg!("Round {}: {:?}, elected {}", round, count.tallies, option)
+        },
+        StvEvent::Excluded { option } => {
+            msg!("Round {}: {:?}, excluded {}", round, count.tallies, option)
+        },
+    }
+}
+
+/// Moves a voter's ballot to another option while the poll is still open.
+fn process_change_vote(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    new_option_index: u32,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (must be signer), clock sysvar,
+    // receipt account (writable, program-owned). Checked against `CHANGE_VOTE_ACCOUNTS`
+    // before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let receipt_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    poll.ensure_poll_type(PollType::Plurality)?;
+    // Receipts do not record the weight a vote carried, so it cannot be moved.
+    if poll.is_weighted() {
+        msg!("Weighted votes cannot be changed.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if poll.is_multi_select() {
+        msg!("Multi-select ballots cannot be changed.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if poll.is_cumulative() {
+        msg!("Cumulative ballots cannot be changed.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+
+    // Only votes recorded in a receipt carry a choice that can be moved.
+    let (receipt_address, _) =
+        find_receipt_address(program_id, poll_account.key, voter_account.key);
+    if *receipt_account.key != receipt_address {
+        msg!("Receipt account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if receipt_account.owner != program_id {
+        msg!("Voter has no recorded vote in this poll.");
+        return Err(VotingError::NoVoteToChange.into());
+    }
+    let mut receipt: VoteReceipt = unpack_record(&receipt_account.data.borrow())?;
+    // The delegators' ballots sit in their own receipts on the same option;
+    // moving this vote alone would leave them behind.
+    if receipt.delegated_ballots > 0 {
+        msg!("Vote also cast {} delegated ballots.", receipt.delegated_ballots);
+        return Err(VotingError::DelegatedVoteLocked.into());
+    }
+
+    let spoiled = receipt.option_index == SPOILED_BALLOT;
+    let abstained = receipt.option_index == ABSTAINED_BALLOT;
+    let old_idx = receipt.option_index as usize;
+    let new_idx = new_option_index as usize;
+    let blank = spoiled || abstained;
+    if new_idx >= poll.options.len() || (!blank && old_idx >= poll.vote_counts.len()) {
+        msg!("Invalid option index.");
+        return Err(VotingError::InvalidOption.into());
+    }
+    // Moving a ballot off a withdrawn option is fine; moving one onto it is not.
+    poll.ensure_not_withdrawn(new_idx)?;
+
+    // Move the ballot from the old option (or the spoiled or abstained pile)
+    // to the new one.
+    let now = clock.unix_timestamp as u64;
+    if spoiled {
+        poll.spoiled_count = poll.spoiled_count.checked_sub(1).ok_or(VotingError::Overflow)?;
+    } else if abstained {
+        poll.abstain_count = poll.abstain_count.checked_sub(1).ok_or(VotingError::Overflow)?;
+    } else {
+        poll.add_ballots(old_idx, -1, now)?;
+    }
+    poll.add_ballots(new_idx, 1, now)?;
+    receipt.option_index = new_option_index;
+    receipt.slot = clock.slot;
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+    // Receipts from before `accepted_terms` have no room for it; it reads as
+    // false there and stays that way.
+    let data = receipt.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let len = data.len().min(receipt_account.data_len());
+    receipt_account.data.borrow_mut()[..len].copy_from_slice(&data[..len]);
+
+    msg!("Vote changed successfully.");
+    Ok(())
+}
+
+/// Closes the poll (only allowed by the poll creator when the voting period has ended).
+fn process_close_poll(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), caller (must be poll creator/signature),
+    // clock sysvar. Checked against `CLOSE_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let caller_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    // Load the poll.
+    let mut poll = load_poll(poll_account)?;
+
+    // Only the poll creator can close the poll.
+    if poll.creator != *caller_account.key {
+        msg!("Caller is not authorized to close the poll.");
+        return Err(VotingError::Unauthorized.into());
+    }
+
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+
+    if poll.is_closed {
+        msg!("Poll is already closed.");
+        return Err(VotingError::PollClosed.into());
+    }
+
+    // Retrieve current time and ensure voting period is over.
+    if !poll.can_close(clock.unix_timestamp as u64) {
+        msg!("Poll voting period is still active.");
+        return Err(VotingError::VotingStillActive.into());
+    }
+
+    poll.is_closed = true;
+    poll.closed_at_slot = Some(clock.slot);
+    poll.reveal_pending()?;
+
+    let withdrawn_votes = poll.withdrawn_vote_count();
+    if withdrawn_votes > 0 {
+        msg!("{} votes were cast for withdrawn options.", withdrawn_votes);
+    }
+    msg!("{} of {} ballots were spoiled.", poll.spoiled_count, poll.turnout());
+    if poll.abstain_count > 0 {
+        msg!("{} voters abstained.", poll.abstain_count);
+    }
+    if poll.spoiled_share_exceeded() {
+        msg!("Spoiled ballots exceed {} basis points; the poll is void.", poll.max_spoiled_bps);
+        poll.is_void = true;
+    }
+
+    // Update the account with the closed poll.
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll closed successfully.");
+    Ok(())
+}
+
+/// Cancels a poll (only allowed by the poll creator before voting starts or
+/// while no vote has been cast).
+fn process_cancel_poll(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), caller (must be poll creator/signature),
+    // clock sysvar. Checked against `CANCEL_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let caller_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    let mut poll = load_poll(poll_account)?;
+
+    if poll.creator != *caller_account.key {
+        msg!("Caller is not authorized to cancel the poll.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_cancelled {
+        msg!("Poll is already cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if poll.is_closed {
+        msg!("Poll is already closed.");
+        return Err(VotingError::PollClosed.into());
+    }
+    if poll.voting_started(clock.unix_timestamp as u64) && poll.has_votes() {
+        msg!("Poll already has votes and cannot be cancelled.");
+        return Err(VotingError::VotesAlreadyCast.into());
+    }
+
+    poll.is_cancelled = true;
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll cancelled successfully.");
+    Ok(())
+}
+
+/// Quarantines a poll (only allowed by the poll's moderator).
+fn process_quarantine_poll(accounts: &[AccountInfo]) -> ProgramResult {
+    // Poll account (writable, program-owned), moderator (must be signer).
+    // Checked against `QUARANTINE_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let moderator_account = next_account_info(account_info_iter)?;
+
+    // The poll may be failing its integrity checks; that is why it is here.
+    let mut poll = read_poll(poll_account)?;
+
+    if poll.moderator != *moderator_account.key {
+        msg!("Caller is not the poll's moderator.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_quarantined {
+        msg!("Poll is already quarantined.");
+        return Err(VotingError::PollQuarantined.into());
+    }
+
+    poll.is_quarantined = true;
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll quarantined.");
+    Ok(())
+}
+
+/// Reports the program version and supported features through return data.
+fn process_get_version() -> ProgramResult {
+    let version = ProgramVersion::current();
+    msg!(
+        "Voting program {}.{}.{}, features {:#x}.",
+        version.major,
+        version.minor,
+        version.patch,
+        version.features
+    );
+    set_return_data(&version.try_to_vec().expect("serializing to a Vec cannot fail"));
+    Ok(())
+}
+
+/// Edits a poll's question, options and voting window (only allowed by the
+/// poll creator before voting starts and while no vote has been cast).
+fn process_update_poll(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    question: String,
+    options: Vec<String>,
+    start_time: u64,
+    end_time: u64,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), caller (must be poll creator/signature),
+    // clock sysvar. Checked against `UPDATE_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let caller_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    let mut poll = load_poll(poll_account)?;
+
+    if poll.creator != *caller_account.key {
+        msg!("Caller is not authorized to update the poll.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if poll.is_closed {
+        msg!("Poll is already closed.");
+        return Err(VotingError::PollClosed.into());
+    }
+    if poll.voting_started(clock.unix_timestamp as u64) {
+        msg!("Poll voting has already started.");
+        return Err(VotingError::PollAlreadyStarted.into());
+    }
+    poll.ensure_options_mutable()?;
+    validate_poll_params(&question, &options, start_time, end_time)?;
+    if poll.unique_question && question != poll.question {
+        msg!("The question is registered as unique and cannot change.");
+        return Err(VotingError::DuplicateQuestion.into());
+    }
+    if poll.max_selections as usize > options.len() {
+        msg!("Ballots may select {} options, more than the new list has.", poll.max_selections);
+        return Err(VotingError::TooManySelections.into());
+    }
+    if poll.seats as usize > options.len() {
+        msg!("Poll elects {} options, more than the new list has.", poll.seats);
+        return Err(VotingError::PollTooLarge.into());
+    }
+    if poll.is_proposal && options != PROPOSAL_OPTIONS {
+        msg!("A proposal's options are always {:?}.", PROPOSAL_OPTIONS);
+        return Err(VotingError::WrongPollType.into());
+    }
+    let condorcet = poll.poll_type == PollType::Condorcet;
+    if condorcet && options.len() > MAX_CONDORCET_OPTIONS {
+        msg!("Condorcet polls are limited to {} options.", MAX_CONDORCET_OPTIONS);
+        return Err(VotingError::PollTooLarge.into());
+    }
+
+    // Counts are re-initialized for the new option list, and candidate keys
+    // and withdrawals referred to the old one.
+    poll.vote_counts = vec![0; options.len()];
+    if poll.has_vote_weights() {
+        poll.vote_weights = vec![0; options.len()];
+    }
+    if condorcet {
+        poll.pairwise = vec![vec![0; options.len()]; options.len()];
+    }
+    poll.candidates.clear();
+    poll.withdrawn_options.clear();
+    poll.question = question;
+    poll.options = options;
+    poll.start_time = start_time;
+    poll.end_time = end_time;
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll updated successfully.");
+    Ok(())
+}
+
+/// Appends an option to a live poll that accepts new options.
+fn process_add_option(
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    label: String,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), proposer (must be signer), clock sysvar.
+    // Checked against `ADD_OPTION_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let _proposer_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    let mut poll = load_poll(poll_account)?;
+
+    if !poll.allow_new_options {
+        msg!("Poll does not accept new options.");
+        return Err(VotingError::NewOptionsNotAllowed.into());
+    }
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+
+    if poll.options.len() >= MAX_OPTIONS || label.len() > MAX_OPTION_LEN {
+        msg!("Poll options exceed the size limits.");
+        return Err(VotingError::PollTooLarge.into());
+    }
+    if poll.options.contains(&label) {
+        msg!("Option '{}' already exists.", label);
+        return Err(VotingError::DuplicateOption.into());
+    }
+
+    // Appending keeps every existing index, so ballots already cast keep their
+    // meaning and the options freeze does not apply.
+    poll.options.push(label);
+    poll.vote_counts.push(0);
+    if poll.has_vote_weights() {
+        poll.vote_weights.push(0);
+    }
+    if !poll.pending_counts.is_empty() {
+        poll.pending_counts.push(0);
+    }
+    if !poll.pending_weights.is_empty() {
+        poll.pending_weights.push(0);
+    }
+
+    let data = poll.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    if data.len() > poll_account.data_len() {
+        msg!("Poll account is too small for another option.");
+        return Err(VotingError::PollTooLarge.into());
+    }
+    poll_account.data.borrow_mut()[..data.len()].copy_from_slice(&data);
+
+    msg!("Option added successfully.");
+    Ok(())
+}
+
+/// Lengthens the voting window of an open poll.
+fn process_extend_poll(
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    new_end_time: u64,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), caller (must be poll creator/signature),
+    // clock sysvar. Checked against `EXTEND_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let caller_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    let mut poll = load_poll(poll_account)?;
+
+    if poll.creator != *caller_account.key {
+        msg!("Caller is not authorized to extend the poll.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if poll.is_closed {
+        msg!("Poll is already closed.");
+        return Err(VotingError::PollClosed.into());
+    }
+
+    // A window that has already ended stays ended; extending it would reopen voting.
+    let current_time = clock.unix_timestamp as u64;
+    if poll.voting_ended(current_time) {
+        msg!("Poll voting period has ended.");
+        return Err(VotingError::VotingNotActive.into());
+    }
+    if new_end_time <= poll.end_time || new_end_time <= current_time {
+        msg!("New end time must be later than the current end and now.");
+        return Err(VotingError::InvalidVotingWindow.into());
+    }
+    validate_voting_window(poll.start_time, new_end_time)?;
+
+    poll.end_time = new_end_time;
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll extended successfully.");
+    Ok(())
+}
+
+/// Records a ballot for no option: spoiled if `marker` is `SPOILED_BALLOT`,
+/// an abstention if it is `ABSTAINED_BALLOT`.
+fn process_blank_ballot(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    marker: u32,
+    accept_terms: bool,
+    eligibility_proof: Vec<Hash>,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), voter (signer, pays), clock sysvar,
+    // receipt or ballot account (writable), system program. Checked against
+    // `SPOIL_BALLOT_ACCOUNTS` or `ABSTAIN_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let record_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    poll.ensure_accepting_votes(clock.unix_timestamp as u64)?;
+    poll.ensure_terms_accepted(accept_terms)?;
+    ensure_eligible(
+        program_id,
+        &poll,
+        voter_account.key,
+        &eligibility_proof,
+        clock.slot,
+        account_info_iter,
+    )?;
+
+    // Ranked-choice and Borda polls keep every ballot as a ranking, so a
+    // spoiled one is an empty ranking there, multi-select polls an empty selection and
+    // cumulative polls an empty allocation; single-choice plurality polls
+    // only need the receipt. An abstention ranks, selects or allocates to
+    // just `ABSTAINED_BALLOT`, which no count picks up.
+    let ranked =
+        matches!(poll.poll_type, PollType::RankedChoice | PollType::Borda | PollType::Condorcet);
+    let multi = poll.is_multi_select();
+    let cumulative = poll.is_cumulative();
+    let (seed, (record_address, bump)) = if ranked || multi || cumulative {
+        (BALLOT_SEED, find_ballot_address(program_id, poll_account.key, voter_account.key))
+    } else {
+        (RECEIPT_SEED, find_receipt_address(program_id, poll_account.key, voter_account.key))
+    };
+    if *record_account.key != record_address {
+        msg!("Receipt account is not the address for this poll and voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if record_account.owner == program_id || poll.voters.contains(voter_account.key) {
+        msg!("Voter has already voted.");
+        return Err(VotingError::DoubleVote.into());
+    }
+    if *record_account.owner != system_program::ID {
+        msg!("Receipt account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (count, options) = if marker == SPOILED_BALLOT {
+        (&mut poll.spoiled_count, Vec::new())
+    } else {
+        (&mut poll.abstain_count, vec![marker])
+    };
+    *count = count.checked_add(1).ok_or(VotingError::Overflow)?;
+
+    let space = if ranked {
+        ranked_ballot_len(options.len())
+    } else if multi {
+        multi_ballot_len(options.len())
+    } else if cumulative {
+        cumulative_ballot_len(options.len())
+    } else {
+        VOTE_RECEIPT_LEN
+    };
+    create_pda_account(
+        program_id,
+        voter_account,
+        record_account,
+        system_program_account,
+        space,
+        &[seed, poll_account.key.as_ref(), voter_account.key.as_ref(), &[bump]],
+    )?;
+    let record = if ranked {
+        RankedBallot {
+            poll: *poll_account.key,
+            voter: *voter_account.key,
+            ranking: options,
+            slot: clock.slot,
+            accepted_terms: accept_terms,
+        }
+        .try_to_vec()
+    } else if multi {
+        MultiBallot {
+            poll: *poll_account.key,
+            voter: *voter_account.key,
+            selections: options,
+            slot: clock.slot,
+            accepted_terms: accept_terms,
+        }
+        .try_to_vec()
+    } else if cumulative {
+        CumulativeBallot {
+            poll: *poll_account.key,
+            voter: *voter_account.key,
+            allocations: options.into_iter().map(|marker| (marker, 0)).collect(),
+            slot: clock.slot,
+            accepted_terms: accept_terms,
+        }
+        .try_to_vec()
+    } else {
+        VoteReceipt {
+            poll: *poll_account.key,
+            voter: *voter_account.key,
+            option_index: marker,
+            slot: clock.slot,
+            accepted_terms: accept_terms,
+            delegate: None,
+            delegated_ballots: 0,
+        }
+        .try_to_vec()
+    }
+    .map_err(|_| ProgramError::InvalidAccountData)?;
+    record_account.data.borrow_mut()[..record.len()].copy_from_slice(&record);
+
+    if poll.first_vote_slot.is_none() {
+        poll.first_vote_slot = Some(clock.slot);
+    }
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    if marker == SPOILED_BALLOT {
+        msg!("Ballot spoiled successfully.");
+    } else {
+        msg!("Abstention recorded successfully.");
+    }
+    Ok(())
+}
+
+/// Marks an option withdrawn at its candidate's request.
+fn process_withdraw_option(
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    option_index: u32,
+) -> ProgramResult {
+    // Poll account (writable, program-owned), candidate (must be signer), clock
+    // sysvar. Checked against `WITHDRAW_OPTION_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let candidate_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+
+    let mut poll = load_poll(poll_account)?;
+
+    let idx = option_index as usize;
+    if idx >= poll.options.len() {
+        msg!("Invalid option index.");
+        return Err(VotingError::InvalidOption.into());
+    }
+    if poll.candidate(idx) != Some(candidate_account.key) {
+        msg!("Signer is not the candidate of option {}.", option_index);
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if poll.is_closed {
+        msg!("Poll is already closed.");
+        return Err(VotingError::PollClosed.into());
+    }
+    if poll.voting_ended(clock.unix_timestamp as u64) {
+        msg!("Poll voting period has ended.");
+        return Err(VotingError::VotingNotActive.into());
+    }
+    poll.ensure_not_withdrawn(idx)?;
+
+    poll.withdrawn_options.push(option_index);
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Option {} withdrawn successfully.", option_index);
+    Ok(())
+}
+
+/// Stores an attestor's signed result for a closed poll.
+fn process_submit_attestation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    signature: [u8; 64],
+) -> ProgramResult {
+    // Poll account (program-owned), attestor (signer, pays), attestation record
+    // (writable), system program. Checked against `SUBMIT_ATTESTATION_ACCOUNTS`
+    // before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let attestor_account = next_account_info(account_info_iter)?;
+    let record_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let poll = load_poll(poll_account)?;
+
+    if !poll.attestors.contains(attestor_account.key) {
+        msg!("Signer is not an attestor of this poll.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    // Results are only final once the poll is closed.
+    let closed_at_slot = match poll.closed_at_slot {
+        Some(slot) if poll.is_closed => slot,
+        _ => {
+            msg!("Poll has not been closed.");
+            return Err(VotingError::PollNotFinalized.into());
+        },
+    };
+
+    let (record_address, bump) =
+        find_attestation_address(program_id, poll_account.key, attestor_account.key);
+    if *record_account.key != record_address {
+        msg!("Attestation account is not the address for this poll and attestor.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if record_account.owner == program_id {
+        msg!("Attestor has already attested this poll.");
+        return Err(VotingError::AlreadyAttested.into());
+    }
+    if *record_account.owner != system_program::ID {
+        msg!("Attestation account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    create_pda_account(
+        program_id,
+        attestor_account,
+        record_account,
+        system_program_account,
+        ATTESTATION_RECORD_LEN,
+        &[ATTESTATION_SEED, poll_account.key.as_ref(), attestor_account.key.as_ref(), &[bump]],
+    )?;
+    let record = AttestationRecord {
+        attestation: ResultAttestation::new(program_id, poll_account.key, &poll, closed_at_slot),
+        attestor: *attestor_account.key,
+        signature,
+    };
+    record
+        .serialize(&mut &mut record_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Attestation submitted successfully.");
+    Ok(())
+}
+
+/// Records the outcome of a closed poll.
+fn process_finalize_poll(accounts: &[AccountInfo]) -> ProgramResult {
+    // Poll account (writable, program-owned). Checked against
+    // `FINALIZE_POLL_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+
+    let mut poll = load_poll(poll_account)?;
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if !poll.is_closed {
+        msg!("Poll has not been closed.");
+        return Err(VotingError::PollNotFinalized.into());
+    }
+    if poll.outcome.is_some() {
+        msg!("Poll has already been finalized.");
+        return Err(VotingError::AlreadyFinalized.into());
+    }
+    if poll.poll_type == PollType::RankedChoice && !poll.is_void && !poll.is_tallied {
+        msg!("Ranked-choice poll must be tallied first.");
+        return Err(VotingError::PollNotFinalized.into());
+    }
+
+    let outcome = poll.finalize();
+    if !poll.tied_options.is_empty() {
+        msg!("Options {:?} tied for first; winner {:?}.", poll.tied_options, poll.winner);
+    }
+    msg!("Participation {}, quorum {:?}: {:?}", poll.participation(), poll.quorum, outcome);
+
+    poll.serialize(&mut &mut poll_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Poll finalized successfully.");
+    Ok(())
+}
+
+/// Copies the poll's current tallies into a labelled snapshot account.
+fn process_snapshot_result(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    label: String,
+) -> ProgramResult {
+    // Poll account (program-owned), creator (signer, pays), clock sysvar, snapshot
+    // account, system program. Checked against `SNAPSHOT_RESULT_ACCOUNTS` before
+    // dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let poll_account = next_account_info(account_info_iter)?;
+    let creator_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let snapshot_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    let poll = load_poll(poll_account)?;
+
+    if poll.creator != *creator_account.key {
+        msg!("Only the poll creator can take snapshots.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    if poll.is_cancelled {
+        msg!("Poll was cancelled.");
+        return Err(VotingError::PollCancelled.into());
+    }
+    if label.len() > MAX_SNAPSHOT_LABEL_LEN {
+        msg!("Snapshot label exceeds {} bytes.", MAX_SNAPSHOT_LABEL_LEN);
+        return Err(VotingError::PollTooLarge.into());
+    }
+
+    let (snapshot_address, bump) = find_snapshot_address(program_id, poll_account.key, &label);
+    if *snapshot_account.key != snapshot_address {
+        msg!("Snapshot account is not the address for this poll and label.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if snapshot_account.owner == program_id {
+        msg!("Poll already has a snapshot labelled '{}'.", label);
+        return Err(VotingError::SnapshotExists.into());
+    }
+    if *snapshot_account.owner != system_program::ID {
+        msg!("Snapshot account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let snapshot = ResultSnapshot::new(
+        poll_account.key,
+        &poll,
+        label,
+        clock.slot,
+        clock.unix_timestamp as u64,
+    );
+    let data = snapshot.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    create_pda_account(
+        program_id,
+        creator_account,
+        snapshot_account,
+        system_program_account,
+        data.len(),
+        &[SNAPSHOT_SEED, poll_account.key.as_ref(), snapshot.label.as_bytes(), &[bump]],
+    )?;
+    snapshot_account.data.borrow_mut()[..data.len()].copy_from_slice(&data);
+
+    msg!("Snapshot '{}' taken at slot {}.", snapshot.label, snapshot.slot);
+    Ok(())
+}
+
+/// Registers the signing wallet as a voter.
+fn process_register_voter(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    time: &dyn TimeProvider,
+    delegate: Option<Pubkey>,
+) -> ProgramResult {
+    // Voter record account (writable), voter (signer, pays), clock sysvar,
+    // system program. Checked against `REGISTER_VOTER_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let record_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+    let clock = time.clock(next_account_info(account_info_iter)?)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if delegate == Some(*voter_account.key) {
+        msg!("Voter cannot be their own delegate.");
+        return Err(VotingError::InvalidDelegate.into());
+    }
+
+    let (record_address, bump) = find_voter_record_address(program_id, voter_account.key);
+    if *record_account.key != record_address {
+        msg!("Voter record account is not the address for this voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if record_account.owner == program_id {
+        msg!("Voter is already registered.");
+        return Err(VotingError::AlreadyRegistered.into());
+    }
+    if *record_account.owner != system_program::ID {
+        msg!("Voter record account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    create_pda_account(
+        program_id,
+        voter_account,
+        record_account,
+        system_program_account,
+        VOTER_RECORD_LEN,
+        &[VOTER_SEED, voter_account.key.as_ref(), &[bump]],
+    )?;
+    let record = VoterRecord {
+        voter: *voter_account.key,
+        registered_at: clock.unix_timestamp as u64,
+        registered_slot: clock.slot,
+        polls_participated: 0,
+        delegate,
+    };
+    record
+        .serialize(&mut &mut record_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Voter registered.");
+    Ok(())
+}
+
+/// Names or, with `None`, revokes the signing voter's delegate.
+fn process_set_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Option<Pubkey>,
+) -> ProgramResult {
+    // Voter record account (writable, program-owned), voter (signer). Checked
+    // against `SET_DELEGATE_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let record_account = next_account_info(account_info_iter)?;
+    let voter_account = next_account_info(account_info_iter)?;
+
+    if *record_account.key != find_voter_record_address(program_id, voter_account.key).0 {
+        msg!("Voter record account is not the address for this voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if delegate == Some(*voter_account.key) {
+        msg!("Voter cannot be their own delegate.");
+        return Err(VotingError::InvalidDelegate.into());
+    }
+    let mut record = VoterRecord::deserialize(&mut &record_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    record.delegate = delegate;
+    record
+        .serialize(&mut &mut record_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    match delegate {
+        Some(_) => msg!("Delegate set."),
+        None => msg!("Delegate revoked."),
+    }
+    Ok(())
+}
+
+/// Creates the program config, signed for by the program's own key.
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    admin: Pubkey,
+    read_only_mode: bool,
+) -> ProgramResult {
+    // Config account (writable), payer (signer, pays), program account
+    // (signer), system program. Checked against `INITIALIZE_CONFIG_ACCOUNTS`
+    // before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let config_account = next_account_info(account_info_iter)?;
+    let payer_account = next_account_info(account_info_iter)?;
+    let program_account = next_account_info(account_info_iter)?;
+    let system_program_account = next_account_info(account_info_iter)?;
+
+    if program_account.key != program_id {
+        msg!("Only the program's own key can create its config.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    let (config_address, bump) = find_config_address(program_id);
+    if *config_account.key != config_address {
+        msg!("Config account is not the program's config address.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if config_account.owner == program_id {
+        msg!("Program config already exists.");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    if *config_account.owner != system_program::ID {
+        msg!("Config account is owned by another program.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    create_pda_account(
+        program_id,
+        payer_account,
+        config_account,
+        system_program_account,
+        PROGRAM_CONFIG_LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
+    ProgramConfig { admin, read_only_mode, ..ProgramConfig::default() }
+        .serialize(&mut &mut config_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!("Program config created.");
+    Ok(())
+}
+
+/// Turns the program's read-only mode on or off, as its config's admin.
+fn process_set_read_only_mode(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    read_only_mode: bool,
+) -> ProgramResult {
+    update_config(program_id, accounts, |config| {
+        config.read_only_mode = read_only_mode;
+    })?;
+
+    match read_only_mode {
+        true => msg!("Program is now read-only."),
+        false => msg!("Program is no longer read-only."),
+    }
+    Ok(())
+}
+
+/// Sets how the instruction tagged `instruction` may be reached, as the
+/// config's admin.
+fn process_set_call_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: u8,
+    policy: CallPolicy,
+) -> ProgramResult {
+    if instruction >= INSTRUCTION_COUNT || CONFIG_FREE_TAGS.contains(&instruction) {
+        msg!("No instruction with tag {} takes the config.", instruction);
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    update_config(program_id, accounts, |config| {
+        config.set_call_policy(instruction, policy);
+    })?;
+
+    msg!("Instruction {} is now {:?}.", instruction, policy);
+    Ok(())
+}
+
+/// Replaces the programs allowed to call `CallPolicy::AllowlistedCpi`
+/// instructions, as the config's admin.
+fn process_set_cpi_allowlist(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    programs: Vec<Pubkey>,
+) -> ProgramResult {
+    if programs.len() > MAX_CPI_ALLOWLIST {
+        msg!("At most {} programs can be allowlisted.", MAX_CPI_ALLOWLIST);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let count = programs.len();
+    update_config(program_id, accounts, |config| {
+        config.cpi_allowlist = programs;
+    })?;
+
+    msg!("{} program(s) allowlisted for CPI.", count);
+    Ok(())
+}
+
+/// Loads the program config, applies `update` and stores it, failing unless
+/// the config's admin signed.
+fn update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut ProgramConfig),
+) -> ProgramResult {
+    // Config account (writable, program-owned), admin (signer). Checked
+    // against `SET_CONFIG_ACCOUNTS` before dispatch.
+    let account_info_iter = &mut accounts.iter();
+    let config_account = next_account_info(account_info_iter)?;
+    let admin_account = next_account_info(account_info_iter)?;
+
+    if *config_account.key != find_config_address(program_id).0 {
+        msg!("Config account is not the program's config address.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    let mut config = ProgramConfig::deserialize(&mut &config_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if config.admin != *admin_account.key {
+        msg!("Signer is not the config's admin.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    update(&mut config);
+    config
+        .serialize(&mut &mut config_account.data.borrow_mut()[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)
+}
+
+/// Checks the creator-supplied poll fields shared by `CreatePoll` and
+/// `UpdatePoll`.
+fn validate_poll_params(
+    question: &str,
+    options: &[String],
+    start_time: u64,
+    end_time: u64,
+) -> ProgramResult {
+    // Bound everything that ends up on the heap when the poll is loaded again.
+    if question.len() > MAX_QUESTION_LEN
+        || options.len() > MAX_OPTIONS
+        || options.iter().any(|option| option.len() > MAX_OPTION_LEN)
+    {
+        msg!("Poll question or options exceed the size limits.");
+        return Err(VotingError::PollTooLarge.into());
+    }
+    validate_voting_window(start_time, end_time)
+}
+
+/// Checks that a voting window is ordered and no longer than `MAX_POLL_DURATION`.
+fn validate_voting_window(start_time: u64, end_time: u64) -> ProgramResult {
+    if start_time > end_time {
+        msg!("Poll ends before it starts.");
+        return Err(VotingError::InvalidVotingWindow.into());
+    }
+    if end_time - start_time > MAX_POLL_DURATION {
+        msg!("Poll runs longer than {} seconds.", MAX_POLL_DURATION);
+        return Err(VotingError::InvalidVotingWindow.into());
+    }
+    Ok(())
+}
+
+/// Creates `account` at a program address through the system program, paid for
+/// by `payer` and owned by this program. `seeds` must include the bump seed.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if *system_program_account.key != system_program::ID {
+        msg!("Expected the system program account.");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            account.key,
+            Rent::default().minimum_balance(space),
+            space as u64,
+            program_id,
+        ),
+        &[payer.clone(), account.clone(), system_program_account.clone()],
+        &[seeds],
+    )
+}
+
+/// Splits the program config off the end of `accounts` if `instruction`
+/// `takes_config`, returning the instruction's own accounts and the config.
+/// A config not yet created, or not taken, reads as the default.
+fn split_config<'a, 'b>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'b>],
+    instruction: &VotingInstruction,
+) -> Result<(&'a [AccountInfo<'b>], ProgramConfig), ProgramError> {
+    if !instruction.takes_config() {
+        return Ok((accounts, ProgramConfig::default()));
+    }
+    let (config_account, accounts) =
+        accounts.split_last().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if *config_account.key != find_config_address(program_id).0 {
+        msg!("Expected the program config account last.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if config_account.owner != program_id {
+        return Ok((accounts, ProgramConfig::default()));
+    }
+    let config = ProgramConfig::deserialize(&mut &config_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok((accounts, config))
+}
+
+/// Fails if the program is read-only, unless `instruction` runs in read-only
+/// mode and the config's admin signed it among `accounts`.
+fn ensure_runs(
+    instruction: &VotingInstruction,
+    config: &ProgramConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if !config.read_only_mode {
+        return Ok(());
+    }
+    let admin_signed =
+        accounts.iter().any(|account| account.is_signer && *account.key == config.admin);
+    if !instruction.runs_when_read_only() || !admin_signed {
+        msg!("Program is read-only; only administrative instructions its admin signs run.");
+        return Err(VotingError::ReadOnlyMode.into());
+    }
+    Ok(())
+}
+
+/// Fails unless the call reached the program as `policy` allows, judged by
+/// the transaction's top-level instruction in the instructions sysvar found
+/// among `accounts` and by `stack_height`, the runtime's invocation depth
+/// (1 at the top level, 0 where the runtime reports none). `CallPolicy::Any`
+/// needs no sysvar.
+fn ensure_caller_allowed(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    policy: CallPolicy,
+    allowlist: &[Pubkey],
+    stack_height: u64,
+) -> ProgramResult {
+    if policy == CallPolicy::Any {
+        return Ok(());
+    }
+    let instructions_account = accounts
+        .iter()
+        .find(|account| sysvar::instructions::check_id(account.key))
+        .ok_or_else(|| {
+            msg!("Expected the instructions sysvar to check the caller.");
+            ProgramError::NotEnoughAccountKeys
+        })?;
+    let current = sysvar::instructions::load_current_index_checked(instructions_account)?;
+    let top_level =
+        sysvar::instructions::load_instruction_at_checked(current as usize, instructions_account)?;
+    let via_cpi = top_level.program_id != *program_id || stack_height > 1;
+    // Only at depth 2 is the top-level program the one calling this one.
+    let direct = stack_height <= 2;
+    match policy {
+        CallPolicy::TopLevelOnly if via_cpi => {
+            msg!("Instruction must be called at the top level, not through CPI.");
+            Err(VotingError::CallerNotAllowed.into())
+        },
+        CallPolicy::AllowlistedCpi
+            if !via_cpi || !direct || !allowlist.contains(&top_level.program_id) =>
+        {
+            msg!("Instruction must be called directly through CPI from an allowlisted program.");
+            Err(VotingError::CallerNotAllowed.into())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Checks `accounts` against `specs`, logging the index and role of the first
+/// account that does not match.
+fn check_accounts(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    specs: &[AccountSpec],
+) -> ProgramResult {
+    if accounts.len() < specs.len() {
+        msg!("Expected {} accounts, got {}.", specs.len(), accounts.len());
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    for (index, (account, spec)) in accounts.iter().zip(specs).enumerate() {
+        if spec.signer && !account.is_signer {
+            msg!("Account {} ({:?}) signature missing.", index, spec.role);
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if spec.writable && !account.is_writable {
+            msg!("Account mismatch at index {}: expected writable {:?}.", index, spec.role);
+            return Err(VotingError::AccountMismatch.into());
+        }
+        if spec.program_owned && account.owner != program_id {
+            msg!("Account {} ({:?}) is not owned by the program.", index, spec.role);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+    }
+    Ok(())
+}
+
+/// A delegator passed with a vote, whose ballot the vote may also cast.
+struct Delegator<'a, 'b> {
+    record: VoterRecord,
+    record_account: &'a AccountInfo<'b>,
+    receipt_account: &'a AccountInfo<'b>,
+    bump: u8,
+    /// Whether the delegator has already voted.
+    voted: bool,
+}
+
+/// Casts a ballot for `option_index` for each delegator in `accounts` whose
+/// delegation chain leads to `voter`.
+///
+/// `accounts` holds a `VoterRecord` and then a receipt account per delegator.
+/// Delegators who have voted are skipped, as are those whose chain runs
+/// through one who has: their ballots follow that vote instead. Each ballot
+/// cast goes through the poll's eligibility rule like a direct vote, with
+/// the delegator's record as its only account, so only `Open` and
+/// `Registered` polls without weights or terms count delegated ballots.
+/// Returns the number of ballots cast.
+#[allow(clippy::too_many_arguments)]
+fn cast_delegated_ballots<'a>(
+    program_id: &Pubkey,
+    poll_key: &Pubkey,
+    poll: &mut Poll,
+    voter: &Pubkey,
+    payer_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+    option_index: u32,
+    clock: &Clock,
+) -> Result<u32, ProgramError> {
+    if accounts.is_empty() {
+        return Ok(0);
+    }
+    if poll.max_delegation_depth == 0 {
+        msg!("Poll does not count delegated ballots.");
+        return Err(VotingError::WrongPollType.into());
+    }
+    if poll.is_weighted()
+        || poll.terms_hash.is_some()
+        || !matches!(poll.eligibility, EligibilityRule::Open | EligibilityRule::Registered)
+    {
+        msg!("Delegated ballots need an unweighted Open or Registered poll without terms.");
+        return Err(VotingError::WrongPollType.into());
+    }
+
+    // Read every delegator first, so chains can be followed in any order.
+    let mut delegators: Vec<Delegator> = Vec::with_capacity(accounts.len() / 2);
+    for pair in accounts.chunks(2) {
+        let (record_account, receipt_account) = match pair {
+            [record_account, receipt_account] => (record_account, receipt_account),
+            _ => {
+                msg!("Each delegator needs a voter record and a receipt account.");
+                return Err(ProgramError::NotEnoughAccountKeys);
+            },
+        };
+        if record_account.owner != program_id {
+            msg!("Delegator has not registered.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let record = VoterRecord::deserialize(&mut &record_account.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if *record_account.key != find_voter_record_address(program_id, &record.voter).0 {
+            msg!("Voter record account is not the address for its voter.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        if record.voter == *voter
+            || delegators.iter().any(|delegator| delegator.record.voter == record.voter)
+        {
+            msg!("Delegators must be distinct from each other and from the voter.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        let (receipt_address, bump) = find_receipt_address(program_id, poll_key, &record.voter);
+        if *receipt_account.key != receipt_address {
+            msg!("Receipt account is not the address for this poll and delegator.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        let voted = receipt_account.owner == program_id || poll.voters.contains(&record.voter);
+        if !voted && *receipt_account.owner != system_program::ID {
+            msg!("Receipt account is owned by another program.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        delegators.push(Delegator { record, record_account, receipt_account, bump, voted });
+    }
+
+    let mut cast = 0;
+    for delegator in &delegators {
+        if delegator.voted {
+            continue;
+        }
+        if !delegation_reaches(voter, &delegator.record, &delegators, poll.max_delegation_depth)? {
+            continue;
+        }
+        let delegator_key = delegator.record.voter;
+        ensure_eligible(
+            program_id,
+            poll,
+            &delegator_key,
+            &[],
+            clock.slot,
+            &mut std::iter::once(delegator.record_account),
+        )?;
+        create_pda_account(
+            program_id,
+            payer_account,
+            delegator.receipt_account,
+            system_program_account,
+            VOTE_RECEIPT_LEN,
+            &[RECEIPT_SEED, poll_key.as_ref(), delegator_key.as_ref(), &[delegator.bump]],
+        )?;
+        let receipt = VoteReceipt {
+            poll: *poll_key,
+            voter: delegator_key,
+            option_index,
+            slot: clock.slot,
+            accepted_terms: false,
+            delegate: Some(*voter),
+            delegated_ballots: 0,
+        };
+        receipt
+            .serialize(&mut &mut delegator.receipt_account.data.borrow_mut()[..])
+            .map_err(|_| ProgramError::AccountDataTooSmall)?;
+        poll.add_ballots(option_index as usize, 1, clock.unix_timestamp as u64)?;
+        cast += 1;
+    }
+    msg!("Cast {} delegated ballots.", cast);
+    Ok(cast)
+}
+
+/// Follows `record`'s chain of delegates through `delegators` towards
+/// `voter`.
+///
+/// Returns whether the chain reaches `voter`, which it does not if it runs
+/// through a delegator who has voted. Fails if the chain loops, ends or
+/// leaves `delegators` first, or takes more than `max_depth` steps.
+fn delegation_reaches(
+    voter: &Pubkey,
+    record: &VoterRecord,
+    delegators: &[Delegator],
+    max_depth: u8,
+) -> Result<bool, ProgramError> {
+    let mut visited = vec![record.voter];
+    let mut next = record.delegate;
+    for _ in 0..max_depth {
+        let delegate = match next {
+            Some(delegate) if delegate == *voter => return Ok(true),
+            Some(delegate) => delegate,
+            None => {
+                msg!("Delegation chain of {:?} ends before the voter.", record.voter);
+                return Err(VotingError::InvalidDelegationChain.into());
+            },
+        };
+        if visited.contains(&delegate) {
+            msg!("Delegation chain of {:?} loops.", record.voter);
+            return Err(VotingError::InvalidDelegationChain.into());
+        }
+        let step = match delegators.iter().find(|delegator| delegator.record.voter == delegate) {
+            Some(step) => step,
+            None => {
+                msg!("Delegation chain of {:?} leaves the delegators passed.", record.voter);
+                return Err(VotingError::InvalidDelegationChain.into());
+            },
+        };
+        if step.voted {
+            return Ok(false);
+        }
+        visited.push(delegate);
+        next = step.record.delegate;
+    }
+    msg!("Delegation chain of {:?} is longer than {} steps.", record.voter, max_depth);
+    Err(VotingError::InvalidDelegationChain.into())
+}
+
+/// Returns the delegate casting a ballot for `voter`, who has not signed.
+///
+/// The voter's `VoterRecord` and the delegate it names, who must sign, are the
+/// last two accounts.
+fn delegate_signer<'a, 'b>(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    accounts: &'a [AccountInfo<'b>],
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let (record_account, delegate_account) = match accounts {
+        [.., record_account, delegate_account] if delegate_account.is_signer => {
+            (record_account, delegate_account)
+        },
+        _ => {
+            msg!("Voter signature missing.");
+            return Err(ProgramError::MissingRequiredSignature);
+        },
+    };
+    if *record_account.key != find_voter_record_address(program_id, voter).0 {
+        msg!("Voter record account is not the address for this voter.");
+        return Err(VotingError::AccountMismatch.into());
+    }
+    if record_account.owner != program_id {
+        msg!("Voter has not registered, so has no delegate.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    let record = VoterRecord::deserialize(&mut &record_account.data.borrow()[..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if record.delegate != Some(*delegate_account.key) {
+        msg!("Signer is not the voter's delegate.");
+        return Err(VotingError::Unauthorized.into());
+    }
+    Ok(delegate_account)
+}
+
+/// Checks `voter` against the poll's eligibility rule, taking the accounts
+/// the rule reads from `account_info_iter`. A voter record among them counts
+/// the ballot toward the voter's participation.
+fn ensure_eligible<'a, 'b: 'a>(
+    program_id: &Pubkey,
+    poll: &Poll,
+    voter: &Pubkey,
+    proof: &[Hash],
+    slot: u64,
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+) -> ProgramResult {
+    let accounts = (0..poll.eligibility.account_count())
+        .map(|_| next_account_info(account_info_iter).cloned())
+        .collect::<Result<Vec<_>, _>>()?;
+    check_eligibility(program_id, &poll.eligibility, voter, proof, &accounts, slot)?;
+    if accounts.is_empty() {
+        return Ok(());
+    }
+
+    let (record_address, _) = find_voter_record_address(program_id, voter);
+    let record_account = accounts
+        .iter()
+        .find(|account| *account.key == record_address && account.owner == program_id);
+    if let Some(record_account) = record_account {
+        let mut record = VoterRecord::deserialize(&mut &record_account.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        record.polls_participated =
+            record.polls_participated.checked_add(1).ok_or(VotingError::Overflow)?;
+        record
+            .serialize(&mut &mut record_account.data.borrow_mut()[..])
+            .map_err(|_| ProgramError::AccountDataTooSmall)?;
+    }
+    Ok(())
+}
+
+/// Deserializes the poll stored in `poll_account`.
+///
+/// Accounts larger than `MAX_POLL_ACCOUNT_LEN` are rejected before any
+/// allocation happens. Trailing bytes past the encoded poll are ignored, since
+/// poll accounts are allocated with room to grow.
+fn load_poll(poll_account: &AccountInfo) -> Result<Poll, ProgramError> {
+    let poll = read_poll(poll_account)?;
+    if poll.is_quarantined {
+        msg!("Poll is quarantined.");
+        return Err(VotingError::PollQuarantined.into());
+    }
+    poll.check_integrity()?;
+    Ok(poll)
+}
+
+/// Deserializes the poll without the quarantine and integrity checks of
+/// `load_poll`.
+fn read_poll(poll_account: &AccountInfo) -> Result<Poll, ProgramError> {
+    let data = poll_account.data.borrow();
+    if data.len() > MAX_POLL_ACCOUNT_LEN {
+        msg!("Poll account exceeds {} bytes.", MAX_POLL_ACCOUNT_LEN);
+        return Err(VotingError::PollTooLarge.into());
+    }
+    Poll::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::BudgetAllocation;
+    use crate::eligibility::METADATA_PROGRAM_ID;
+    use crate::instruction::{
+        AccountRole, CLOSE_POLL_ACCOUNTS, FEATURE_CALL_POLICY, FEATURE_PDA_POLLS,
+        FEATURE_READ_ONLY_MODE, FEATURE_VOTE_RECEIPTS,
+    };
+    use crate::merkle::{compute_root, hash_leaf};
+    use crate::state::{
+        is_after_with_tolerance, is_before_with_tolerance, PollOutcome, MAX_CALL_POLICIES,
+        MAX_OPTIONS, MAX_POLL_ACCOUNT_LEN, TOKEN_PROGRAM_ID,
+    };
+    use crate::test_utils::{
+        account_info, instructions_sysvar_data, nft_metadata_data, token_account_data, FixedClock,
+        PollBuilder,
+    };
+    use crate::weight::WeightTransform;
+
+    /// Id of the program under test, and owner of every mock account. Distinct
+    /// from the all-zero system program id.
+    const PROGRAM_ID: Pubkey = Pubkey::new_from_array([0xAA; 32]);
+    /// Slot reported by the test clock.
+    const TEST_SLOT: u64 = 90_000_000;
+    /// Unix time (seconds) reported by the test clock.
+    const TEST_TIME: u64 = 1_620_000_000;
+
+    /// Processes an instruction at `TEST_SLOT` / `TEST_TIME`.
+    fn process(accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+        let clock = FixedClock { slot: TEST_SLOT, unix_timestamp: TEST_TIME };
+        process_with_clock(accounts, instruction_data, &clock)
+    }
+
+    /// Processes an instruction at `clock`, passing a program config not yet
+    /// created after `accounts` if the instruction takes one.
+    fn process_with_clock(
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+        clock: &FixedClock,
+    ) -> ProgramResult {
+        let takes_config = VotingInstruction::try_from_slice(instruction_data)
+            .is_ok_and(|instruction| instruction.takes_config());
+        if !takes_config {
+            return process_instruction_with_clock(&PROGRAM_ID, accounts, instruction_data, clock);
+        }
+        let mut accounts = accounts.to_vec();
+        accounts.push(account_info(config_key(), &system_program::ID, false, &mut []));
+        process_instruction_with_clock(&PROGRAM_ID, &accounts, instruction_data, clock)
+    }
+
+    /// Address of the program config under test.
+    fn config_key() -> &'static Pubkey {
+        static CONFIG_KEY: std::sync::OnceLock<Pubkey> = std::sync::OnceLock::new();
+        CONFIG_KEY.get_or_init(|| find_config_address(&PROGRAM_ID).0)
+    }
+
+    /// Helper function to generate a dummy Pubkey.
+    fn dummy_pubkey(seed: u8) -> Pubkey {
+        let mut bytes = [0u8; 32];
+        bytes[0] = seed;
+        Pubkey::new_from_array(bytes)
+    }
+
+    /// Writable mock account owned by the program.
+    fn create_account_info<'a>(
+        key: &'a Pubkey,
+        is_signer: bool,
+        data: &'a mut [u8],
+    ) -> AccountInfo<'a> {
+        account_info(key, &PROGRAM_ID, is_signer, data)
+    }
+
+    /// A writable account still owned by the system program, as an address the
+    /// program has not created yet would be.
+    fn system_account_info<'a>(key: &'a Pubkey, data: &'a mut [u8]) -> AccountInfo<'a> {
+        account_info(key, &system_program::ID, false, data)
+    }
+
+    #[test]
+    fn test_create_poll() {
+        let creator_key = dummy_pubkey(1);
+        let (poll_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 7);
+        let mut poll_data = vec![0u8; 1024]; // pre-allocated space
+        let mut creator_data = vec![];
+
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+
+        let mut clock_data = vec![];
+
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+
+        let accounts = &mut [poll_account, creator_account, clock_account];
+        let question = "Best programming language?".to_string();
+        let options = vec!["Rust".to_string(), "Go".to_string(), "JavaScript".to_string()];
+        let start_time = 1_619_999_000;
+        let end_time = 1_620_001_000;
+
+        let instruction = VotingInstruction::CreatePoll {
+            poll_id: 7,
+            question: question.clone(),
+            options: options.clone(),
+            start_time,
+            end_time,
+            moderator: dummy_pubkey(8),
+            allow_new_options: false,
+            attestors: Vec::new(),
+            poll_type: PollType::Plurality,
+            candidates: Vec::new(),
+            max_spoiled_bps: 0,
+            weight_mint: None,
+            blackout_secs: 0,
+            max_selections: 0,
+            terms_hash: None,
+            weight_program: None,
+            unique_question: false,
+            quorum: None,
+            approval_threshold_bps: 0,
+            tie_break: TieBreak::Fail,
+            eligibility: EligibilityRule::Open,
+            max_delegation_depth: 0,
+            weight_curve: WeightCurve::LINEAR,
+            points_per_voter: 0,
+            seats: 0,
+            clock_tolerance_secs: 30,
+        };
+        let instruction_data = instruction.try_to_vec().unwrap();
+
+        // The clock tolerance is bounded.
+        let mut too_lax = VotingInstruction::try_from_slice(&instruction_data).unwrap();
+        if let VotingInstruction::CreatePoll { clock_tolerance_secs, .. } = &mut too_lax {
+            *clock_tolerance_secs = MAX_CLOCK_TOLERANCE_SECS + 1;
+        }
+        assert_eq!(
+            process(accounts, &too_lax.try_to_vec().unwrap()),
+            Err(VotingError::InvalidVotingWindow.into())
+        );
+
+        let result = process(accounts, &instruction_data);
+        assert!(result.is_ok());
+
+        let poll = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(poll.creator, creator_key);
+        assert_eq!(poll.question, question);
+        assert_eq!(poll.options, options);
+        assert_eq!(poll.vote_counts, vec![0, 0, 0]);
+        assert_eq!(poll.start_time, start_time);
+        assert_eq!(poll.end_time, end_time);
+        assert_eq!(poll.clock_tolerance_secs, 30);
+        assert!(!poll.is_closed);
+        assert!(poll.voters.is_empty());
+        assert_eq!(poll.created_at_slot, TEST_SLOT);
+        assert_eq!(poll.created_at_time, TEST_TIME);
+        assert_eq!(poll.first_vote_slot, None);
+        assert_eq!(poll.poll_id, 7);
+        assert_eq!(poll.moderator, dummy_pubkey(8));
+        assert!(!poll.is_quarantined);
+        assert!(poll.ensure_options_mutable().is_ok());
+
+        // The same address cannot be initialized twice.
+        let result = process(accounts, &instruction_data);
+        assert_eq!(result, Err(ProgramError::AccountAlreadyInitialized));
+    }
+
+    #[test]
+    fn test_create_poll_verifies_address() {
+        let creator_key = dummy_pubkey(1);
+        let (poll_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 1);
+        let mut poll_data = vec![0u8; MAX_POLL_ACCOUNT_LEN];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let mut system_data = vec![];
+        let create = |poll_id| {
+            VotingInstruction::CreatePoll {
+                poll_id,
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: 1_619_999_000,
+                end_time: 1_620_001_000,
+                moderator: dummy_pubkey(8),
+                allow_new_options: false,
+                attestors: Vec::new(),
+                poll_type: PollType::Plurality,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+
+        // A poll account owned by the system program is created through the
+        // system program, which must then be passed.
+        let system_id = system_program::ID;
+        let poll_account =
+            AccountInfo::new(&poll_key, false, true, &mut poll_data, &system_id, false, 0);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+        let system_account =
+            AccountInfo::new(&system_id, false, false, &mut system_data, &system_id, true, 0);
+        let accounts = [poll_account, creator_account, clock_account, system_account];
+
+        assert_eq!(process(&accounts[..3], &create(1)), Err(ProgramError::NotEnoughAccountKeys));
+        // Any other poll id derives a different address.
+        assert_eq!(process(&accounts, &create(2)), Err(VotingError::AccountMismatch.into()));
+        assert!(process(&accounts, &create(1)).is_ok());
+
+        let poll = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(poll.creator, creator_key);
+        assert_eq!(poll.poll_id, 1);
+    }
+
+    #[test]
+    fn test_create_proposal() {
+        let creator_key = dummy_pubkey(1);
+        let (poll_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 1);
+        let mut poll_data = vec![0u8; MAX_POLL_ACCOUNT_LEN];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let mut system_data = vec![];
+        let system_id = system_program::ID;
+        let accounts = [
+            AccountInfo::new(&poll_key, false, true, &mut poll_data, &system_id, false, 0),
+            create_account_info(&creator_key, true, &mut creator_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            AccountInfo::new(&system_id, false, false, &mut system_data, &system_id, true, 0),
+        ];
+        let create = VotingInstruction::CreateProposal {
+            poll_id: 1,
+            question: "Fund the audit?".to_string(),
+            start_time: TEST_TIME + 100,
+            end_time: TEST_TIME + 1_000,
+            quorum: Some(5),
+            approval_threshold_bps: 6_000,
+        };
+        assert!(process(&accounts, &create.try_to_vec().unwrap()).is_ok());
+        let poll = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert!(poll.is_proposal);
+        assert_eq!(poll.options, PROPOSAL_OPTIONS);
+        assert_eq!((poll.poll_type, poll.moderator), (PollType::Plurality, creator_key));
+        assert_eq!((poll.quorum, poll.approval_threshold_bps), (Some(5), 6_000));
+
+        // The options stay Yes and No.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .creator(creator_key)
+            .proposal()
+            .window(TEST_TIME + 100, TEST_TIME + 200)
+            .account_data();
+        let update = |options: &[&str]| VotingInstruction::UpdatePoll {
+            question: "Fund the second audit?".to_string(),
+            options: options.iter().map(|option| option.to_string()).collect(),
+            start_time: TEST_TIME + 100,
+            end_time: TEST_TIME + 200,
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &update(&["Yes", "No", "Later"])),
+            Err(VotingError::WrongPollType.into())
+        );
+        assert!(run_instruction(&mut poll, &creator_key, &update(&["Yes", "No"])).is_ok());
+
+        // Only Yes passes, and only with its share of the Yes and No votes;
+        // abstentions count toward the quorum alone.
+        for (counts, abstained, outcome) in [
+            ([3, 2], 0, PollOutcome::Passed),
+            ([2, 3], 0, PollOutcome::Failed),
+            ([3, 3], 0, PollOutcome::Failed),
+            ([3, 1], 1, PollOutcome::Passed),
+            ([3, 1], 0, PollOutcome::QuorumNotMet),
+        ] {
+            let mut state =
+                PollBuilder::new().proposal().vote_counts(&counts).abstain_count(abstained).build();
+            state.quorum = Some(5);
+            state.approval_threshold_bps = 6_000;
+            assert_eq!(state.finalize(), outcome, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_unique_question() {
+        let creator_key = dummy_pubkey(1);
+        let question = "Best programming language?";
+        let (record_key, _) =
+            find_question_address(&PROGRAM_ID, &creator_key, &question_hash(question));
+        let (first_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 1);
+        let (second_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 2);
+        let mut first_data = vec![0u8; MAX_POLL_ACCOUNT_LEN];
+        let mut second_data = vec![0u8; MAX_POLL_ACCOUNT_LEN];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let mut system_data = vec![];
+        let mut record_data = vec![0u8; QUESTION_RECORD_LEN];
+        let system_id = system_program::ID;
+        let create = |poll_id, question: &str| {
+            VotingInstruction::CreatePoll {
+                poll_id,
+                question: question.to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: TEST_TIME + 100,
+                end_time: TEST_TIME + 1_000,
+                moderator: dummy_pubkey(8),
+                allow_new_options: false,
+                attestors: Vec::new(),
+                poll_type: PollType::Plurality,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: true,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+
+        {
+            let accounts = [
+                create_account_info(&first_key, false, &mut first_data),
+                create_account_info(&creator_key, true, &mut creator_data),
+                create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+                AccountInfo::new(&system_id, false, false, &mut system_data, &system_id, true, 0),
+                system_account_info(&record_key, &mut record_data),
+            ];
+            // The record must be passed, at the address for this question.
+            assert_eq!(
+                process(&accounts[..4], &create(1, question)),
+                Err(ProgramError::NotEnoughAccountKeys)
+            );
+            assert_eq!(
+                process(&accounts, &create(1, "Best editor?")),
+                Err(VotingError::AccountMismatch.into())
+            );
+            assert!(process(&accounts, &create(1, question)).is_ok());
+
+            // The registered question cannot be edited away.
+            let update = |question: &str| {
+                VotingInstruction::UpdatePoll {
+                    question: question.to_string(),
+                    options: vec!["Rust".to_string(), "Zig".to_string()],
+                    start_time: TEST_TIME + 100,
+                    end_time: TEST_TIME + 2_000,
+                }
+                .try_to_vec()
+                .unwrap()
+            };
+            assert_eq!(
+                process(&accounts[..3], &update("Best editor?")),
+                Err(VotingError::DuplicateQuestion.into())
+            );
+            assert!(process(&accounts[..3], &update(question)).is_ok());
+        }
+        let record = QuestionRecord::try_from_slice(&record_data).unwrap();
+        assert_eq!(record, QuestionRecord { poll: first_key });
+
+        // A second poll asking the same question is rejected.
+        let accounts = [
+            create_account_info(&second_key, false, &mut second_data),
+            create_account_info(&creator_key, true, &mut creator_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            AccountInfo::new(&system_id, false, false, &mut system_data, &system_id, true, 0),
+            create_account_info(&record_key, false, &mut record_data),
+        ];
+        assert_eq!(
+            process(&accounts, &create(2, question)),
+            Err(VotingError::DuplicateQuestion.into())
+        );
+        assert!(accounts[0].data.borrow().iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_cast_vote() {
+        let creator_key = dummy_pubkey(1);
+        let voter_key = dummy_pubkey(3);
+        let poll_key = dummy_pubkey(2);
+
+        let mut poll_data = PollBuilder::new()
+            .creator(creator_key)
+            .options(&["Rust", "Go", "JavaScript"])
+            .account_data();
+
+        let mut voter_data = vec![];
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let voter_account = create_account_info(&voter_key, true, &mut voter_data);
+
+        let mut clock_data = vec![];
+
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+        let receipt_account = system_account_info(&receipt_key, &mut receipt_data);
+        let system_account = system_account_info(&system_program::ID, &mut system_data);
+
+        let accounts =
+            &mut [poll_account, voter_account, clock_account, receipt_account, system_account];
+
+        // Cast a vote for the first option (index 0)
+        let instruction = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let instruction_data = instruction.try_to_vec().unwrap();
+        let result = process(accounts, &instruction_data);
+        assert!(result.is_ok());
+
+        let poll_after = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(poll_after.vote_counts[0], 1);
+        assert!(poll_after.voters.is_empty());
+        assert_eq!(poll_after.first_vote_slot, Some(TEST_SLOT));
+        assert_eq!(poll_after.ensure_options_mutable(), Err(VotingError::OptionsFrozen.into()));
+
+        let receipt = VoteReceipt::deserialize(&mut &accounts[3].data.borrow()[..]).unwrap();
+        assert_eq!(
+            receipt,
+            VoteReceipt {
+                poll: poll_key,
+                voter: voter_key,
+                option_index: 0,
+                slot: TEST_SLOT,
+                accepted_terms: false,
+                delegate: None,
+                delegated_ballots: 0,
+            }
+        );
+        // Only a delegated vote fills the receipt.
+        assert_eq!(receipt.try_to_vec().unwrap().len(), VOTE_RECEIPT_LEN - 32);
+
+        // Once the receipt belongs to the program, a second vote fails.
+        accounts[3].owner = &PROGRAM_ID;
+        let dup_result = process(accounts, &instruction_data);
+        assert_eq!(dup_result, Err(VotingError::DoubleVote.into()));
+
+        // A receipt at any other address is rejected.
+        accounts[3].key = &poll_key;
+        let result = process(accounts, &instruction_data);
+        assert_eq!(result, Err(VotingError::AccountMismatch.into()));
+    }
+
+    #[test]
+    fn test_vote_honours_legacy_voters() {
+        let voter_key = dummy_pubkey(3);
+        // A poll from before receipts, listing its voters inline.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .vote_counts(&[1, 0])
+            .voters(&[voter_key])
+            .first_vote_slot(1)
+            .account_data();
+
+        let vote = VotingInstruction::Vote {
+            option_index: 1,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &voter_key, &vote),
+            Err(VotingError::DoubleVote.into())
+        );
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &vote).is_ok());
+
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 1]);
+        assert_eq!(state.voters, vec![voter_key]);
+        assert_eq!(poll.receipt_votes(), vec![(dummy_pubkey(4), 1)]);
+
+        // The legacy vote has no receipt recording its choice.
+        let change = VotingInstruction::ChangeVote { new_option_index: 0 };
+        assert_eq!(
+            run_instruction(&mut poll, &voter_key, &change),
+            Err(VotingError::NoVoteToChange.into())
+        );
+    }
+
+    #[test]
+    fn test_close_poll() {
+        let creator_key = dummy_pubkey(1);
+        let poll_key = dummy_pubkey(2);
+
+        // End time in the past relative to our simulated current time.
+        let mut poll_data = PollBuilder::new()
+            .creator(creator_key)
+            .options(&["Rust", "Go", "JavaScript"])
+            .vote_counts(&[3, 2, 1])
+            .window(1_619_900_000, 1_619_999_000)
+            .voters(&[dummy_pubkey(3)])
+            .account_data();
+
+        let mut creator_data = vec![];
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+
+        let mut clock_data = vec![];
+
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+
+        let accounts = &mut [poll_account, creator_account, clock_account];
+        let instruction = VotingInstruction::ClosePoll;
+        let instruction_data = instruction.try_to_vec().unwrap();
+
+        let result = process(accounts, &instruction_data);
+        assert!(result.is_ok());
+
+        let poll_after = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert!(poll_after.is_closed);
+        assert_eq!(poll_after.closed_at_slot, Some(TEST_SLOT));
+    }
+
+    #[test]
+    fn test_create_poll_rejects_too_many_options() {
+        let creator_key = dummy_pubkey(1);
+        let poll_key = dummy_pubkey(2);
+        let mut poll_data = vec![0u8; 1024];
+        let mut creator_data = vec![];
+
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+        let mut clock_data = vec![];
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+        let accounts = &mut [poll_account, creator_account, clock_account];
+
+        let instruction = VotingInstruction::CreatePoll {
+            poll_id: 0,
+            question: "Pick a number".to_string(),
+            options: (0..=MAX_OPTIONS).map(|i| i.to_string()).collect(),
+            start_time: 1_619_999_000,
+            end_time: 1_620_001_000,
+            moderator: dummy_pubkey(8),
+            allow_new_options: false,
+            attestors: Vec::new(),
+            poll_type: PollType::Plurality,
+            candidates: Vec::new(),
+            max_spoiled_bps: 0,
+            weight_mint: None,
+            blackout_secs: 0,
+            max_selections: 0,
+            terms_hash: None,
+            weight_program: None,
+            unique_question: false,
+            quorum: None,
+            approval_threshold_bps: 0,
+            tie_break: TieBreak::Fail,
+            eligibility: EligibilityRule::Open,
+            max_delegation_depth: 0,
+            weight_curve: WeightCurve::LINEAR,
+            points_per_voter: 0,
+            seats: 0,
+            clock_tolerance_secs: 0,
+        };
+        let instruction_data = instruction.try_to_vec().unwrap();
+
+        let result = process(accounts, &instruction_data);
+        assert_eq!(result, Err(VotingError::PollTooLarge.into()));
+    }
+
+    #[test]
+    fn test_vote_rejects_oversized_poll_account() {
+        let creator_key = dummy_pubkey(1);
+        let voter_key = dummy_pubkey(3);
+
+        let mut poll = TestPoll::new();
+        poll.data =
+            PollBuilder::new().creator(creator_key).account_data_with_len(MAX_POLL_ACCOUNT_LEN + 1);
+
+        let instruction = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let result = run_instruction(&mut poll, &voter_key, &instruction);
+        assert_eq!(result, Err(VotingError::PollTooLarge.into()));
+    }
+
+    /// A poll account plus the receipt accounts the program has created for
+    /// it, standing in for the runtime's account store.
+    struct TestPoll {
+        data: Vec<u8>,
+        receipts: Vec<(Pubkey, Vec<u8>)>,
+    }
+
+    impl TestPoll {
+        fn new() -> Self {
+            Self { data: vec![0u8; 1024], receipts: Vec::new() }
+        }
+
+        /// Voters holding a receipt and their current choices, in voting order.
+        fn receipt_votes(&self) -> Vec<(Pubkey, u32)> {
+            self.receipts
+                .iter()
+                .map(|(_, data)| {
+                    let receipt: VoteReceipt = unpack_record(data).unwrap();
+                    (receipt.voter, receipt.option_index)
+                })
+                .collect()
+        }
+    }
+
+    /// Runs one instruction against `poll` signed by `signer`.
+    fn run_instruction(
+        poll: &mut TestPoll,
+        signer: &Pubkey,
+        instruction: &VotingInstruction,
+    ) -> ProgramResult {
+        run_instruction_as(poll, signer, true, instruction)
+    }
+
+    /// Runs one instruction against `poll` with `authority` as the second
+    /// account, signing only if `is_signer` is set. Votes also get the
+    /// authority's receipt account, or ranked ballot account for ranked votes
+    /// (and the system program, to create it); accounts the program writes are
+    /// kept in `receipts` for later instructions.
+    fn run_instruction_as(
+        poll: &mut TestPoll,
+        authority: &Pubkey,
+        is_signer: bool,
+        instruction: &VotingInstruction,
+    ) -> ProgramResult {
+        let poll_key = match instruction {
+            VotingInstruction::CreatePoll { poll_id, .. } => {
+                find_poll_address(&PROGRAM_ID, authority, *poll_id).0
+            },
+            _ => dummy_pubkey(2),
+        };
+        let (receipt_key, receipt_len) = match instruction {
+            VotingInstruction::RankedVote { ranking, .. } => (
+                find_ballot_address(&PROGRAM_ID, &poll_key, authority).0,
+                ranked_ballot_len(ranking.len()),
+            ),
+            VotingInstruction::VoteMulti { option_indices, .. } => (
+                find_ballot_address(&PROGRAM_ID, &poll_key, authority).0,
+                multi_ballot_len(option_indices.len()),
+            ),
+            VotingInstruction::VoteCumulative { allocations, .. } => (
+                find_ballot_address(&PROGRAM_ID, &poll_key, authority).0,
+                cumulative_ballot_len(allocations.len()),
+            ),
+            _ => (find_receipt_address(&PROGRAM_ID, &poll_key, authority).0, VOTE_RECEIPT_LEN),
+        };
+        let existing = poll.receipts.iter().position(|(key, _)| *key == receipt_key);
+        let mut receipt_data = match existing {
+            Some(index) => poll.receipts[index].1.clone(),
+            None => vec![0u8; receipt_len],
+        };
+
+        let result = {
+            let mut signer_data = vec![];
+            let mut clock_data = vec![];
+            let mut system_data = vec![];
+            let mut accounts = vec![
+                create_account_info(&poll_key, false, &mut poll.data),
+                create_account_info(authority, is_signer, &mut signer_data),
+                create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            ];
+            let receipt_account = match existing {
+                Some(_) => create_account_info(&receipt_key, false, &mut receipt_data),
+                None => system_account_info(&receipt_key, &mut receipt_data),
+            };
+            match instruction {
+                VotingInstruction::Vote { .. }
+                | VotingInstruction::RankedVote { .. }
+                | VotingInstruction::VoteMulti { .. }
+                | VotingInstruction::VoteCumulative { .. }
+                | VotingInstruction::SpoilBallot { .. }
+                | VotingInstruction::Abstain { .. } => {
+                    accounts.push(receipt_account);
+                    accounts.push(system_account_info(&system_program::ID, &mut system_data));
+                },
+                VotingInstruction::ChangeVote { .. } => accounts.push(receipt_account),
+                _ => {},
+            }
+            process(&accounts, &instruction.try_to_vec().unwrap())
+        };
+
+        if result.is_ok() && receipt_data.iter().any(|&byte| byte != 0) {
+            match existing {
+                Some(index) => poll.receipts[index].1 = receipt_data,
+                None => poll.receipts.push((receipt_key, receipt_data)),
+            }
+        }
+        result
+    }
+
+    /// Reference model of the poll lifecycle the program is checked against.
+    #[derive(Default)]
+    struct PollModel {
+        vote_counts: Vec<u64>,
+        votes: Vec<(Pubkey, u32)>,
+        is_closed: bool,
+    }
+
+    impl PollModel {
+        fn vote(&mut self, voter: Pubkey, option_index: u32) -> ProgramResult {
+            if self.is_closed {
+                return Err(VotingError::PollClosed.into());
+            }
+            if self.votes.iter().any(|(recorded, _)| *recorded == voter) {
+                return Err(VotingError::DoubleVote.into());
+            }
+            let idx = option_index as usize;
+            if idx >= self.vote_counts.len() {
+                return Err(VotingError::InvalidOption.into());
+            }
+            self.vote_counts[idx] += 1;
+            self.votes.push((voter, option_index));
+            Ok(())
+        }
+
+        fn change(&mut self, voter: Pubkey, new_option_index: u32) -> ProgramResult {
+            if self.is_closed {
+                return Err(VotingError::PollClosed.into());
+            }
+            let vote = self
+                .votes
+                .iter_mut()
+                .find(|(recorded, _)| *recorded == voter)
+                .ok_or(VotingError::NoVoteToChange)?;
+            let idx = new_option_index as usize;
+            if idx >= self.vote_counts.len() {
+                return Err(VotingError::InvalidOption.into());
+            }
+            self.vote_counts[vote.1 as usize] -= 1;
+            self.vote_counts[idx] += 1;
+            vote.1 = new_option_index;
+            Ok(())
+        }
+
+        fn close(&mut self, caller: Pubkey, creator: Pubkey) -> ProgramResult {
+            if caller != creator {
+                return Err(VotingError::Unauthorized.into());
+            }
+            if self.is_closed {
+                return Err(VotingError::PollClosed.into());
+            }
+            self.is_closed = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_matches_model() {
+        let creator_key = dummy_pubkey(1);
+
+        for seed in 0..32u64 {
+            let mut rng = seed;
+            let mut next = |bound: u64| {
+                rng = rng
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                (rng >> 33) % bound
+            };
+
+            // End exactly at the simulated current time so both voting and
+            // closing are allowed, leaving sequencing as the only constraint.
+            let mut poll = TestPoll::new();
+            let create = VotingInstruction::CreatePoll {
+                poll_id: 0,
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string(), "JavaScript".to_string()],
+                start_time: 1_619_999_000,
+                end_time: TEST_TIME,
+                moderator: dummy_pubkey(8),
+                allow_new_options: false,
+                attestors: Vec::new(),
+                poll_type: PollType::Plurality,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            };
+            assert!(run_instruction(&mut poll, &creator_key, &create).is_ok());
+
+            let mut model = PollModel { vote_counts: vec![0; 3], ..PollModel::default() };
+
+            for _ in 0..64 {
+                let (signer, instruction, expected) = if next(8) == 0 {
+                    // Mostly the creator, sometimes an outsider.
+                    let caller = if next(2) == 0 { creator_key } else { dummy_pubkey(9) };
+                    (caller, VotingInstruction::ClosePoll, model.close(caller, creator_key))
+                } else {
+                    let voter = dummy_pubkey(10 + next(6) as u8);
+                    let option_index = next(4) as u32; // index 3 is out of range
+                    if next(4) == 0 {
+                        (
+                            voter,
+                            VotingInstruction::ChangeVote { new_option_index: option_index },
+                            model.change(voter, option_index),
+                        )
+                    } else {
+                        (
+                            voter,
+                            VotingInstruction::Vote {
+                                option_index,
+                                accept_terms: false,
+                                eligibility_proof: Vec::new(),
+                            },
+                            model.vote(voter, option_index),
+                        )
+                    }
+                };
+
+                let result = run_instruction(&mut poll, &signer, &instruction);
+                assert_eq!(result, expected, "seed {} diverged on {:?}", seed, instruction);
+
+                let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+                assert_eq!(state.vote_counts, model.vote_counts);
+                assert_eq!(poll.receipt_votes(), model.votes);
+                assert!(state.voters.is_empty());
+                assert_eq!(state.is_closed, model.is_closed);
+                assert_eq!(state.vote_counts.iter().sum::<u64>(), poll.receipts.len() as u64);
+            }
+        }
+    }
+
+    /// Who may run an instruction in `test_access_matrix`.
+    enum Access {
+        /// Any signer.
+        Anyone,
+        /// Only this key; any other signer is `Unauthorized`.
+        Only(Pubkey),
+        /// No account signs, so anyone can.
+        Unsigned,
+    }
+
+    #[test]
+    fn test_access_matrix() {
+        let creator_key = dummy_pubkey(1);
+        let moderator_key = dummy_pubkey(8);
+        let candidate_key = dummy_pubkey(5);
+        let attestor_key = dummy_pubkey(6);
+        let voter_key = dummy_pubkey(3);
+        let admin_key = dummy_pubkey(7);
+        let outsider_key = dummy_pubkey(9);
+        let poll_key = dummy_pubkey(2);
+        let open = || PollBuilder::new().creator(creator_key).moderator(moderator_key);
+        let ranked = || open().poll_type(PollType::RankedChoice).closed();
+
+        // A runnable instance of `instruction`'s variant, the poll it runs
+        // against (`None` for a new poll account) and who may run it. Every
+        // variant is matched, so a new one cannot be left out.
+        let case = |instruction: VotingInstruction| match instruction {
+            VotingInstruction::CreatePoll { .. } => (
+                VotingInstruction::CreatePoll {
+                    poll_id: 0,
+                    question: "Best programming language?".to_string(),
+                    options: vec!["Rust".to_string(), "Go".to_string()],
+                    start_time: 1_619_999_000,
+                    end_time: 1_620_001_000,
+                    moderator: moderator_key,
+                    allow_new_options: false,
+                    attestors: Vec::new(),
+                    poll_type: PollType::Plurality,
+                    candidates: Vec::new(),
+                    max_spoiled_bps: 0,
+                    weight_mint: None,
+                    blackout_secs: 0,
+                    max_selections: 0,
+                    terms_hash: None,
+                    weight_program: None,
+                    unique_question: false,
+                    quorum: None,
+                    approval_threshold_bps: 0,
+                    tie_break: TieBreak::Fail,
+                    eligibility: EligibilityRule::Open,
+                    max_delegation_depth: 0,
+                    weight_curve: WeightCurve::LINEAR,
+                    points_per_voter: 0,
+                    seats: 0,
+                    clock_tolerance_secs: 0,
+                },
+                None,
+                Access::Anyone,
+            ),
+            VotingInstruction::CreateProposal { .. } => (
+                VotingInstruction::CreateProposal {
+                    poll_id: 0,
+                    question: "Adopt Rust?".to_string(),
+                    start_time: 1_619_999_000,
+                    end_time: 1_620_001_000,
+                    quorum: None,
+                    approval_threshold_bps: 0,
+                },
+                None,
+                Access::Anyone,
+            ),
+            VotingInstruction::Vote { .. } => (
+                VotingInstruction::Vote {
+                    option_index: 0,
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                },
+                Some(open()),
+                Access::Anyone,
+            ),
+            VotingInstruction::ClosePoll => (
+                VotingInstruction::ClosePoll,
+                Some(open().window(1_619_999_000, TEST_TIME)),
+                Access::Only(creator_key),
+            ),
+            VotingInstruction::GetVersion => {
+                (VotingInstruction::GetVersion, None, Access::Unsigned)
+            },
+            VotingInstruction::ChangeVote { .. } => (
+                VotingInstruction::ChangeVote { new_option_index: 1 },
+                Some(open().vote_counts(&[2, 0])),
+                Access::Anyone,
+            ),
+            VotingInstruction::CancelPoll => {
+                (VotingInstruction::CancelPoll, Some(open()), Access::Only(creator_key))
+            },
+            VotingInstruction::QuarantinePoll => {
+                (VotingInstruction::QuarantinePoll, Some(open()), Access::Only(moderator_key))
+            },
+            VotingInstruction::UpdatePoll { .. } => (
+                VotingInstruction::UpdatePoll {
+                    question: "Best systems language?".to_string(),
+                    options: vec!["Rust".to_string(), "Zig".to_string()],
+                    start_time: TEST_TIME + 100,
+                    end_time: TEST_TIME + 2_000,
+                },
+                Some(open().window(TEST_TIME + 100, TEST_TIME + 1_000)),
+                Access::Only(creator_key),
+            ),
+            VotingInstruction::AddOption { .. } => (
+                VotingInstruction::AddOption { label: "Zig".to_string() },
+                Some(open().allow_new_options()),
+                Access::Anyone,
+            ),
+            VotingInstruction::ExtendPoll { .. } => (
+                VotingInstruction::ExtendPoll { new_end_time: 1_620_002_000 },
+                Some(open()),
+                Access::Only(creator_key),
+            ),
+            VotingInstruction::SubmitAttestation { .. } => (
+                VotingInstruction::SubmitAttestation { signature: [9u8; 64] },
+                Some(open().attestors(&[attestor_key]).closed_at_slot(77)),
+                Access::Only(attestor_key),
+            ),
+            VotingInstruction::RankedVote { .. } => (
+                VotingInstruction::RankedVote {
+                    ranking: vec![1, 0],
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                },
+                Some(open().poll_type(PollType::RankedChoice)),
+                Access::Anyone,
+            ),
+            VotingInstruction::Tally => {
+                (VotingInstruction::Tally, Some(ranked()), Access::Unsigned)
+            },
+            VotingInstruction::WithdrawOption { .. } => (
+                VotingInstruction::WithdrawOption { option_index: 0 },
+                Some(open().candidates(&[Some(candidate_key), None])),
+                Access::Only(candidate_key),
+            ),
+            VotingInstruction::SpoilBallot { .. } => (
+                VotingInstruction::SpoilBallot {
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                },
+                Some(open()),
+                Access::Anyone,
+            ),
+            VotingInstruction::VoteMulti { .. } => (
+                VotingInstruction::VoteMulti {
+                    option_indices: vec![0, 1],
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                },
+                Some(open().max_selections(2)),
+                Access::Anyone,
+            ),
+            VotingInstruction::Abstain { .. } => (
+                VotingInstruction::Abstain { accept_terms: false, eligibility_proof: Vec::new() },
+                Some(open()),
+                Access::Anyone,
+            ),
+            VotingInstruction::FinalizePoll => (
+                VotingInstruction::FinalizePoll,
+                Some(open().vote_counts(&[3, 1]).closed()),
+                Access::Unsigned,
+            ),
+            VotingInstruction::SnapshotResult { .. } => (
+                VotingInstruction::SnapshotResult { label: "day one".to_string() },
+                Some(open().vote_counts(&[3, 1])),
+                Access::Only(creator_key),
+            ),
+            VotingInstruction::TallyBatch => {
+                (VotingInstruction::TallyBatch, Some(ranked()), Access::Anyone)
+            },
+            VotingInstruction::RegisterVoter { .. } => {
+                (VotingInstruction::RegisterVoter { delegate: None }, None, Access::Anyone)
+            },
+            VotingInstruction::SetDelegate { .. } => {
+                (VotingInstruction::SetDelegate { delegate: dummy_pubkey(4) }, None, Access::Anyone)
+            },
+            VotingInstruction::RevokeDelegate => {
+                (VotingInstruction::RevokeDelegate, None, Access::Anyone)
+            },
+            VotingInstruction::VoteCumulative { .. } => (
+                VotingInstruction::VoteCumulative {
+                    allocations: vec![(0, 2), (1, 1)],
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                },
+                Some(open().points_per_voter(3)),
+                Access::Anyone,
+            ),
+            VotingInstruction::InitializeConfig { .. } => (
+                VotingInstruction::InitializeConfig { admin: admin_key, read_only_mode: false },
+                None,
+                Access::Anyone,
+            ),
+            VotingInstruction::SetReadOnlyMode { .. } => (
+                VotingInstruction::SetReadOnlyMode { read_only_mode: true },
+                None,
+                Access::Only(admin_key),
+            ),
+            VotingInstruction::SetCallPolicy { .. } => (
+                VotingInstruction::SetCallPolicy {
+                    instruction: 19,
+                    policy: CallPolicy::TopLevelOnly,
+                },
+                None,
+                Access::Only(admin_key),
+            ),
+            VotingInstruction::SetCpiAllowlist { .. } => (
+                VotingInstruction::SetCpiAllowlist { programs: vec![outsider_key] },
+                None,
+                Access::Only(admin_key),
+            ),
+        };
+
+        // The accounts `instruction` lists, as (key, signer, program-owned,
+        // data), with `authority` as its wallet and `poll_data` as its poll.
+        let accounts_for = |instruction: &VotingInstruction,
+                            poll_data: &[u8],
+                            authority: Pubkey,
+                            signed: bool| {
+            let poll_key = match instruction {
+                VotingInstruction::CreatePoll { poll_id, .. }
+                | VotingInstruction::CreateProposal { poll_id, .. } => {
+                    find_poll_address(&PROGRAM_ID, &authority, *poll_id).0
+                },
+                _ => poll_key,
+            };
+            let mut voter_record = VoterRecord {
+                voter: authority,
+                registered_at: 0,
+                registered_slot: 0,
+                polls_participated: 0,
+                delegate: None,
+            }
+            .try_to_vec()
+            .unwrap();
+            voter_record.resize(VOTER_RECORD_LEN, 0);
+            let mut receipt = VoteReceipt {
+                poll: poll_key,
+                voter: authority,
+                option_index: 0,
+                slot: 0,
+                accepted_terms: false,
+                delegate: None,
+                delegated_ballots: 0,
+            }
+            .try_to_vec()
+            .unwrap();
+            receipt.resize(VOTE_RECEIPT_LEN, 0);
+            let mut config = ProgramConfig { admin: admin_key, ..ProgramConfig::default() }
+                .try_to_vec()
+                .unwrap();
+            config.resize(PROGRAM_CONFIG_LEN, 0);
+            let created = |key: Pubkey, len: usize| (key, false, false, vec![0u8; len]);
+            instruction
+                .accounts()
+                .iter()
+                .map(|spec| match spec.role {
+                    AccountRole::Poll => (poll_key, false, true, poll_data.to_vec()),
+                    AccountRole::Creator
+                    | AccountRole::Voter
+                    | AccountRole::Caller
+                    | AccountRole::Moderator
+                    | AccountRole::Proposer
+                    | AccountRole::Candidate
+                    | AccountRole::Attestor
+                    | AccountRole::Payer
+                    | AccountRole::Admin => (authority, signed, true, Vec::new()),
+                    AccountRole::Program => (PROGRAM_ID, true, false, Vec::new()),
+                    AccountRole::Clock => (sysvar::clock::ID, false, true, Vec::new()),
+                    AccountRole::SystemProgram => (system_program::ID, false, false, Vec::new()),
+                    AccountRole::Receipt => {
+                        let key = find_receipt_address(&PROGRAM_ID, &poll_key, &authority).0;
+                        match instruction {
+                            VotingInstruction::ChangeVote { .. } => {
+                                (key, false, true, receipt.clone())
+                            },
+                            _ => created(key, VOTE_RECEIPT_LEN),
+                        }
+                    },
+                    AccountRole::Ballot => {
+                        created(find_ballot_address(&PROGRAM_ID, &poll_key, &authority).0, 1024)
+                    },
+                    AccountRole::Attestation => created(
+                        find_attestation_address(&PROGRAM_ID, &poll_key, &authority).0,
+                        ATTESTATION_RECORD_LEN,
+                    ),
+                    AccountRole::Snapshot => match instruction {
+                        VotingInstruction::SnapshotResult { label } => {
+                            created(find_snapshot_address(&PROGRAM_ID, &poll_key, label).0, 1024)
+                        },
+                        _ => unreachable!("only snapshots take a snapshot account"),
+                    },
+                    AccountRole::Cursor => created(
+                        find_cursor_address(&PROGRAM_ID, &poll_key, CursorTask::Tally).0,
+                        MAX_CURSOR_ACCOUNT_LEN,
+                    ),
+                    AccountRole::VoterRecord => {
+                        let key = find_voter_record_address(&PROGRAM_ID, &authority).0;
+                        match instruction {
+                            VotingInstruction::RegisterVoter { .. } => {
+                                created(key, VOTER_RECORD_LEN)
+                            },
+                            _ => (key, false, true, voter_record.clone()),
+                        }
+                    },
+                    AccountRole::Config => match instruction {
+                        VotingInstruction::InitializeConfig { .. } => {
+                            created(*config_key(), PROGRAM_CONFIG_LEN)
+                        },
+                        _ => (*config_key(), false, true, config.clone()),
+                    },
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Every variant decodes from its tag with its fields zeroed, up to the
+        // first tag that is not one.
+        let mut variants = 0;
+        for tag in 0..=u8::MAX {
+            let mut data = vec![0u8; 256];
+            data[0] = tag;
+            let instruction = match VotingInstruction::deserialize(&mut &data[..]) {
+                Ok(instruction) => instruction,
+                Err(_) => break,
+            };
+            variants += 1;
+            assert_eq!(instruction.takes_config(), !CONFIG_FREE_TAGS.contains(&tag));
+
+            let (instruction, poll, access) = case(instruction);
+            let poll_data = poll.map_or_else(|| vec![0u8; 1024], |poll| poll.account_data());
+            let signature_missing = Err(ProgramError::MissingRequiredSignature);
+            // (authority, signed, expected result)
+            let matrix = match access {
+                Access::Anyone => vec![
+                    (voter_key, true, Ok(())),
+                    (outsider_key, true, Ok(())),
+                    (voter_key, false, signature_missing.clone()),
+                    (outsider_key, false, signature_missing),
+                ],
+                Access::Only(key) => vec![
+                    (key, true, Ok(())),
+                    (outsider_key, true, Err(VotingError::Unauthorized.into())),
+                    (key, false, signature_missing.clone()),
+                    (outsider_key, false, signature_missing),
+                ],
+                Access::Unsigned => vec![(outsider_key, false, Ok(()))],
+            };
+
+            for (authority, signed, expected) in matrix {
+                let mut accounts = accounts_for(&instruction, &poll_data, authority, signed);
+                let accounts: Vec<AccountInfo> = accounts
+                    .iter_mut()
+                    .map(|(key, signer, program_owned, data)| {
+                        let owner = if *program_owned { &PROGRAM_ID } else { &system_program::ID };
+                        account_info(key, owner, *signer, data)
+                    })
+                    .collect();
+                let result = process(&accounts, &instruction.try_to_vec().unwrap());
+                assert_eq!(
+                    result, expected,
+                    "{:?} by {:?} (signed: {})",
+                    instruction, authority, signed
+                );
+            }
+        }
+        assert_eq!(variants, INSTRUCTION_COUNT);
+    }
+
+    #[test]
+    fn test_account_schema_enforced() {
+        let creator_key = dummy_pubkey(1);
+        let poll_key = dummy_pubkey(2);
+        let mut poll_data = vec![0u8; 1024];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let instruction_data = VotingInstruction::ClosePoll.try_to_vec().unwrap();
+
+        // Missing accounts are reported instead of panicking on an index.
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let result = process(&[poll_account], &instruction_data);
+        assert_eq!(result, Err(ProgramError::NotEnoughAccountKeys));
+
+        // A read-only poll account is an account mismatch.
+        let poll_account =
+            AccountInfo::new(&poll_key, false, false, &mut poll_data, &PROGRAM_ID, false, 0);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+        let result = process(&[poll_account, creator_account, clock_account], &instruction_data);
+        assert_eq!(result, Err(VotingError::AccountMismatch.into()));
+
+        // A poll account owned by another program is rejected before it is read.
+        let foreign_owner = dummy_pubkey(9);
+        let poll_account =
+            AccountInfo::new(&poll_key, false, true, &mut poll_data, &foreign_owner, false, 0);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+        let clock_account = create_account_info(&sysvar::clock::ID, false, &mut clock_data);
+        let result = process(&[poll_account, creator_account, clock_account], &instruction_data);
+        assert_eq!(result, Err(ProgramError::IncorrectProgramId));
+
+        assert_eq!(VotingInstruction::ClosePoll.accounts(), &CLOSE_POLL_ACCOUNTS);
+    }
+
+    #[test]
+    fn test_rejects_wrong_clock_account() {
+        let creator_key = dummy_pubkey(1);
+        let poll_key = dummy_pubkey(2);
+        let fake_clock_key = dummy_pubkey(4);
+        let mut poll_data = vec![0u8; 1024];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![0u8; 40];
+
+        let poll_account = create_account_info(&poll_key, false, &mut poll_data);
+        let creator_account = create_account_info(&creator_key, true, &mut creator_data);
+        let clock_account = create_account_info(&fake_clock_key, false, &mut clock_data);
+        let config_account = system_account_info(config_key(), &mut []);
+
+        let instruction_data = VotingInstruction::CreatePoll {
+            poll_id: 0,
+            question: "Best programming language?".to_string(),
+            options: vec!["Rust".to_string(), "Go".to_string()],
+            start_time: TEST_TIME,
+            end_time: TEST_TIME + 1_000,
+            moderator: dummy_pubkey(8),
+            allow_new_options: false,
+            attestors: Vec::new(),
+            poll_type: PollType::Plurality,
+            candidates: Vec::new(),
+            max_spoiled_bps: 0,
+            weight_mint: None,
+            blackout_secs: 0,
+            max_selections: 0,
+            terms_hash: None,
+            weight_program: None,
+            unique_question: false,
+            quorum: None,
+            approval_threshold_bps: 0,
+            tie_break: TieBreak::Fail,
+            eligibility: EligibilityRule::Open,
+            max_delegation_depth: 0,
+            weight_curve: WeightCurve::LINEAR,
+            points_per_voter: 0,
+            seats: 0,
+            clock_tolerance_secs: 0,
+        }
+        .try_to_vec()
+        .unwrap();
+
+        // The real entrypoint reads the clock sysvar and must not accept a stand-in.
+        let result = process_instruction(
+            &PROGRAM_ID,
+            &[poll_account, creator_account, clock_account, config_account],
+            &instruction_data,
+        );
+        assert_eq!(result, Err(VotingError::AccountMismatch.into()));
+    }
+
+    #[test]
+    fn test_cancel_poll() {
+        let creator_key = dummy_pubkey(1);
+        let cancel = VotingInstruction::CancelPoll;
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let fresh = || {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new().creator(creator_key).account_data();
+            poll
+        };
+
+        // Only the creator may cancel.
+        let mut poll = fresh();
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(9), &cancel),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(run_instruction(&mut poll, &creator_key, &cancel).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert!(state.is_cancelled);
+        assert!(!state.is_closed);
+
+        // A cancelled poll takes no votes, cannot be closed or cancelled again.
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote),
+            Err(VotingError::PollCancelled.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &VotingInstruction::ClosePoll),
+            Err(VotingError::PollCancelled.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &cancel),
+            Err(VotingError::PollCancelled.into())
+        );
+
+        // Once voting has started and a vote is in, cancelling is too late.
+        let mut poll = fresh();
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &vote).is_ok());
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &cancel),
+            Err(VotingError::VotesAlreadyCast.into())
+        );
+
+        // Before the voting window opens, cancelling is always allowed.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .creator(creator_key)
+            .window(TEST_TIME + 100, TEST_TIME + 200)
+            .vote_counts(&[1, 0])
+            .account_data();
+        assert!(run_instruction(&mut poll, &creator_key, &cancel).is_ok());
+    }
+
+    #[test]
+    fn test_quarantine_poll() {
+        let moderator_key = dummy_pubkey(8);
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let quarantine = VotingInstruction::QuarantinePoll;
+
+        // Vote counts that no longer line up with the options are caught on load.
+        let mut poll = TestPoll::new();
+        poll.data =
+            PollBuilder::new().moderator(moderator_key).vote_counts(&[4, 1, 2]).account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote),
+            Err(VotingError::CorruptPollState.into())
+        );
+
+        // Only the moderator can quarantine, even a poll that fails its checks.
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(1), &quarantine),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(run_instruction(&mut poll, &moderator_key, &quarantine).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert!(state.is_quarantined);
+
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote),
+            Err(VotingError::PollQuarantined.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &moderator_key, &quarantine),
+            Err(VotingError::PollQuarantined.into())
+        );
+
+        // A reversed voting window is corrupt too.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().window(TEST_TIME + 1, TEST_TIME).account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote),
+            Err(VotingError::CorruptPollState.into())
+        );
+    }
+
+    #[test]
+    fn test_update_poll() {
+        let creator_key = dummy_pubkey(1);
+        let update = VotingInstruction::UpdatePoll {
+            question: "Best systems language?".to_string(),
+            options: vec!["Rust".to_string(), "C".to_string(), "Zig".to_string()],
+            start_time: TEST_TIME + 50,
+            end_time: TEST_TIME + 500,
+        };
+        let upcoming = || {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new()
+                .creator(creator_key)
+                .window(TEST_TIME + 100, TEST_TIME + 200)
+                .account_data();
+            poll
+        };
+
+        let mut poll = upcoming();
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(9), &update),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(run_instruction(&mut poll, &creator_key, &update).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.question, "Best systems language?");
+        assert_eq!(state.options, vec!["Rust", "C", "Zig"]);
+        assert_eq!(state.vote_counts, vec![0, 0, 0]);
+        assert_eq!((state.start_time, state.end_time), (TEST_TIME + 50, TEST_TIME + 500));
+
+        // The new window and options are validated like CreatePoll's.
+        let reversed = VotingInstruction::UpdatePoll {
+            question: "Best systems language?".to_string(),
+            options: vec!["Rust".to_string()],
+            start_time: TEST_TIME + 500,
+            end_time: TEST_TIME + 50,
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &reversed),
+            Err(VotingError::InvalidVotingWindow.into())
+        );
+
+        // Too late once the window has opened.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().creator(creator_key).account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &update),
+            Err(VotingError::PollAlreadyStarted.into())
+        );
+
+        // Or once any ballot is recorded.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .creator(creator_key)
+            .window(TEST_TIME + 100, TEST_TIME + 200)
+            .first_vote_slot(1)
+            .account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &update),
+            Err(VotingError::OptionsFrozen.into())
+        );
+    }
+
+    #[test]
+    fn test_ranked_choice() {
+        let rank = |ranking: &[u32]| VotingInstruction::RankedVote {
+            ranking: ranking.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .poll_type(PollType::RankedChoice)
+            .account_data();
+
+        // Ranked polls take rankings, not single votes.
+        assert_eq!(
+            run_instruction(
+                &mut poll,
+                &dummy_pubkey(3),
+                &VotingInstruction::Vote {
+                    option_index: 0,
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                }
+            ),
+            Err(VotingError::WrongPollType.into())
+        );
+        for bad in [&[][..], &[3], &[1, 1]] {
+            assert_eq!(
+                run_instruction(&mut poll, &dummy_pubkey(3), &rank(bad)),
+                Err(VotingError::InvalidOption.into())
+            );
+        }
+
+        // Zig is eliminated first and its ballots transfer to Go.
+        let ballots: [&[u32]; 5] = [&[0], &[0], &[1], &[1], &[2, 1, 0]];
+        for (seed, ranking) in (10u8..).zip(ballots) {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank(ranking)).is_ok());
+        }
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(10), &rank(&[1])),
+            Err(VotingError::DoubleVote.into())
+        );
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 2, 1]);
+
+        let poll_key = dummy_pubkey(2);
+        let tally = |poll_data: &mut Vec<u8>, ballots: &mut [(Pubkey, Vec<u8>)]| {
+            let mut accounts = vec![create_account_info(&poll_key, false, poll_data)];
+            for (key, data) in ballots.iter_mut() {
+                accounts.push(create_account_info(key, false, data));
+            }
+            process(&accounts, &VotingInstruction::Tally.try_to_vec().unwrap())
+        };
+        assert_eq!(
+            tally(&mut poll.data, &mut poll.receipts),
+            Err(VotingError::PollNotFinalized.into())
+        );
+
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        state.is_closed = true;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+
+        // Every ballot, each once.
+        let mut missing = poll.receipts[..4].to_vec();
+        assert_eq!(tally(&mut poll.data, &mut missing), Err(VotingError::AccountMismatch.into()));
+        let mut repeated = poll.receipts[..4].to_vec();
+        repeated.push(poll.receipts[0].clone());
+        assert_eq!(tally(&mut poll.data, &mut repeated), Err(VotingError::AccountMismatch.into()));
+
+        assert!(tally(&mut poll.data, &mut poll.receipts).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert!(state.is_tallied);
+        assert_eq!(state.winner, Some(1));
+        assert_eq!(
+            tally(&mut poll.data, &mut poll.receipts),
+            Err(VotingError::AlreadyTallied.into())
+        );
+    }
+
+    #[test]
+    fn test_multi_seat_tally() {
+        let rank = |ranking: &[u32]| VotingInstruction::RankedVote {
+            ranking: ranking.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig", "C"])
+            .poll_type(PollType::RankedChoice)
+            .seats(2)
+            .account_data();
+
+        // Seven ballots set a quota of 3. Rust's surplus of one vote moves to
+        // Go at a quarter per ballot, and with C's ballot Go edges out Zig.
+        let ballots: [&[u32]; 7] = [&[0, 1], &[0, 1], &[0, 1], &[0, 1], &[2], &[2], &[3, 1]];
+        for (seed, ranking) in (10u8..).zip(ballots) {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank(ranking)).is_ok());
+        }
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        state.is_closed = true;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+
+        let poll_key = dummy_pubkey(2);
+        let mut accounts = vec![create_account_info(&poll_key, false, &mut poll.data)];
+        for (key, data) in poll.receipts.iter_mut() {
+            accounts.push(create_account_info(key, false, data));
+        }
+        assert!(process(&accounts, &VotingInstruction::Tally.try_to_vec().unwrap()).is_ok());
+        let mut state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert!(state.is_tallied);
+        assert_eq!((state.winner, state.elected.clone()), (None, vec![0, 1]));
+        assert_eq!(state.finalize(), PollOutcome::Passed);
+
+        // Only ranked-choice polls elect several options, and never more
+        // options than they offer.
+        let creator_key = dummy_pubkey(1);
+        let mut poll_data = vec![0u8; 1024];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let accounts = [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&creator_key, true, &mut creator_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+        ];
+        let create = |poll_type, seats| {
+            VotingInstruction::CreatePoll {
+                poll_id: 0,
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: 1_619_999_000,
+                end_time: 1_620_001_000,
+                moderator: dummy_pubkey(8),
+                allow_new_options: false,
+                attestors: Vec::new(),
+                poll_type,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats,
+                clock_tolerance_secs: 0,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+        assert_eq!(
+            process(&accounts, &create(PollType::Borda, 2)),
+            Err(VotingError::WrongPollType.into())
+        );
+        assert_eq!(
+            process(&accounts, &create(PollType::RankedChoice, 3)),
+            Err(VotingError::PollTooLarge.into())
+        );
+    }
+
+    #[test]
+    fn test_borda_vote() {
+        let rank = |ranking: &[u32]| VotingInstruction::RankedVote {
+            ranking: ranking.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig", "C"])
+            .poll_type(PollType::Borda)
+            .withdrawn_options(&[3])
+            .account_data();
+
+        // Every option still standing must be ranked.
+        for (bad, error) in [
+            (&[0, 1][..], VotingError::InvalidOption),
+            (&[0, 1, 2, 3][..], VotingError::OptionWithdrawn),
+            (&[0, 1, 1][..], VotingError::InvalidOption),
+        ] {
+            assert_eq!(run_instruction(&mut poll, &dummy_pubkey(3), &rank(bad)), Err(error.into()));
+        }
+
+        // Places score 2, 1 and 0: Rust 2 + 0 + 1, Go 1 + 2 + 2, Zig 0 + 1 + 0.
+        let ballots: [&[u32]; 3] = [&[0, 1, 2], &[1, 2, 0], &[1, 0, 2]];
+        for (seed, ranking) in (10u8..).zip(ballots) {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank(ranking)).is_ok());
+        }
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 2, 0, 0]);
+        assert_eq!(state.vote_weights, vec![3, 5, 1, 0]);
+        assert_eq!(state.participation(), 3);
+
+        // The highest score wins without a separate tally.
+        state.is_closed = true;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+        let finalize = VotingInstruction::FinalizePoll;
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &finalize).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.winner, Some(1));
+
+        // Options cannot be added once ballots have scored the slate.
+        let creator_key = dummy_pubkey(1);
+        let (poll_key, _) = find_poll_address(&PROGRAM_ID, &creator_key, 0);
+        let mut poll_data = vec![0u8; 1024];
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let accounts = [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&creator_key, true, &mut creator_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+        ];
+        let create = |allow_new_options| {
+            VotingInstruction::CreatePoll {
+                poll_id: 0,
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: 1_619_999_000,
+                end_time: 1_620_001_000,
+                moderator: dummy_pubkey(8),
+                allow_new_options,
+                attestors: Vec::new(),
+                poll_type: PollType::Borda,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+        assert_eq!(process(&accounts, &create(true)), Err(VotingError::WrongPollType.into()));
+        assert!(process(&accounts, &create(false)).is_ok());
+    }
+
+    #[test]
+    fn test_condorcet_vote() {
+        let rank = |ranking: &[u32]| VotingInstruction::RankedVote {
+            ranking: ranking.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .poll_type(PollType::Condorcet)
+            .account_data();
+        let repeated = rank(&[0, 0]);
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &repeated),
+            Err(VotingError::InvalidOption.into())
+        );
+
+        // Rust and Zig split the first preferences, but Go, ranked above
+        // unranked options, beats each of them three to two.
+        let ballots: [&[u32]; 5] = [&[0, 1, 2], &[0, 1, 2], &[2, 1, 0], &[2, 1, 0], &[1]];
+        for (seed, ranking) in (10u8..).zip(ballots) {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank(ranking)).is_ok());
+        }
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 1, 2]);
+        assert_eq!(state.pairwise, vec![vec![0, 2, 2], vec![3, 0, 3], vec![2, 2, 0]]);
+
+        state.is_closed = true;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+        let finalize = VotingInstruction::FinalizePoll;
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &finalize).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.winner, state.outcome), (Some(1), Some(PollOutcome::Passed)));
+    }
+
+    #[test]
+    fn test_tally_batch() {
+        let rank = |ranking: &[u32]| VotingInstruction::RankedVote {
+            ranking: ranking.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .poll_type(PollType::RankedChoice)
+            .account_data();
+        let ballots: [&[u32]; 5] = [&[0], &[0], &[1], &[1], &[2, 1, 0]];
+        for (seed, ranking) in (10u8..).zip(ballots) {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank(ranking)).is_ok());
+        }
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        state.is_closed = true;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+        poll.receipts.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+        let poll_key = dummy_pubkey(2);
+        let caller_key = dummy_pubkey(7);
+        let (cursor_key, _) = find_cursor_address(&PROGRAM_ID, &poll_key, CursorTask::Tally);
+        let mut cursor_data = vec![0u8; MAX_CURSOR_ACCOUNT_LEN];
+        let batch = |poll_data: &mut Vec<u8>,
+                     cursor_data: &mut Vec<u8>,
+                     cursor_exists: bool,
+                     ballots: &mut [(Pubkey, Vec<u8>)]| {
+            let (mut caller_data, mut system_data) = (vec![], vec![]);
+            let mut accounts = vec![
+                create_account_info(&poll_key, false, poll_data),
+                create_account_info(&caller_key, true, &mut caller_data),
+                system_account_info(&cursor_key, cursor_data),
+                system_account_info(&system_program::ID, &mut system_data),
+            ];
+            if cursor_exists {
+                accounts[2].owner = &PROGRAM_ID;
+            }
+            for (key, data) in ballots.iter_mut() {
+                accounts.push(create_account_info(key, false, data));
+            }
+            process(&accounts, &VotingInstruction::TallyBatch.try_to_vec().unwrap())
+        };
+
+        // The first batch creates the cursor; anyone can continue it.
+        assert!(batch(&mut poll.data, &mut cursor_data, false, &mut poll.receipts[..2]).is_ok());
+        let cursor = WorkCursor::deserialize(&mut &cursor_data[..]).unwrap();
+        assert_eq!(cursor.processed, 2);
+        assert!(!Poll::deserialize(&mut &poll.data[..]).unwrap().is_tallied);
+
+        // Ballots already counted, or passed out of order, are rejected.
+        for range in [1..3, 0..1] {
+            assert_eq!(
+                batch(&mut poll.data, &mut cursor_data, true, &mut poll.receipts[range]),
+                Err(VotingError::AccountMismatch.into())
+            );
+        }
+
+        // The batch that reaches turnout counts the first round, 2-2-1 with
+        // no majority, eliminates Zig and starts the next pass.
+        assert!(batch(&mut poll.data, &mut cursor_data, true, &mut poll.receipts[2..]).is_ok());
+        assert!(!Poll::deserialize(&mut &poll.data[..]).unwrap().is_tallied);
+        let cursor = WorkCursor::deserialize(&mut &cursor_data[..]).unwrap();
+        assert_eq!((cursor.processed, cursor.last_key), (0, None));
+        assert_eq!(cursor.rounds, TallyRounds::Runoff { eliminated: vec![2] });
+
+        // The second pass records the same winner as `Tally`.
+        assert!(batch(&mut poll.data, &mut cursor_data, true, &mut poll.receipts[..]).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert!(state.is_tallied);
+        assert_eq!(state.winner, Some(1));
+        assert_eq!(
+            batch(&mut poll.data, &mut cursor_data, true, &mut []),
+            Err(VotingError::AlreadyTallied.into())
+        );
+    }
+
+    #[test]
+    fn test_tally_batch_many_rankings() {
+        // 100 ballots each ranking all 32 options in a different order, more
+        // distinct rankings than a cursor account could list.
+        let options: Vec<String> = (0..MAX_OPTIONS).map(|option| option.to_string()).collect();
+        let options: Vec<&str> = options.iter().map(String::as_str).collect();
+        let rankings: Vec<Vec<u32>> = (0..100u32)
+            .map(|ballot| {
+                let (start, step) = (ballot % 32, 2 * (ballot / 32) + 1);
+                (0..32).map(|place| (start + place * step) % 32).collect()
+            })
+            .collect();
+        let (poll_key, caller_key) = (dummy_pubkey(2), dummy_pubkey(7));
+        let (cursor_key, _) = find_cursor_address(&PROGRAM_ID, &poll_key, CursorTask::Tally);
+
+        for seats in [1, 3] {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new()
+                .options(&options)
+                .poll_type(PollType::RankedChoice)
+                .seats(seats)
+                .account_data();
+            for (seed, ranking) in (10u8..).zip(&rankings) {
+                let rank = VotingInstruction::RankedVote {
+                    ranking: ranking.clone(),
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                };
+                assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &rank).is_ok());
+            }
+            let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+            state.is_closed = true;
+            state.serialize(&mut &mut poll.data[..]).unwrap();
+            poll.receipts.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+            // Batches of 25 ballots, passing every ballot once per round.
+            let mut cursor_data = vec![0u8; MAX_CURSOR_ACCOUNT_LEN];
+            let mut cursor_exists = false;
+            let mut passes = 0;
+            while !Poll::deserialize(&mut &poll.data[..]).unwrap().is_tallied {
+                for ballots in poll.receipts.chunks_mut(25) {
+                    let (mut caller_data, mut system_data) = (vec![], vec![]);
+                    let mut accounts = vec![
+                        create_account_info(&poll_key, false, &mut poll.data),
+                        create_account_info(&caller_key, true, &mut caller_data),
+                        system_account_info(&cursor_key, &mut cursor_data),
+                        system_account_info(&system_program::ID, &mut system_data),
+                    ];
+                    if cursor_exists {
+                        accounts[2].owner = &PROGRAM_ID;
+                    }
+                    for (key, data) in ballots.iter_mut() {
+                        accounts.push(create_account_info(key, false, data));
+                    }
+                    let batch = VotingInstruction::TallyBatch.try_to_vec().unwrap();
+                    assert_eq!(process(&accounts, &batch), Ok(()));
+                    cursor_exists = true;
+                }
+                passes += 1;
+                assert!(passes <= MAX_OPTIONS);
+            }
+
+            let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+            let groups: Vec<(&[u32], u64)> =
+                rankings.iter().map(|ranking| (ranking.as_slice(), 1)).collect();
+            if seats == 1 {
+                assert_eq!(state.winner, instant_runoff_grouped(MAX_OPTIONS, &[], &groups).winner);
+                assert!(state.winner.is_some());
+            } else {
+                let result = single_transferable_vote(MAX_OPTIONS, 3, &[], &groups);
+                assert_eq!(state.elected, result.elected);
+                assert_eq!(state.elected.len(), 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_finalize_poll() {
+        let poll_key = dummy_pubkey(2);
+        let finalize = |poll_data: &mut Vec<u8>| {
+            let accounts = [create_account_info(&poll_key, false, poll_data)];
+            process(&accounts, &VotingInstruction::FinalizePoll.try_to_vec().unwrap())
+        };
+        let outcome = |builder: PollBuilder| {
+            let mut poll_data = builder.account_data();
+            finalize(&mut poll_data)?;
+            Ok::<_, ProgramError>(Poll::deserialize(&mut &poll_data[..]).unwrap().outcome)
+        };
+
+        assert_eq!(
+            outcome(PollBuilder::new().vote_counts(&[3, 1])),
+            Err(VotingError::PollNotFinalized.into())
+        );
+        assert_eq!(
+            outcome(PollBuilder::new().vote_counts(&[3, 1]).closed()),
+            Ok(Some(PollOutcome::Passed))
+        );
+        // Quorum counts spoiled ballots and abstentions toward turnout.
+        let short = || PollBuilder::new().vote_counts(&[3, 1]).quorum(6).closed();
+        assert_eq!(outcome(short()), Ok(Some(PollOutcome::QuorumNotMet)));
+        assert_eq!(
+            outcome(short().spoiled_count(1).abstain_count(1)),
+            Ok(Some(PollOutcome::Passed))
+        );
+        // A tie for first, or a tie broken only by a withdrawn option, fails.
+        assert_eq!(
+            outcome(PollBuilder::new().vote_counts(&[2, 2]).closed()),
+            Ok(Some(PollOutcome::Failed))
+        );
+        let withdrawn = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .vote_counts(&[2, 2, 5])
+            .withdrawn_options(&[2])
+            .closed();
+        assert_eq!(outcome(withdrawn), Ok(Some(PollOutcome::Failed)));
+
+        // An approval threshold weighs the leader against votes for options
+        // only; 6667 admits exactly two-thirds.
+        let supermajority = |vote_counts: &[u64]| {
+            PollBuilder::new().vote_counts(vote_counts).approval_threshold_bps(6_667).closed()
+        };
+        assert_eq!(outcome(supermajority(&[4, 2])), Ok(Some(PollOutcome::Passed)));
+        assert_eq!(outcome(supermajority(&[3, 2])), Ok(Some(PollOutcome::Failed)));
+        assert_eq!(outcome(supermajority(&[4, 2]).abstain_count(4)), Ok(Some(PollOutcome::Passed)));
+
+        // Ties are listed and settled by the poll's tie-break policy.
+        let tie = |tie_break| {
+            let mut poll_data = PollBuilder::new()
+                .options(&["Rust", "Go", "Zig"])
+                .vote_counts(&[1, 4, 4])
+                .tie_break(tie_break)
+                .closed()
+                .account_data();
+            finalize(&mut poll_data).unwrap();
+            let state = Poll::deserialize(&mut &poll_data[..]).unwrap();
+            assert_eq!(state.tied_options, vec![1, 2]);
+            (state.winner, state.outcome.unwrap())
+        };
+        assert_eq!(tie(TieBreak::Fail), (None, PollOutcome::Failed));
+        assert_eq!(tie(TieBreak::EarliestOption), (Some(1), PollOutcome::Passed));
+        assert_eq!(tie(TieBreak::Rerun), (None, PollOutcome::Tied));
+
+        // Weighted polls measure quorum and the winner by weight.
+        let mut poll_data = PollBuilder::new()
+            .weight_mint(dummy_pubkey(6))
+            .vote_counts(&[3, 1])
+            .vote_weights(&[10, 50])
+            .quorum(60)
+            .closed()
+            .account_data();
+        assert!(finalize(&mut poll_data).is_ok());
+        let state = Poll::deserialize(&mut &poll_data[..]).unwrap();
+        assert_eq!(state.plurality_leader(), Some(1));
+        assert_eq!(state.outcome, Some(PollOutcome::Passed));
+        assert_eq!(finalize(&mut poll_data), Err(VotingError::AlreadyFinalized.into()));
+
+        // Ranked-choice polls are finalized from their tallied winner.
+        let ranked =
+            || PollBuilder::new().poll_type(PollType::RankedChoice).vote_counts(&[1, 1]).closed();
+        assert_eq!(outcome(ranked()), Err(VotingError::PollNotFinalized.into()));
+        let mut tallied = ranked().build();
+        tallied.is_tallied = true;
+        tallied.winner = Some(0);
+        let mut poll_data = tallied.try_to_vec().unwrap();
+        poll_data.resize(MAX_POLL_ACCOUNT_LEN, 0);
+        assert!(finalize(&mut poll_data).is_ok());
+        let state = Poll::deserialize(&mut &poll_data[..]).unwrap();
+        assert_eq!(state.outcome, Some(PollOutcome::Passed));
+    }
+
+    #[test]
+    fn test_withdraw_option() {
+        let candidate_key = dummy_pubkey(7);
+        let withdraw = VotingInstruction::WithdrawOption { option_index: 1 };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .vote_counts(&[1, 2, 0])
+            .candidates(&[None, Some(candidate_key)])
+            .account_data();
+
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(9), &withdraw),
+            Err(VotingError::Unauthorized.into())
+        );
+        // Option 2 has no candidate key, so nobody can withdraw it.
+        assert_eq!(
+            run_instruction(
+                &mut poll,
+                &candidate_key,
+                &VotingInstruction::WithdrawOption { option_index: 2 }
+            ),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(run_instruction(&mut poll, &candidate_key, &withdraw).is_ok());
+        assert_eq!(
+            run_instruction(&mut poll, &candidate_key, &withdraw),
+            Err(VotingError::OptionWithdrawn.into())
+        );
+
+        // Its votes are kept but reported apart, and it takes no new ones.
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 2, 0]);
+        assert_eq!(state.withdrawn_vote_count(), 2);
+        assert_eq!(
+            run_instruction(
+                &mut poll,
+                &dummy_pubkey(3),
+                &VotingInstruction::Vote {
+                    option_index: 1,
+                    accept_terms: false,
+                    eligibility_proof: Vec::new(),
+                }
+            ),
+            Err(VotingError::OptionWithdrawn.into())
+        );
+        assert!(run_instruction(
+            &mut poll,
+            &dummy_pubkey(3),
+            &VotingInstruction::Vote {
+                option_index: 2,
+                accept_terms: false,
+                eligibility_proof: Vec::new(),
+            }
+        )
+        .is_ok());
+        assert_eq!(
+            run_instruction(
+                &mut poll,
+                &dummy_pubkey(3),
+                &VotingInstruction::ChangeVote { new_option_index: 1 }
+            ),
+            Err(VotingError::OptionWithdrawn.into())
+        );
+
+        // Up to and including the last second that accepts votes, widened by
+        // the poll's clock tolerance, but not after.
+        for (end_time, tolerance, expected) in [
+            (TEST_TIME, 0, Ok(())),
+            (TEST_TIME - 1, 0, Err(VotingError::VotingNotActive.into())),
+            (TEST_TIME - 5, 5, Ok(())),
+            (TEST_TIME - 6, 5, Err(VotingError::VotingNotActive.into())),
+        ] {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new()
+                .candidates(&[None, Some(candidate_key)])
+                .window(TEST_TIME - 200, end_time)
+                .clock_tolerance(tolerance)
+                .account_data();
+            assert_eq!(
+                run_instruction(&mut poll, &candidate_key, &withdraw),
+                expected,
+                "withdrawing {}s after the end with {}s tolerance",
+                TEST_TIME - end_time,
+                tolerance
+            );
+        }
+    }
+
+    #[test]
+    fn test_spoil_ballot() {
+        let creator_key = dummy_pubkey(1);
+        let vote = |option_index| VotingInstruction::Vote {
+            option_index,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let spoil =
+            VotingInstruction::SpoilBallot { accept_terms: false, eligibility_proof: Vec::new() };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().creator(creator_key).max_spoiled_bps(5_000).account_data();
+
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &spoil).is_ok());
+        assert_eq!(poll.receipt_votes(), vec![(dummy_pubkey(3), SPOILED_BALLOT)]);
+        // A spoiled ballot is still a ballot.
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote(0)),
+            Err(VotingError::DoubleVote.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &spoil),
+            Err(VotingError::DoubleVote.into())
+        );
+
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &vote(0)).is_ok());
+        assert!(run_instruction(&mut poll, &dummy_pubkey(5), &spoil).is_ok());
+        // A spoiler can still change their mind while voting is open.
+        let change = VotingInstruction::ChangeVote { new_option_index: 1 };
+        assert!(run_instruction(&mut poll, &dummy_pubkey(5), &change).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.vote_counts.clone(), state.spoiled_count), (vec![1, 1], 1));
+        assert_eq!(state.turnout(), 3);
+
+        // At exactly the threshold the poll stands; above it, closing voids it.
+        for (spoiled_count, void) in [(2, false), (3, true)] {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new()
+                .creator(creator_key)
+                .vote_counts(&[1, 1])
+                .spoiled_count(spoiled_count)
+                .max_spoiled_bps(5_000)
+                .window(TEST_TIME - 200, TEST_TIME - 100)
+                .account_data();
+            let close = VotingInstruction::ClosePoll;
+            assert!(run_instruction(&mut poll, &creator_key, &close).is_ok());
+            let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+            assert_eq!(state.is_void, void);
+        }
+    }
+
+    #[test]
+    fn test_terms_acceptance() {
+        let vote = |option_index, accept_terms| VotingInstruction::Vote {
+            option_index,
+            accept_terms,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().terms_hash([7u8; 32]).account_data();
+
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote(0, false)),
+            Err(VotingError::TermsNotAccepted.into())
+        );
+        let abstain =
+            VotingInstruction::Abstain { accept_terms: false, eligibility_proof: Vec::new() };
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &abstain),
+            Err(VotingError::TermsNotAccepted.into())
+        );
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &vote(0, true)).is_ok());
+        let receipt: VoteReceipt = unpack_record(&poll.receipts[0].1).unwrap();
+        assert!(receipt.accepted_terms);
+
+        // Receipts from before the flag existed read as not accepted and can
+        // still be changed.
+        let mut poll = TestPoll::new();
+        let voter_key = dummy_pubkey(4);
+        let receipt_key = find_receipt_address(&PROGRAM_ID, &dummy_pubkey(2), &voter_key).0;
+        let mut legacy = VoteReceipt {
+            poll: dummy_pubkey(2),
+            voter: voter_key,
+            option_index: 0,
+            slot: 0,
+            accepted_terms: false,
+            delegate: Some(dummy_pubkey(5)),
+            delegated_ballots: 0,
+        }
+        .try_to_vec()
+        .unwrap();
+        legacy.truncate(VOTE_RECEIPT_LEN - 38);
+        poll.data = PollBuilder::new().vote_counts(&[1, 0]).account_data();
+        poll.receipts.push((receipt_key, legacy));
+        let change = VotingInstruction::ChangeVote { new_option_index: 1 };
+        assert!(run_instruction(&mut poll, &voter_key, &change).is_ok());
+        assert_eq!(poll.receipts[0].1.len(), VOTE_RECEIPT_LEN - 38);
+        let receipt: VoteReceipt = unpack_record(&poll.receipts[0].1).unwrap();
+        assert_eq!((receipt.option_index, receipt.accepted_terms), (1, false));
+        assert_eq!(receipt.delegate, None);
+    }
+
+    #[test]
+    fn test_abstain() {
+        let creator_key = dummy_pubkey(1);
+        let abstain =
+            VotingInstruction::Abstain { accept_terms: false, eligibility_proof: Vec::new() };
+        let spoil =
+            VotingInstruction::SpoilBallot { accept_terms: false, eligibility_proof: Vec::new() };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().creator(creator_key).max_spoiled_bps(5_000).account_data();
+
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &abstain).is_ok());
+        assert_eq!(poll.receipt_votes(), vec![(dummy_pubkey(3), ABSTAINED_BALLOT)]);
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &spoil),
+            Err(VotingError::DoubleVote.into())
+        );
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &abstain).is_ok());
+        assert!(run_instruction(&mut poll, &dummy_pubkey(5), &spoil).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.vote_counts.clone(), state.abstain_count), (vec![0, 0], 2));
+        // Abstentions count toward turnout, keeping the spoiled share under half.
+        assert_eq!(state.turnout(), 3);
+        assert!(!state.spoiled_share_exceeded());
+
+        // An abstainer can still change their mind while voting is open.
+        let change = VotingInstruction::ChangeVote { new_option_index: 1 };
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &change).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.vote_counts, state.abstain_count), (vec![0, 1], 1));
+
+        // Nobody casts a blank ballot for a voter who has not signed.
+        for blank in [&abstain, &spoil] {
+            assert_eq!(
+                run_instruction_as(&mut poll, &dummy_pubkey(6), false, blank),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+        }
+        assert!(poll.receipt_votes().iter().all(|(voter, _)| *voter != dummy_pubkey(6)));
+    }
+
+    #[test]
+    fn test_eligibility_root() {
+        let leaves: Vec<Hash> = (3..6).map(|seed| hash_leaf(dummy_pubkey(seed).as_ref())).collect();
+        let vote = |eligibility_proof: &[Hash]| VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: eligibility_proof.to_vec(),
+        };
+        let root = compute_root(&leaves).unwrap();
+        let mut poll = TestPoll::new();
+        poll.data =
+            PollBuilder::new().eligibility(EligibilityRule::Allowlist { root }).account_data();
+
+        let proof = [leaves[1], leaves[2]];
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &vote(&proof)).is_ok());
+        // The proof must be for the signing voter.
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(4), &vote(&proof)),
+            Err(VotingError::NotEligible.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(6), &vote(&[])),
+            Err(VotingError::NotEligible.into())
+        );
+
+        // Blank ballots count toward turnout, so they need a proof as well.
+        let abstain = |eligibility_proof: &[Hash]| VotingInstruction::Abstain {
+            accept_terms: false,
+            eligibility_proof: eligibility_proof.to_vec(),
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(5), &abstain(&[])),
+            Err(VotingError::NotEligible.into())
+        );
+        let proof = [compute_root(&leaves[..2]).unwrap()];
+        assert!(run_instruction(&mut poll, &dummy_pubkey(5), &abstain(&proof)).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.vote_counts, state.abstain_count), (vec![1, 0], 1));
+    }
+
+    #[test]
+    fn test_vote_multi() {
+        let multi = |option_indices: &[u32]| VotingInstruction::VoteMulti {
+            option_indices: option_indices.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig", "C"])
+            .max_selections(2)
+            .withdrawn_options(&[3])
+            .account_data();
+
+        for (selection, error) in [
+            (&[][..], VotingError::InvalidOption),
+            (&[0, 1, 2][..], VotingError::TooManySelections),
+            (&[1, 1][..], VotingError::InvalidOption),
+            (&[0, 4][..], VotingError::InvalidOption),
+            (&[0, 3][..], VotingError::OptionWithdrawn),
+        ] {
+            assert_eq!(
+                run_instruction(&mut poll, &dummy_pubkey(3), &multi(selection)),
+                Err(error.into())
+            );
+        }
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &multi(&[2, 0])).is_ok());
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &multi(&[0])).is_ok());
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(4), &multi(&[1])),
+            Err(VotingError::DoubleVote.into())
+        );
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 0, 1, 0]);
+        let ballot = MultiBallot::try_from_slice(&poll.receipts[0].1).unwrap();
+        assert_eq!(ballot.selections, vec![2, 0]);
+
+        // Single-choice instructions do not apply, and vice versa.
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(5), &vote),
+            Err(VotingError::WrongPollType.into())
+        );
+        let mut single = TestPoll::new();
+        assert_eq!(
+            run_instruction(&mut single, &dummy_pubkey(5), &multi(&[0])),
+            Err(VotingError::WrongPollType.into())
+        );
+    }
+
+    #[test]
+    fn test_vote_cumulative() {
+        let cumulative = |allocations: &[(u32, u64)]| VotingInstruction::VoteCumulative {
+            allocations: allocations.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig", "C"])
+            .points_per_voter(10)
+            .withdrawn_options(&[3])
+            .account_data();
+
+        for (allocation, error) in [
+            (&[][..], VotingError::InvalidOption),
+            (&[(0, 7), (1, 4)][..], VotingError::PointBudgetExceeded),
+            (&[(0, 5), (0, 5)][..], VotingError::InvalidOption),
+            (&[(0, 10), (1, 0)][..], VotingError::InvalidOption),
+            (&[(4, 1)][..], VotingError::InvalidOption),
+            (&[(3, 1)][..], VotingError::OptionWithdrawn),
+            (&[(0, u64::MAX), (1, 1)][..], VotingError::Overflow),
+        ] {
+            assert_eq!(
+                run_instruction(&mut poll, &dummy_pubkey(3), &cumulative(allocation)),
+                Err(error.into())
+            );
+        }
+        // Voters need not spend every point.
+        let ballot = cumulative(&[(0, 7), (1, 3)]);
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &ballot).is_ok());
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &cumulative(&[(1, 6)])).is_ok());
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(4), &cumulative(&[(2, 1)])),
+            Err(VotingError::DoubleVote.into())
+        );
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 2, 0, 0]);
+        assert_eq!(state.vote_weights, vec![7, 9, 0, 0]);
+        assert_eq!((state.participation(), state.leading_options()), (16, vec![1]));
+        let ballot = CumulativeBallot::try_from_slice(&poll.receipts[0].1).unwrap();
+        assert_eq!(ballot.allocations, vec![(0, 7), (1, 3)]);
+
+        // Single-choice instructions do not apply, and vice versa.
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(5), &vote),
+            Err(VotingError::WrongPollType.into())
+        );
+        let mut single = TestPoll::new();
+        assert_eq!(
+            run_instruction(&mut single, &dummy_pubkey(5), &cumulative(&[(0, 1)])),
+            Err(VotingError::WrongPollType.into())
+        );
+    }
+
+    #[test]
+    fn test_budget_vote() {
+        let allocate = |allocations: &[(u32, u64)]| VotingInstruction::VoteCumulative {
+            allocations: allocations.to_vec(),
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .options(&["Grants", "Audits", "Events"])
+            .poll_type(PollType::Budget)
+            .points_per_voter(3)
+            .account_data();
+
+        // Every ballot allocates the whole budget.
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &allocate(&[(0, 1), (1, 1)])),
+            Err(VotingError::BudgetNotAllocated.into())
+        );
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &allocate(&[(0, 2), (1, 2)])),
+            Err(VotingError::PointBudgetExceeded.into())
+        );
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(3), &vote),
+            Err(VotingError::WrongPollType.into())
+        );
+
+        // Whole budgets, thirds and a two-to-one split average out to four
+        // ninths, three ninths and two ninths, rounded to basis points that
+        // add up to the whole budget.
+        for (seed, ballot) in
+            (3u8..).zip([&[(0, 3)][..], &[(0, 1), (1, 1), (2, 1)], &[(1, 2), (2, 1)]])
+        {
+            assert!(run_instruction(&mut poll, &dummy_pubkey(seed), &allocate(ballot)).is_ok());
+        }
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 2, 2]);
+        assert_eq!(state.vote_weights, vec![13_333_333_334, 10_000_000_000, 6_666_666_666]);
+        assert_eq!(
+            state.budget_allocation(),
+            BudgetAllocation { ballots: 3, allocations_bps: vec![4_445, 3_333, 2_222] }
+        );
+        assert_eq!(state.participation(), 3);
+        state.is_closed = true;
+        assert_eq!(state.finalize(), PollOutcome::Passed);
+        assert_eq!(state.winner, None);
+    }
+
+    #[test]
+    fn test_blackout_holds_back_tallies() {
+        let creator_key = dummy_pubkey(1);
+        let vote = |option_index| VotingInstruction::Vote {
+            option_index,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .creator(creator_key)
+            .window(TEST_TIME - 100, TEST_TIME + 100)
+            .blackout_secs(50)
+            .vote_counts(&[1, 0])
+            .account_data();
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert!(!state.in_blackout(TEST_TIME) && state.in_blackout(TEST_TIME + 50));
+
+        // Ballots cast in the blackout, including changes, stay out of the counts.
+        state.blackout_secs = 100;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &vote(1)).is_ok());
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &vote(1)).is_ok());
+        let change = VotingInstruction::ChangeVote { new_option_index: 0 };
+        assert!(run_instruction(&mut poll, &dummy_pubkey(4), &change).is_ok());
+        let mut state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 0]);
+        assert_eq!(state.pending_counts, vec![1, 1]);
+
+        // Closing folds them in.
+        state.end_time = TEST_TIME - 1;
+        state.serialize(&mut &mut poll.data[..]).unwrap();
+        assert!(run_instruction(&mut poll, &creator_key, &VotingInstruction::ClosePoll).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 1]);
+        assert!(state.pending_counts.is_empty());
+    }
+
+    #[test]
+    fn test_token_weighted_vote() {
+        let voter_key = dummy_pubkey(3);
+        let poll_key = dummy_pubkey(2);
+        let mint_key = dummy_pubkey(40);
+        let token_key = dummy_pubkey(41);
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+        let big_balance = u64::MAX - 1;
+
+        let mut poll_data =
+            PollBuilder::new().weight_mint(mint_key).vote_counts(&[1, 0]).account_data();
+        let mut state = Poll::deserialize(&mut &poll_data[..]).unwrap();
+        state.vote_weights = vec![u128::from(u64::MAX), 0];
+        state.serialize(&mut &mut poll_data[..]).unwrap();
+
+        let mut voter_data = vec![];
+        let mut clock_data = vec![];
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+        let mut token_data = token_account_data(&mint_key, &voter_key, big_balance);
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+            account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut token_data),
+        ];
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+
+        // The token account must belong to the token program, the voter and the mint.
+        accounts[5].owner = &PROGRAM_ID;
+        assert_eq!(process(accounts, &vote), Err(ProgramError::IncorrectProgramId));
+        accounts[5].owner = &TOKEN_PROGRAM_ID;
+        accounts[5].data.borrow_mut()[0] ^= 1;
+        assert_eq!(process(accounts, &vote), Err(VotingError::AccountMismatch.into()));
+        accounts[5].data.borrow_mut()[0] ^= 1;
+
+        // Weights sum past u64 without overflowing.
+        assert!(process(accounts, &vote).is_ok());
+        let state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![2, 0]);
+        assert_eq!(state.vote_weights, vec![u128::from(u64::MAX) + u128::from(big_balance), 0]);
+
+        // An empty balance carries no vote.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().weight_mint(mint_key).account_data();
+        let mut empty_data = token_account_data(&mint_key, &voter_key, 0);
+        accounts[0] = create_account_info(&poll_key, false, &mut poll.data);
+        accounts[5] = account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut empty_data);
+        assert_eq!(process(accounts, &vote), Err(VotingError::NoVotingWeight.into()));
+
+        // The poll's curve caps the balance and takes its square root.
+        let curve = WeightCurve {
+            min_weight: 0,
+            max_weight: Some(10_000),
+            transform: WeightTransform::SquareRoot,
+        };
+        let mut poll_data =
+            PollBuilder::new().weight_mint(mint_key).weight_curve(curve).account_data();
+        let mut token_data = token_account_data(&mint_key, &voter_key, big_balance);
+        accounts[0] = create_account_info(&poll_key, false, &mut poll_data);
+        accounts[5] = account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut token_data);
+        assert!(process(accounts, &vote).is_ok());
+        let state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!((state.vote_counts, state.vote_weights), (vec![1, 0], vec![100, 0]));
+    }
+
+    #[test]
+    fn test_token_gated_vote() {
+        let voter_key = dummy_pubkey(3);
+        let poll_key = dummy_pubkey(2);
+        let (mint_key, token_key) = (dummy_pubkey(40), dummy_pubkey(41));
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+
+        let mut poll_data = PollBuilder::new()
+            .eligibility(EligibilityRule::TokenHolder { mint: mint_key, min_amount: 100 })
+            .account_data();
+        let mut voter_data = vec![];
+        let mut clock_data = vec![];
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+        let mut token_data = token_account_data(&mint_key, &voter_key, 99);
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+            account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut token_data),
+        ];
+        let vote = VotingInstruction::Vote {
+            option_index: 1,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+
+        assert_eq!(process(&accounts[..5], &vote), Err(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(process(accounts, &vote), Err(VotingError::InsufficientTokenBalance.into()));
+        let mut enough_data = token_account_data(&mint_key, &voter_key, 100);
+        accounts[5] = account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut enough_data);
+        assert!(process(accounts, &vote).is_ok());
+        let state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_nft_gated_vote() {
+        let voter_key = dummy_pubkey(3);
+        let poll_key = dummy_pubkey(2);
+        let (nft_mint, token_key) = (dummy_pubkey(40), dummy_pubkey(41));
+        let collection = dummy_pubkey(42);
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+
+        let mut poll_data = PollBuilder::new()
+            .eligibility(EligibilityRule::NftCollection { collection })
+            .account_data();
+        let mut voter_data = vec![];
+        let mut clock_data = vec![];
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+        let mut token_data = token_account_data(&nft_mint, &voter_key, 1);
+        let mut metadata_data = nft_metadata_data(&nft_mint, &dummy_pubkey(43), true);
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+            account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut token_data),
+            account_info(&nft_mint, &METADATA_PROGRAM_ID, false, &mut metadata_data),
+        ];
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+
+        // The holder accounts are required, and the NFT must be in the collection.
+        assert_eq!(process(&accounts[..5], &vote), Err(ProgramError::NotEnoughAccountKeys));
+        assert_eq!(process(accounts, &vote), Err(VotingError::NotEligible.into()));
+        let mut metadata_data = nft_metadata_data(&nft_mint, &collection, true);
+        accounts[6] = account_info(&nft_mint, &METADATA_PROGRAM_ID, false, &mut metadata_data);
+        assert!(process(accounts, &vote).is_ok());
+        let state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_register_voter() {
+        let voter_key = dummy_pubkey(3);
+        let (record_key, _) = find_voter_record_address(&PROGRAM_ID, &voter_key);
+        let register = VotingInstruction::RegisterVoter { delegate: Some(dummy_pubkey(4)) }
+            .try_to_vec()
+            .unwrap();
+
+        let mut record_data = vec![0u8; VOTER_RECORD_LEN];
+        let mut voter_data = vec![];
+        let mut clock_data = vec![];
+        let mut system_data = vec![];
+        let accounts = &mut [
+            system_account_info(&record_key, &mut record_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&system_program::ID, &mut system_data),
+        ];
+        assert!(process(accounts, &register).is_ok());
+        let record = VoterRecord::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(
+            record,
+            VoterRecord {
+                voter: voter_key,
+                registered_at: TEST_TIME,
+                registered_slot: TEST_SLOT,
+                polls_participated: 0,
+                delegate: Some(dummy_pubkey(4)),
+            }
+        );
+
+        // A wallet registers once, at its own address.
+        accounts[0].owner = &PROGRAM_ID;
+        assert_eq!(process(accounts, &register), Err(VotingError::AlreadyRegistered.into()));
+        let other_key = dummy_pubkey(5);
+        accounts[1].key = &other_key;
+        assert_eq!(process(accounts, &register), Err(VotingError::AccountMismatch.into()));
+        let mut record_data = accounts[0].data.borrow().to_vec();
+
+        // A poll for registered voters reads the record and counts the ballot
+        // toward the voter's participation.
+        let poll_key = dummy_pubkey(2);
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+        let mut poll_data =
+            PollBuilder::new().eligibility(EligibilityRule::Registered).account_data();
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+            system_account_info(&record_key, &mut record_data),
+        ];
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(process(accounts, &vote), Err(VotingError::NotEligible.into()));
+        accounts[5].owner = &PROGRAM_ID;
+        assert!(process(accounts, &vote).is_ok());
+        let record = VoterRecord::deserialize(&mut &accounts[5].data.borrow()[..]).unwrap();
+        assert_eq!(record.polls_participated, 1);
+    }
+
+    #[test]
+    fn test_delegated_vote() {
+        let (voter_key, delegate_key) = (dummy_pubkey(3), dummy_pubkey(4));
+        let (record_key, _) = find_voter_record_address(&PROGRAM_ID, &voter_key);
+        let set_delegate =
+            |delegate: Pubkey| VotingInstruction::SetDelegate { delegate }.try_to_vec().unwrap();
+        let revoke = VotingInstruction::RevokeDelegate.try_to_vec().unwrap();
+
+        let mut record_data = VoterRecord {
+            voter: voter_key,
+            registered_at: 0,
+            registered_slot: 0,
+            polls_participated: 0,
+            delegate: None,
+        }
+        .try_to_vec()
+        .unwrap();
+        record_data.resize(VOTER_RECORD_LEN, 0);
+        let mut voter_data = vec![];
+        let accounts = &mut [
+            create_account_info(&record_key, false, &mut record_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+        ];
+        assert_eq!(
+            process(accounts, &set_delegate(voter_key)),
+            Err(VotingError::InvalidDelegate.into())
+        );
+        assert!(process(accounts, &set_delegate(delegate_key)).is_ok());
+        let record = VoterRecord::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(record.delegate, Some(delegate_key));
+        let mut record_data = accounts[0].data.borrow().to_vec();
+
+        // The delegate signs for the unsigned voter, after every other account.
+        let poll_key = dummy_pubkey(2);
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+        let mut poll_data = PollBuilder::new().account_data();
+        let mut clock_data = vec![];
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+        let mut delegate_data = vec![];
+        let outsider_key = dummy_pubkey(9);
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&voter_key, false, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+            create_account_info(&record_key, false, &mut record_data),
+            create_account_info(&outsider_key, true, &mut delegate_data),
+        ];
+        let vote = VotingInstruction::Vote {
+            option_index: 1,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(process(&accounts[..5], &vote), Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(process(accounts, &vote), Err(VotingError::Unauthorized.into()));
+        accounts[6].key = &delegate_key;
+        assert!(process(accounts, &vote).is_ok());
+        let receipt = VoteReceipt::try_from_slice(&accounts[3].data.borrow()).unwrap();
+        assert_eq!((receipt.voter, receipt.delegate), (voter_key, Some(delegate_key)));
+        let state = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![0, 1]);
+
+        // Once revoked, the delegate can no longer vote for the voter.
+        let mut voter_data = vec![];
+        let mut record_data = accounts[5].data.borrow().to_vec();
+        let revoke_accounts = &mut [
+            create_account_info(&record_key, false, &mut record_data),
+            create_account_info(&voter_key, true, &mut voter_data),
+        ];
+        assert!(process(revoke_accounts, &revoke).is_ok());
+        let mut poll_data = PollBuilder::new().account_data();
+        accounts[0] = create_account_info(&poll_key, false, &mut poll_data);
+        accounts[5] = create_account_info(&record_key, false, &mut record_data);
+        assert_eq!(process(accounts, &vote), Err(VotingError::Unauthorized.into()));
+    }
+
+    #[test]
+    fn test_transitive_delegation() {
+        let poll_key = dummy_pubkey(2);
+        let voter_key = dummy_pubkey(3);
+        let (a, b, d, e) = (dummy_pubkey(5), dummy_pubkey(6), dummy_pubkey(7), dummy_pubkey(8));
+        let (mint_key, token_key) = (dummy_pubkey(40), dummy_pubkey(41));
+        // Votes for option 1 as `voter_key` on `poll`, passing `delegators` as
+        // (delegator, delegate, voted), with the voter's token account on
+        // weighted polls and record on `Registered` ones. Returns the result,
+        // the vote counts, the delegate recorded on each delegator's receipt,
+        // the polls each delegator's record counts and the delegated ballots
+        // the voter's receipt carries.
+        let vote = |poll: PollBuilder, delegators: &[(Pubkey, Pubkey, bool)]| {
+            let mut poll_data = poll.account_data();
+            let state = poll.build();
+            let mut voter_data = vec![];
+            let (voter_record_key, _) = find_voter_record_address(&PROGRAM_ID, &voter_key);
+            let mut token_data = token_account_data(&mint_key, &voter_key, 10);
+            let mut voter_record = VoterRecord {
+                voter: voter_key,
+                registered_at: 0,
+                registered_slot: 0,
+                polls_participated: 0,
+                delegate: None,
+            }
+            .try_to_vec()
+            .unwrap();
+            let mut clock_data = vec![];
+            let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+            let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+            let mut system_data = vec![];
+            let keys: Vec<(Pubkey, Pubkey)> = delegators
+                .iter()
+                .map(|(delegator, ..)| {
+                    (
+                        find_voter_record_address(&PROGRAM_ID, delegator).0,
+                        find_receipt_address(&PROGRAM_ID, &poll_key, delegator).0,
+                    )
+                })
+                .collect();
+            let mut data: Vec<(Vec<u8>, Vec<u8>)> = delegators
+                .iter()
+                .map(|&(delegator, delegate, _)| {
+                    let record = VoterRecord {
+                        voter: delegator,
+                        registered_at: 0,
+                        registered_slot: 0,
+                        polls_participated: 0,
+                        delegate: Some(delegate),
+                    };
+                    (record.try_to_vec().unwrap(), vec![0u8; VOTE_RECEIPT_LEN])
+                })
+                .collect();
+
+            let mut accounts = vec![
+                create_account_info(&poll_key, false, &mut poll_data),
+                create_account_info(&voter_key, true, &mut voter_data),
+                create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+                system_account_info(&receipt_key, &mut receipt_data),
+                system_account_info(&system_program::ID, &mut system_data),
+            ];
+            if state.weight_mint.is_some() {
+                accounts.push(account_info(&token_key, &TOKEN_PROGRAM_ID, false, &mut token_data));
+            }
+            if state.eligibility == EligibilityRule::Registered {
+                accounts.push(create_account_info(&voter_record_key, false, &mut voter_record));
+            }
+            for (((record_key, receipt_key), (record_data, receipt_data)), &(.., voted)) in
+                keys.iter().zip(data.iter_mut()).zip(delegators)
+            {
+                accounts.push(create_account_info(record_key, false, record_data));
+                let owner = if voted { &PROGRAM_ID } else { &system_program::ID };
+                accounts.push(account_info(receipt_key, owner, false, receipt_data));
+            }
+            let instruction = VotingInstruction::Vote {
+                option_index: 1,
+                accept_terms: state.terms_hash.is_some(),
+                eligibility_proof: Vec::new(),
+            }
+            .try_to_vec()
+            .unwrap();
+            let result = process(&accounts, &instruction);
+            let poll = Poll::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+            drop(accounts);
+            let carried = unpack_record::<VoteReceipt>(&receipt_data).unwrap().delegated_ballots;
+            let receipts: Vec<Option<Pubkey>> = data
+                .iter()
+                .map(|(_, receipt)| unpack_record::<VoteReceipt>(receipt).unwrap().delegate)
+                .collect();
+            let participated: Vec<u64> = data
+                .iter()
+                .map(|(record, _)| VoterRecord::try_from_slice(record).unwrap().polls_participated)
+                .collect();
+            (result, poll.vote_counts, receipts, participated, carried)
+        };
+        let depth = |depth: u8| PollBuilder::new().max_delegation_depth(depth);
+
+        // A -> B -> voter and D -> voter all ride on the vote, in any order.
+        let (result, counts, receipts, _, carried) =
+            vote(depth(2), &[(a, b, false), (b, voter_key, false), (d, voter_key, false)]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(counts, vec![0, 4]);
+        assert_eq!(receipts, vec![Some(voter_key); 3]);
+        assert_eq!(carried, 3);
+
+        // B voted directly, so neither B nor A, whose ballot follows B's, is cast.
+        let (result, counts, receipts, _, carried) =
+            vote(depth(2), &[(a, b, false), (b, voter_key, true), (d, voter_key, false)]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(counts, vec![0, 2]);
+        assert_eq!(receipts, vec![None, None, Some(voter_key)]);
+        assert_eq!(carried, 1);
+
+        // E -> A -> B -> voter takes three steps.
+        let chain = [(e, a, false), (a, b, false), (b, voter_key, false)];
+        assert_eq!(vote(depth(2), &chain).0, Err(VotingError::InvalidDelegationChain.into()));
+        assert_eq!(vote(depth(3), &chain).1, vec![0, 4]);
+
+        // Loops and chains through delegators not passed are rejected.
+        for delegators in [vec![(a, b, false), (b, a, false)], vec![(a, b, false)]] {
+            assert_eq!(
+                vote(depth(2), &delegators).0,
+                Err(VotingError::InvalidDelegationChain.into())
+            );
+        }
+        assert_eq!(
+            vote(depth(0), &[(d, voter_key, false)]).0,
+            Err(VotingError::WrongPollType.into())
+        );
+
+        // Delegators pass no weight, terms or proof of their own, so polls
+        // needing them reject delegated ballots even if created without the
+        // checks `CreatePoll` makes.
+        let root = hash_leaf(voter_key.as_ref());
+        for poll in [
+            depth(2).weight_mint(mint_key),
+            depth(2).terms_hash([9u8; 32]),
+            depth(2).eligibility(EligibilityRule::Allowlist { root }),
+        ] {
+            let result = vote(poll, &[(d, voter_key, false)]).0;
+            assert_eq!(result, Err(VotingError::WrongPollType.into()));
+        }
+
+        // On `Registered` polls each delegated ballot counts towards its
+        // delegator's participation, as a direct vote would.
+        let registered = depth(2).eligibility(EligibilityRule::Registered);
+        let (result, counts, _, participated, _) =
+            vote(registered, &[(a, voter_key, false), (d, voter_key, false)]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(counts, vec![0, 3]);
+        assert_eq!(participated, vec![1, 1]);
+
+        // A vote that carried delegated ballots stays put, since their
+        // receipts would be left on the old option. Each delegator can still
+        // move their own ballot.
+        let receipt = |voter, delegate, delegated_ballots| {
+            let receipt = VoteReceipt {
+                poll: poll_key,
+                voter,
+                option_index: 1,
+                slot: 0,
+                accepted_terms: false,
+                delegate,
+                delegated_ballots,
+            };
+            let mut data = receipt.try_to_vec().unwrap();
+            data.resize(VOTE_RECEIPT_LEN, 0);
+            (find_receipt_address(&PROGRAM_ID, &poll_key, &voter).0, data)
+        };
+        let mut poll = TestPoll::new();
+        poll.data = depth(2).vote_counts(&[0, 2]).account_data();
+        poll.receipts.push(receipt(voter_key, None, 1));
+        poll.receipts.push(receipt(d, Some(voter_key), 0));
+        let change = VotingInstruction::ChangeVote { new_option_index: 0 };
+        assert_eq!(
+            run_instruction(&mut poll, &voter_key, &change),
+            Err(VotingError::DelegatedVoteLocked.into())
+        );
+        assert!(run_instruction(&mut poll, &d, &change).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.vote_counts, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_submit_attestation() {
+        let attestor_key = dummy_pubkey(6);
+        let poll_key = dummy_pubkey(2);
+        let (record_key, _) = find_attestation_address(&PROGRAM_ID, &poll_key, &attestor_key);
+        let builder = || PollBuilder::new().attestors(&[attestor_key]).vote_counts(&[3, 1]);
+        let submit = VotingInstruction::SubmitAttestation { signature: [9u8; 64] };
+
+        let mut poll_data = builder().closed_at_slot(77).account_data();
+        let mut attestor_data = vec![];
+        let mut record_data = vec![0u8; ATTESTATION_RECORD_LEN];
+        let mut system_data = vec![];
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&attestor_key, true, &mut attestor_data),
+            system_account_info(&record_key, &mut record_data),
+            system_account_info(&system_program::ID, &mut system_data),
+        ];
+        assert!(process(accounts, &submit.try_to_vec().unwrap()).is_ok());
+
+        let record = AttestationRecord::try_from_slice(&accounts[2].data.borrow()).unwrap();
+        let poll = builder().build();
+        assert_eq!(
+            record,
+            AttestationRecord {
+                attestation: ResultAttestation::new(&PROGRAM_ID, &poll_key, &poll, 77),
+                attestor: attestor_key,
+                signature: [9u8; 64],
+            }
+        );
+
+        // One record per attestor.
+        accounts[2].owner = &PROGRAM_ID;
+        assert_eq!(
+            process(accounts, &submit.try_to_vec().unwrap()),
+            Err(VotingError::AlreadyAttested.into())
+        );
+        accounts[2].owner = &system_program::ID;
+
+        // Only named attestors, and only after the poll is closed.
+        let outsider_key = dummy_pubkey(9);
+        accounts[1].key = &outsider_key;
+        assert_eq!(
+            process(accounts, &submit.try_to_vec().unwrap()),
+            Err(VotingError::Unauthorized.into())
+        );
+        accounts[1].key = &attestor_key;
+        accounts[0].data.borrow_mut().copy_from_slice(&builder().account_data());
+        assert_eq!(
+            process(accounts, &submit.try_to_vec().unwrap()),
+            Err(VotingError::PollNotFinalized.into())
+        );
+    }
+
+    #[test]
+    fn test_snapshot_result() {
+        let creator_key = dummy_pubkey(1);
+        let poll_key = dummy_pubkey(2);
+        let (snapshot_key, _) = find_snapshot_address(&PROGRAM_ID, &poll_key, "midpoint");
+        let snapshot = |label: &str| {
+            VotingInstruction::SnapshotResult { label: label.to_string() }.try_to_vec().unwrap()
+        };
+
+        let builder = || PollBuilder::new().creator(creator_key).vote_counts(&[3, 1]);
+        let mut poll_data = builder().spoiled_count(1).account_data();
+        let expected = ResultSnapshot::new(
+            &poll_key,
+            &builder().spoiled_count(1).build(),
+            "midpoint".to_string(),
+            TEST_SLOT,
+            TEST_TIME,
+        );
+        let mut creator_data = vec![];
+        let mut clock_data = vec![];
+        let mut snapshot_data = vec![0u8; expected.try_to_vec().unwrap().len()];
+        let mut system_data = vec![];
+        let accounts = &mut [
+            create_account_info(&poll_key, false, &mut poll_data),
+            create_account_info(&creator_key, true, &mut creator_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&snapshot_key, &mut snapshot_data),
+            system_account_info(&system_program::ID, &mut system_data),
+        ];
+
+        // Labels pick the address, and are address seeds, so short.
+        assert_eq!(
+            process(accounts, &snapshot("24h before close")),
+            Err(VotingError::AccountMismatch.into())
+        );
+        assert_eq!(
+            process(accounts, &snapshot(&"x".repeat(MAX_SNAPSHOT_LABEL_LEN + 1))),
+            Err(VotingError::PollTooLarge.into())
+        );
+        assert!(process(accounts, &snapshot("midpoint")).is_ok());
+        let stored = ResultSnapshot::try_from_slice(&accounts[3].data.borrow()).unwrap();
+        assert_eq!(stored, expected);
+
+        // A label is taken once.
+        accounts[3].owner = &PROGRAM_ID;
+        assert_eq!(
+            process(accounts, &snapshot("midpoint")),
+            Err(VotingError::SnapshotExists.into())
+        );
+        accounts[3].owner = &system_program::ID;
+
+        // Only the creator takes snapshots.
+        let outsider_key = dummy_pubkey(9);
+        accounts[1].key = &outsider_key;
+        assert_eq!(process(accounts, &snapshot("midpoint")), Err(VotingError::Unauthorized.into()));
+    }
+
+    #[test]
+    fn test_extend_poll() {
+        let creator_key = dummy_pubkey(1);
+        let extend = |new_end_time| VotingInstruction::ExtendPoll { new_end_time };
+        let open = || {
+            let mut poll = TestPoll::new();
+            poll.data = PollBuilder::new()
+                .creator(creator_key)
+                .window(TEST_TIME - 100, TEST_TIME + 100)
+                .account_data();
+            poll
+        };
+
+        let mut poll = open();
+        assert_eq!(
+            run_instruction(&mut poll, &dummy_pubkey(9), &extend(TEST_TIME + 500)),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(run_instruction(&mut poll, &creator_key, &extend(TEST_TIME + 500)).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!((state.start_time, state.end_time), (TEST_TIME - 100, TEST_TIME + 500));
+
+        // Never shorter, and never past the maximum duration.
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &extend(TEST_TIME + 200)),
+            Err(VotingError::InvalidVotingWindow.into())
+        );
+        let too_long = TEST_TIME - 100 + MAX_POLL_DURATION + 1;
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &extend(too_long)),
+            Err(VotingError::InvalidVotingWindow.into())
+        );
+
+        // A window that has ended cannot be reopened.
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new()
+            .creator(creator_key)
+            .window(TEST_TIME - 200, TEST_TIME - 100)
+            .account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &extend(TEST_TIME + 500)),
+            Err(VotingError::VotingNotActive.into())
+        );
+
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().creator(creator_key).closed().account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &creator_key, &extend(TEST_TIME + 500)),
+            Err(VotingError::PollClosed.into())
+        );
+    }
+
+    #[test]
+    fn test_add_option() {
+        let proposer_key = dummy_pubkey(5);
+        let add = |label: &str| VotingInstruction::AddOption { label: label.to_string() };
+
+        let mut poll = TestPoll::new();
+        poll.data = PollBuilder::new().account_data();
+        assert_eq!(
+            run_instruction(&mut poll, &proposer_key, &add("Zig")),
+            Err(VotingError::NewOptionsNotAllowed.into())
+        );
+
+        poll.data = PollBuilder::new().allow_new_options().vote_counts(&[2, 1]).account_data();
+        assert!(run_instruction(&mut poll, &proposer_key, &add("Zig")).is_ok());
+        let state = Poll::deserialize(&mut &poll.data[..]).unwrap();
+        assert_eq!(state.options, vec!["Rust", "Go", "Zig"]);
+        assert_eq!(state.vote_counts, vec![2, 1, 0]);
+
+        // The new option can be voted for straight away.
+        let vote = VotingInstruction::Vote {
+            option_index: 2,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        assert!(run_instruction(&mut poll, &dummy_pubkey(3), &vote).is_ok());
+
+        assert_eq!(
+            run_instruction(&mut poll, &proposer_key, &add("Go")),
+            Err(VotingError::DuplicateOption.into())
+        );
+        let long_label = "x".repeat(MAX_OPTION_LEN + 1);
+        assert_eq!(
+            run_instruction(&mut poll, &proposer_key, &add(&long_label)),
+            Err(VotingError::PollTooLarge.into())
+        );
+
+        // The account must have room for the grown poll.
+        let builder = PollBuilder::new().allow_new_options();
+        let exact_len = builder.build().try_to_vec().unwrap().len();
+        poll.data = PollBuilder::new().allow_new_options().account_data_with_len(exact_len);
+        assert_eq!(
+            run_instruction(&mut poll, &proposer_key, &add("Zig")),
+            Err(VotingError::PollTooLarge.into())
+        );
+    }
+
+    #[test]
+    fn test_get_version() {
+        let instruction_data = VotingInstruction::GetVersion.try_to_vec().unwrap();
+        assert!(process(&[], &instruction_data).is_ok());
+
+        let (_, data) = arch_program::program::get_return_data().unwrap();
+        let version = ProgramVersion::try_from_slice(&data).unwrap();
+        assert_eq!(version, ProgramVersion::current());
+        assert_eq!((version.major, version.minor, version.patch), (0, 1, 0));
+        assert_eq!(version.accounts_schema, crate::instruction::ACCOUNTS_SCHEMA_VERSION);
+        assert!(version.supports(FEATURE_PDA_POLLS | FEATURE_VOTE_RECEIPTS));
+        assert!(!version.supports(1 << 63));
+    }
+
+    #[test]
+    fn test_read_only_mode() {
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        };
+        let (admin_key, payer_key) = (dummy_pubkey(7), dummy_pubkey(4));
+        let read_only =
+            ProgramConfig { admin: admin_key, read_only_mode: true, ..ProgramConfig::default() };
+        let mut admin_data = vec![];
+        let admin = [create_account_info(&admin_key, true, &mut admin_data)];
+        for instruction in [&vote, &VotingInstruction::FinalizePoll, &VotingInstruction::Tally] {
+            assert!(!instruction.is_admin());
+            assert!(ensure_runs(instruction, &ProgramConfig::default(), &[]).is_ok());
+            assert_eq!(
+                ensure_runs(instruction, &read_only, &admin),
+                Err(VotingError::ReadOnlyMode.into())
+            );
+        }
+        let close = VotingInstruction::ClosePoll;
+        assert!(ensure_runs(&close, &read_only, &admin).is_ok());
+        assert_eq!(ensure_runs(&close, &read_only, &[]), Err(VotingError::ReadOnlyMode.into()));
+        assert!(ProgramVersion::current().supports(FEATURE_READ_ONLY_MODE));
+
+        let clock = FixedClock { slot: TEST_SLOT, unix_timestamp: TEST_TIME };
+        let (creator_key, poll_key) = (dummy_pubkey(1), dummy_pubkey(2));
+        let config_key = *config_key();
+        let mut config_data = vec![0u8; PROGRAM_CONFIG_LEN];
+
+        // Only the program's own key can create the config, once.
+        let initialize =
+            VotingInstruction::InitializeConfig { admin: admin_key, read_only_mode: true };
+        let impostor_key = dummy_pubkey(9);
+        for (program_key, created, expected) in [
+            (impostor_key, false, Err(VotingError::Unauthorized.into())),
+            (PROGRAM_ID, false, Ok(())),
+            (PROGRAM_ID, true, Err(ProgramError::AccountAlreadyInitialized)),
+        ] {
+            let (mut payer_data, mut program_data, mut system_data) = (vec![], vec![], vec![]);
+            let owner = if created { &PROGRAM_ID } else { &system_program::ID };
+            let accounts = [
+                account_info(&config_key, owner, false, &mut config_data),
+                create_account_info(&payer_key, true, &mut payer_data),
+                create_account_info(&program_key, true, &mut program_data),
+                system_account_info(&system_program::ID, &mut system_data),
+            ];
+            let data = initialize.try_to_vec().unwrap();
+            let result = process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock);
+            assert_eq!(result, expected);
+        }
+        let config = ProgramConfig::deserialize(&mut &config_data[..]).unwrap();
+        assert_eq!(
+            config,
+            ProgramConfig { admin: admin_key, read_only_mode: true, ..ProgramConfig::default() }
+        );
+
+        // Runs `instruction` against a fresh `poll` made by `creator`, passing
+        // the config last; administrative instructions are signed by the
+        // poll's creator.
+        let run = |instruction: &VotingInstruction,
+                   poll: PollBuilder,
+                   creator: &Pubkey,
+                   config_data: &mut [u8]| {
+            let mut poll_data = poll.creator(*creator).account_data();
+            let (mut creator_data, mut clock_data) = (vec![], vec![]);
+            let mut accounts = vec![create_account_info(&poll_key, false, &mut poll_data)];
+            if instruction.is_admin() {
+                accounts.push(create_account_info(creator, true, &mut creator_data));
+                accounts.push(create_account_info(&sysvar::clock::ID, false, &mut clock_data));
+            }
+            accounts.push(create_account_info(&config_key, false, config_data));
+            let data = instruction.try_to_vec().unwrap();
+            process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock)
+        };
+        let set = |read_only_mode, signer: &Pubkey, config_data: &mut [u8]| {
+            let mut signer_data = vec![];
+            let accounts = [
+                create_account_info(&config_key, false, config_data),
+                create_account_info(signer, true, &mut signer_data),
+            ];
+            let data = VotingInstruction::SetReadOnlyMode { read_only_mode }.try_to_vec().unwrap();
+            process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock)
+        };
+        let ended = || PollBuilder::new().window(TEST_TIME - 1_000, TEST_TIME);
+        let closed = || PollBuilder::new().vote_counts(&[3, 1]).closed();
+        let finalize = VotingInstruction::FinalizePoll;
+
+        // Creates poll 0 for `creator`, passing the config last.
+        let create = |creator: &Pubkey, config_data: &mut [u8]| {
+            let poll_key = find_poll_address(&PROGRAM_ID, creator, 0).0;
+            let mut poll_data = vec![0u8; 1024];
+            let (mut creator_data, mut clock_data) = (vec![], vec![]);
+            let accounts = [
+                create_account_info(&poll_key, false, &mut poll_data),
+                create_account_info(creator, true, &mut creator_data),
+                create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+                create_account_info(&config_key, false, config_data),
+            ];
+            let data = VotingInstruction::CreatePoll {
+                poll_id: 0,
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: TEST_TIME + 100,
+                end_time: TEST_TIME + 1_000,
+                moderator: *creator,
+                allow_new_options: false,
+                attestors: Vec::new(),
+                poll_type: PollType::Plurality,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                approval_threshold_bps: 0,
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            }
+            .try_to_vec()
+            .unwrap();
+            process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock)
+        };
+        let read_only_error = Err(VotingError::ReadOnlyMode.into());
+
+        // Read-only, only the config's admin sets up and manages polls, and
+        // no poll is finalized.
+        assert_eq!(create(&creator_key, &mut config_data), read_only_error);
+        assert!(create(&admin_key, &mut config_data).is_ok());
+        let close = VotingInstruction::ClosePoll;
+        assert_eq!(run(&close, ended(), &creator_key, &mut config_data), read_only_error);
+        assert!(run(&close, ended(), &admin_key, &mut config_data).is_ok());
+        assert_eq!(run(&finalize, closed(), &admin_key, &mut config_data), read_only_error);
+
+        // Only the admin opens the program, without redeploying it.
+        assert_eq!(
+            set(false, &impostor_key, &mut config_data),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(set(false, &admin_key, &mut config_data).is_ok());
+        assert!(create(&creator_key, &mut config_data).is_ok());
+        assert!(run(&close, ended(), &creator_key, &mut config_data).is_ok());
+        assert!(run(&finalize, closed(), &creator_key, &mut config_data).is_ok());
+        assert!(set(true, &admin_key, &mut config_data).is_ok());
+        assert_eq!(run(&finalize, closed(), &creator_key, &mut config_data), read_only_error);
+
+        // The config cannot be left off.
+        let mut poll_data = closed().account_data();
+        let accounts = [create_account_info(&poll_key, false, &mut poll_data)];
+        let data = finalize.try_to_vec().unwrap();
+        assert_eq!(
+            process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock),
+            Err(VotingError::AccountMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_call_policy() {
+        let relay = dummy_pubkey(40);
+        let sysvar_key = sysvar::instructions::ID;
+        // Checks a call at `stack_height` while `programs` run at the top level.
+        let check = |programs: &[Pubkey], stack_height: u64, policy: CallPolicy| {
+            let mut data = instructions_sysvar_data(programs, 1);
+            let (poll_key, caller_key) = (dummy_pubkey(2), dummy_pubkey(1));
+            let (mut poll_data, mut caller_data) = (vec![], vec![]);
+            let accounts = [
+                account_info(&poll_key, &PROGRAM_ID, false, &mut poll_data),
+                account_info(&caller_key, &PROGRAM_ID, true, &mut caller_data),
+                account_info(&sysvar_key, &sysvar_key, false, &mut data),
+            ];
+            ensure_caller_allowed(&PROGRAM_ID, &accounts, policy, &[relay], stack_height)
+        };
+        let denied = Err(VotingError::CallerNotAllowed.into());
+
+        // The second top-level instruction runs: this program directly, or
+        // the allowlisted relay or another program calling in through CPI.
+        let direct = [dummy_pubkey(41), PROGRAM_ID];
+        let relayed = [PROGRAM_ID, relay];
+        let foreign = [PROGRAM_ID, dummy_pubkey(41)];
+        for (programs, stack_height) in [(&direct, 1), (&relayed, 2), (&foreign, 2)] {
+            assert!(check(programs, stack_height, CallPolicy::Any).is_ok());
+        }
+        assert!(check(&direct, 1, CallPolicy::TopLevelOnly).is_ok());
+        assert_eq!(check(&relayed, 2, CallPolicy::TopLevelOnly), denied);
+        assert!(check(&relayed, 2, CallPolicy::AllowlistedCpi).is_ok());
+        assert_eq!(check(&foreign, 2, CallPolicy::AllowlistedCpi), denied);
+        assert_eq!(check(&direct, 1, CallPolicy::AllowlistedCpi), denied);
+
+        // Nested CPI: a program the relay invokes calls in, or this program
+        // is re-entered through another one.
+        assert_eq!(check(&relayed, 3, CallPolicy::AllowlistedCpi), denied);
+        assert_eq!(check(&direct, 2, CallPolicy::TopLevelOnly), denied);
+        assert_eq!(check(&direct, 3, CallPolicy::AllowlistedCpi), denied);
+
+        // A guarded instruction needs the sysvar; others do not.
+        assert_eq!(
+            ensure_caller_allowed(&PROGRAM_ID, &[], CallPolicy::TopLevelOnly, &[], 1),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert!(ensure_caller_allowed(&PROGRAM_ID, &[], CallPolicy::Any, &[], 1).is_ok());
+        assert!(ProgramVersion::current().supports(FEATURE_CALL_POLICY));
+        assert!(usize::from(INSTRUCTION_COUNT) <= MAX_CALL_POLICIES);
+
+        let clock = FixedClock { slot: TEST_SLOT, unix_timestamp: TEST_TIME };
+        let (admin_key, impostor_key) = (dummy_pubkey(7), dummy_pubkey(9));
+        let poll_key = dummy_pubkey(2);
+        let config_key = *config_key();
+        let mut config_data =
+            ProgramConfig { admin: admin_key, ..ProgramConfig::default() }.try_to_vec().unwrap();
+        config_data.resize(PROGRAM_CONFIG_LEN, 0);
+        let configure =
+            |instruction: VotingInstruction, signer: &Pubkey, config_data: &mut [u8]| {
+                let mut signer_data = vec![];
+                let accounts = [
+                    create_account_info(&config_key, false, config_data),
+                    create_account_info(signer, true, &mut signer_data),
+                ];
+                let data = instruction.try_to_vec().unwrap();
+                process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock)
+            };
+        // Finalizes a closed poll, passing the instructions sysvar for
+        // `programs` if any and the config last.
+        let finalize = |programs: Option<&[Pubkey]>, config_data: &mut [u8]| {
+            let mut poll_data = PollBuilder::new().vote_counts(&[3, 1]).closed().account_data();
+            let mut sysvar_data =
+                programs.map_or_else(Vec::new, |programs| instructions_sysvar_data(programs, 1));
+            let mut accounts = vec![create_account_info(&poll_key, false, &mut poll_data)];
+            if programs.is_some() {
+                accounts.push(account_info(&sysvar_key, &sysvar_key, false, &mut sysvar_data));
+            }
+            accounts.push(create_account_info(&config_key, false, config_data));
+            let data = VotingInstruction::FinalizePoll.try_to_vec().unwrap();
+            process_instruction_with_clock(&PROGRAM_ID, &accounts, &data, &clock)
+        };
+        let finalize_tag = VotingInstruction::FinalizePoll.try_to_vec().unwrap()[0];
+        let set_policy =
+            |policy| VotingInstruction::SetCallPolicy { instruction: finalize_tag, policy };
+
+        // Only the admin guards an instruction, and only that one.
+        assert!(finalize(None, &mut config_data).is_ok());
+        assert_eq!(
+            configure(set_policy(CallPolicy::TopLevelOnly), &impostor_key, &mut config_data),
+            Err(VotingError::Unauthorized.into())
+        );
+        let guard = set_policy(CallPolicy::TopLevelOnly);
+        assert!(configure(guard, &admin_key, &mut config_data).is_ok());
+        let config = ProgramConfig::deserialize(&mut &config_data[..]).unwrap();
+        assert_eq!(config.call_policy(finalize_tag), CallPolicy::TopLevelOnly);
+        assert_eq!(config.call_policy(finalize_tag + 1), CallPolicy::Any);
+        assert_eq!(finalize(None, &mut config_data), Err(ProgramError::NotEnoughAccountKeys));
+        assert!(finalize(Some(&direct), &mut config_data).is_ok());
+        assert_eq!(finalize(Some(&relayed), &mut config_data), denied);
+
+        // Through CPI, only from the programs the admin allowlists.
+        let guard = set_policy(CallPolicy::AllowlistedCpi);
+        assert!(configure(guard, &admin_key, &mut config_data).is_ok());
+        assert_eq!(finalize(Some(&relayed), &mut config_data), denied);
+        let allowlist = |programs: Vec<Pubkey>| VotingInstruction::SetCpiAllowlist { programs };
+        assert_eq!(
+            configure(allowlist(vec![relay]), &impostor_key, &mut config_data),
+            Err(VotingError::Unauthorized.into())
+        );
+        assert!(configure(allowlist(vec![relay]), &admin_key, &mut config_data).is_ok());
+        assert!(finalize(Some(&relayed), &mut config_data).is_ok());
+        assert_eq!(finalize(Some(&foreign), &mut config_data), denied);
+        assert_eq!(finalize(Some(&direct), &mut config_data), denied);
+
+        // Lists stay within the config account.
+        assert_eq!(
+            configure(allowlist(vec![relay; MAX_CPI_ALLOWLIST + 1]), &admin_key, &mut config_data),
+            Err(ProgramError::InvalidArgument)
+        );
+        let guard_tag = |instruction| VotingInstruction::SetCallPolicy {
+            instruction,
+            policy: CallPolicy::TopLevelOnly,
+        };
+        // Only instructions that take the config can be guarded.
+        for tag in 0..=INSTRUCTION_COUNT {
+            let expected = match tag {
+                INSTRUCTION_COUNT => Err(ProgramError::InvalidInstructionData),
+                _ if CONFIG_FREE_TAGS.contains(&tag) => Err(ProgramError::InvalidInstructionData),
+                _ => Ok(()),
+            };
+            assert_eq!(configure(guard_tag(tag), &admin_key, &mut config_data), expected);
+        }
+
+        // Lifting the policy drops its entry.
+        assert!(configure(set_policy(CallPolicy::Any), &admin_key, &mut config_data).is_ok());
+        assert!(finalize(None, &mut config_data).is_ok());
+        let config = ProgramConfig::deserialize(&mut &config_data[..]).unwrap();
+        let guarded = usize::from(INSTRUCTION_COUNT) - CONFIG_FREE_TAGS.len();
+        assert_eq!(config.call_policies.len(), guarded - 1);
+    }
+
+    #[test]
+    fn test_vote_outside_voting_window() {
+        let creator_key = dummy_pubkey(1);
+        let voter_key = dummy_pubkey(3);
+        let mut poll = TestPoll::new();
+        let create = VotingInstruction::CreatePoll {
+            poll_id: 0,
+            question: "Best programming language?".to_string(),
+            options: vec!["Rust".to_string(), "Go".to_string()],
+            start_time: TEST_TIME + 100,
+            end_time: TEST_TIME + 200,
+            moderator: dummy_pubkey(8),
+            allow_new_options: false,
+            attestors: Vec::new(),
+            poll_type: PollType::Plurality,
+            candidates: Vec::new(),
+            max_spoiled_bps: 0,
+            weight_mint: None,
+            blackout_secs: 0,
+            max_selections: 0,
+            terms_hash: None,
+            weight_program: None,
+            unique_question: false,
+            quorum: None,
+            approval_threshold_bps: 0,
+            tie_break: TieBreak::Fail,
+            eligibility: EligibilityRule::Open,
+            max_delegation_depth: 0,
+            weight_curve: WeightCurve::LINEAR,
+            points_per_voter: 0,
+            seats: 0,
+            clock_tolerance_secs: 0,
+        };
+        assert!(run_instruction(&mut poll, &creator_key, &create).is_ok());
+
+        let vote = VotingInstruction::Vote {
+            option_index: 0,
+            accept_terms: false,
+            eligibility_proof: Vec::new(),
+        }
+        .try_to_vec()
+        .unwrap();
+        let poll_key = dummy_pubkey(2);
+        let (receipt_key, _) = find_receipt_address(&PROGRAM_ID, &poll_key, &voter_key);
+        let mut voter_data = vec![];
+        let mut clock_data = vec![];
+        let mut receipt_data = vec![0u8; VOTE_RECEIPT_LEN];
+        let mut system_data = vec![];
+        let accounts = [
+            create_account_info(&poll_key, false, &mut poll.data),
+            create_account_info(&voter_key, true, &mut voter_data),
+            create_account_info(&sysvar::clock::ID, false, &mut clock_data),
+            system_account_info(&receipt_key, &mut receipt_data),
+            system_account_info(&system_program::ID, &mut system_data),
+        ];
+
+        for (now, expected) in [
+            (TEST_TIME + 99, Err(VotingError::VotingNotActive.into())),
+            (TEST_TIME + 201, Err(VotingError::VotingNotActive.into())),
+            (TEST_TIME + 150, Ok(())),
+        ] {
+            let clock = FixedClock { slot: TEST_SLOT, unix_timestamp: now };
+            let result = process_with_clock(&accounts, &vote, &clock);
+            assert_eq!(result, expected, "voting at {}", now);
+        }
+    }
+
+    #[test]
+    fn test_window_boundaries() {
+        assert!(is_before_with_tolerance(105, 100, 5) && !is_before_with_tolerance(106, 100, 5));
+        assert!(is_after_with_tolerance(95, 100, 5) && !is_after_with_tolerance(94, 100, 5));
+        assert!(is_before_with_tolerance(u64::MAX, u64::MAX - 1, 5));
+
+        // Both ends of the window accept votes, and closing opens at the end.
+        let poll = PollBuilder::new().window(TEST_TIME, TEST_TIME + 100).build();
+        for (now, started, ended, can_close) in [
+            (TEST_TIME - 1, false, false, false),
+            (TEST_TIME, true, false, false),
+            (TEST_TIME + 100, true, false, true),
+            (TEST_TIME + 101, true, true, true),
+        ] {
+            assert_eq!(
+                (poll.voting_started(now), poll.voting_ended(now), poll.can_close(now)),
+                (started, ended, can_close),
+                "at {}",
+                now
+            );
+            assert_eq!(poll.ensure_accepting_votes(now).is_ok(), started && !ended);
+        }
+
+        // A poll's clock tolerance widens every boundary, the blackout's too.
+        let poll = PollBuilder::new()
+            .window(TEST_TIME, TEST_TIME + 100)
+            .blackout_secs(20)
+            .clock_tolerance(5)
+            .build();
+        for (now, started, ended, can_close, in_blackout) in [
+            (TEST_TIME - 6, false, false, false, false),
+            (TEST_TIME - 5, true, false, false, false),
+            (TEST_TIME + 74, true, false, false, false),
+            (TEST_TIME + 75, true, false, false, true),
+            (TEST_TIME + 104, true, false, false, true),
+            (TEST_TIME + 105, true, false, true, true),
+            (TEST_TIME + 106, true, true, true, true),
+        ] {
+            assert_eq!(
+                (
+                    poll.voting_started(now),
+                    poll.voting_ended(now),
+                    poll.can_close(now),
+                    poll.in_blackout(now)
+                ),
+                (started, ended, can_close, in_blackout),
+                "at {}",
+                now
+            );
+        }
+    }
+}
@@ -0,0 +1,1034 @@
+// src/state.rs
+
+use arch_program::{entrypoint::ProgramResult, msg, program_error::ProgramError, pubkey::Pubkey};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    attestation::{ResultAttestation, RESULT_ATTESTATION_LEN},
+    budget::{budget_allocation, BudgetAllocation},
+    eligibility::EligibilityRule,
+    error::VotingError,
+    merkle::Hash,
+    tally::condorcet_winners,
+    weight::WeightCurve,
+};
+
+/// Maximum length of a poll question, in bytes.
+pub const MAX_QUESTION_LEN: usize = 256;
+/// Maximum number of options a poll may offer.
+pub const MAX_OPTIONS: usize = 32;
+/// Maximum length of a single option label, in bytes.
+pub const MAX_OPTION_LEN: usize = 64;
+/// Options of every proposal created with `CreateProposal`, in index order.
+pub const PROPOSAL_OPTIONS: [&str; 2] = ["Yes", "No"];
+
+/// Index of the Yes option of a proposal.
+pub const PROPOSAL_YES: u32 = 0;
+
+/// Maximum number of options a Condorcet poll may offer, so its pairwise
+/// matrix fits in the poll account.
+pub const MAX_CONDORCET_OPTIONS: usize = 16;
+/// Largest poll account the program will deserialize.
+///
+/// The runtime gives the program a 32 KiB bump heap that never frees. A
+/// deserialized poll takes roughly its serialized size on the heap, so 8 KiB
+/// of account data keeps the worst-case vote comfortably inside the heap.
+pub const MAX_POLL_ACCOUNT_LEN: usize = 8 * 1024;
+/// Maximum number of attestors a poll may name.
+pub const MAX_ATTESTORS: usize = 8;
+/// Longest voting window, in seconds, that `CreatePoll`, `UpdatePoll` and
+/// `ExtendPoll` accept.
+pub const MAX_POLL_DURATION: u64 = 366 * 24 * 60 * 60;
+/// Longest delegation chain, in steps, a poll may count ballots along.
+pub const MAX_DELEGATION_DEPTH: u8 = 8;
+
+/// Largest `clock_tolerance_secs`, in seconds, a poll may be created with.
+pub const MAX_CLOCK_TOLERANCE_SECS: u64 = 5 * 60;
+
+/// Whether unix time `now` is no later than `boundary`, counting up to
+/// `tolerance` seconds past it as on time.
+pub fn is_before_with_tolerance(now: u64, boundary: u64, tolerance: u64) -> bool {
+    now <= boundary.saturating_add(tolerance)
+}
+
+/// Whether unix time `now` has reached `boundary`, counting up to `tolerance`
+/// seconds short of it as reached.
+pub fn is_after_with_tolerance(now: u64, boundary: u64, tolerance: u64) -> bool {
+    now.saturating_add(tolerance) >= boundary
+}
+
+/// Program owning the token accounts that token-weighted polls read
+/// balances from.
+pub const TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array(*b"apl-token00000000000000000000000");
+
+/// Length of a token account.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Seed prefix of poll account addresses.
+pub const POLL_SEED: &[u8] = b"poll";
+
+/// Derives the address of `creator`'s poll `poll_id` and its bump seed.
+///
+/// Poll accounts live at the program address for `["poll", creator, poll_id]`,
+/// with `poll_id` encoded as little-endian bytes.
+pub fn find_poll_address(program_id: &Pubkey, creator: &Pubkey, poll_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POLL_SEED, creator.as_ref(), &poll_id.to_le_bytes()], program_id)
+}
+
+/// Seed prefix of vote receipt addresses.
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+
+/// `VoteReceipt::option_index` of a spoiled ballot.
+pub const SPOILED_BALLOT: u32 = u32::MAX;
+
+/// `VoteReceipt::option_index` of an abstention, and the only entry of an
+/// abstaining ranked or multi-select ballot.
+pub const ABSTAINED_BALLOT: u32 = u32::MAX - 1;
+
+/// Basis points in a whole.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Serialized size of a [`VoteReceipt`].
+pub const VOTE_RECEIPT_LEN: usize = 32 + 32 + 4 + 8 + 1 + 33 + 4;
+
+/// Derives the address of `voter`'s receipt for `poll` and its bump seed.
+///
+/// Receipts live at the program address for `["receipt", poll, voter]`.
+pub fn find_receipt_address(program_id: &Pubkey, poll: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED, poll.as_ref(), voter.as_ref()], program_id)
+}
+
+/// Seed prefix of ranked ballot addresses.
+pub const BALLOT_SEED: &[u8] = b"ballot";
+
+/// Serialized size of a [`RankedBallot`] ranking `ranked` options.
+pub const fn ranked_ballot_len(ranked: usize) -> usize {
+    32 + 32 + 4 + 4 * ranked + 8 + 1
+}
+
+/// Serialized size of a [`MultiBallot`] selecting `selected` options.
+pub const fn multi_ballot_len(selected: usize) -> usize {
+    32 + 32 + 4 + 4 * selected + 8 + 1
+}
+
+/// Serialized size of a [`CumulativeBallot`] allocating points to
+/// `allocated` options.
+pub const fn cumulative_ballot_len(allocated: usize) -> usize {
+    32 + 32 + 4 + 12 * allocated + 8 + 1
+}
+
+/// Reads a receipt or ballot account. Records written before
+/// `accepted_terms` was added end one byte short and read as not accepted;
+/// receipts written before `delegate` read as cast by the voter, and those
+/// written before `delegated_ballots` as carrying none.
+pub fn unpack_record<T: BorshDeserialize>(data: &[u8]) -> Result<T, ProgramError> {
+    let mut padded = data.to_vec();
+    padded.extend_from_slice(&[0; 6]);
+    T::deserialize(&mut &padded[..]).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Derives the address of `voter`'s ranked ballot for `poll` and its bump seed.
+///
+/// Ballots live at the program address for `["ballot", poll, voter]`.
+pub fn find_ballot_address(program_id: &Pubkey, poll: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BALLOT_SEED, poll.as_ref(), voter.as_ref()], program_id)
+}
+
+/// Seed prefix of attestation record addresses.
+pub const ATTESTATION_SEED: &[u8] = b"attestation";
+
+/// Serialized size of an [`AttestationRecord`].
+pub const ATTESTATION_RECORD_LEN: usize = RESULT_ATTESTATION_LEN + 32 + 64;
+
+/// Derives the address of `attestor`'s record for `poll` and its bump seed.
+///
+/// Records live at the program address for `["attestation", poll, attestor]`.
+pub fn find_attestation_address(
+    program_id: &Pubkey,
+    poll: &Pubkey,
+    attestor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ATTESTATION_SEED, poll.as_ref(), attestor.as_ref()], program_id)
+}
+
+/// Seed prefix of result snapshot addresses.
+pub const SNAPSHOT_SEED: &[u8] = b"snapshot";
+
+/// Maximum length of a snapshot label, in bytes; labels are address seeds.
+pub const MAX_SNAPSHOT_LABEL_LEN: usize = 32;
+
+/// Derives the address of `poll`'s snapshot labelled `label` and its bump seed.
+///
+/// Snapshots live at the program address for `["snapshot", poll, label]`.
+pub fn find_snapshot_address(program_id: &Pubkey, poll: &Pubkey, label: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SNAPSHOT_SEED, poll.as_ref(), label.as_bytes()], program_id)
+}
+
+/// Seed prefix of question record addresses.
+pub const QUESTION_SEED: &[u8] = b"question";
+
+/// Serialized size of a [`QuestionRecord`].
+pub const QUESTION_RECORD_LEN: usize = 32;
+
+/// SHA-256 of a poll question, as used in question record addresses.
+pub fn question_hash(question: &str) -> Hash {
+    Sha256::digest(question.as_bytes()).into()
+}
+
+/// Derives the address of `creator`'s record for the question hashing to
+/// `question_hash`, and its bump seed.
+///
+/// Records live at the program address for `["question", creator, question_hash]`.
+pub fn find_question_address(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    question_hash: &Hash,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[QUESTION_SEED, creator.as_ref(), question_hash], program_id)
+}
+
+/// Seed prefix of voter record addresses.
+pub const VOTER_SEED: &[u8] = b"voter";
+
+/// Serialized size of a [`VoterRecord`].
+pub const VOTER_RECORD_LEN: usize = 32 + 8 + 8 + 8 + 33;
+
+/// Derives the address of `voter`'s record and its bump seed.
+///
+/// Records live at the program address for `["voter", voter]`.
+pub fn find_voter_record_address(program_id: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VOTER_SEED, voter.as_ref()], program_id)
+}
+
+/// Seed of the program config's address.
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Most instructions a [`ProgramConfig`] can give a call policy.
+pub const MAX_CALL_POLICIES: usize = 64;
+
+/// Most programs a [`ProgramConfig`] can allowlist for CPI.
+pub const MAX_CPI_ALLOWLIST: usize = 8;
+
+/// Serialized size of a [`ProgramConfig`] with every list full.
+pub const PROGRAM_CONFIG_LEN: usize =
+    32 + 1 + (4 + 2 * MAX_CALL_POLICIES) + (4 + 32 * MAX_CPI_ALLOWLIST);
+
+/// Derives the address of the program's config and its bump seed.
+///
+/// The config lives at the program address for `["config"]`.
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Result of a closed poll, recorded by `FinalizePoll`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollOutcome {
+    /// Quorum was met and an option won, outright or by tie-break, with the
+    /// required share.
+    Passed,
+    /// Quorum was met but no option won: an unbroken tie, no votes, a lead
+    /// short of the approval threshold, or a voided poll.
+    Failed,
+    /// Participation fell short of the poll's `quorum`.
+    QuorumNotMet,
+    /// Quorum was met but options tied for first under `TieBreak::Rerun`;
+    /// they are listed in `tied_options` for a runoff poll.
+    Tied,
+}
+
+/// How `FinalizePoll` settles a tie for first place on a plurality poll.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// No option wins and the poll fails.
+    Fail,
+    /// The earliest-listed tied option wins.
+    EarliestOption,
+    /// No option wins, and the outcome is `Tied` so a runoff poll can be
+    /// held among the tied options.
+    Rerun,
+}
+
+/// How a poll's ballots are cast and counted.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollType {
+    /// One option per voter; the option with the most votes leads.
+    Plurality,
+    /// Voters rank options with `RankedVote`; `Tally` runs instant runoff
+    /// after the poll is closed.
+    RankedChoice,
+    /// Voters rank every option with `RankedVote`. On a ballot ranking `n`
+    /// options, the one in place `p` (from 0) scores `n - 1 - p` points,
+    /// kept as running scores in `vote_weights`; the highest score leads.
+    Borda,
+    /// Voters rank options with `RankedVote`, each ballot updating the
+    /// poll's `pairwise` preference matrix. `FinalizePoll` elects the option
+    /// that beats every other head to head, falling back to the Schulze
+    /// method when there is none.
+    Condorcet,
+    /// Voters split a budget of `points_per_voter` points across options
+    /// with `VoteCumulative`, each ballot normalized and kept in
+    /// `vote_weights`; the result is the average allocation (see `budget`).
+    Budget,
+}
+
+/// The poll state stored in an account.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct Poll {
+    /// Creator of the poll.
+    pub creator: Pubkey,
+    /// Poll question.
+    pub question: String,
+    /// Poll options.
+    pub options: Vec<String>,
+    /// Vote count per option (parallel to `options`).
+    pub vote_counts: Vec<u64>,
+    /// Poll start time (unix timestamp in seconds).
+    pub start_time: u64,
+    /// Poll end time (unix timestamp in seconds).
+    pub end_time: u64,
+    /// Whether the poll is closed.
+    pub is_closed: bool,
+    /// Voters recorded before vote receipts were introduced.
+    ///
+    /// New votes are not added here; a `VoteReceipt` account records them
+    /// instead. Existing entries are still checked, so voters of older polls
+    /// cannot vote a second time.
+    pub voters: Vec<Pubkey>,
+    /// Slot in which the poll was created.
+    pub created_at_slot: u64,
+    /// On-chain unix time (seconds) at which the poll was created.
+    pub created_at_time: u64,
+    /// Slot of the first recorded ballot; options are frozen from then on.
+    pub first_vote_slot: Option<u64>,
+    /// Creator-scoped id the poll address is derived from.
+    pub poll_id: u64,
+    /// Whether the creator cancelled the poll before any vote was cast.
+    pub is_cancelled: bool,
+    /// Wallet allowed to quarantine the poll. All zeros on polls created
+    /// before moderators existed, which therefore have none.
+    pub moderator: Pubkey,
+    /// Whether the moderator has taken the poll out of service.
+    pub is_quarantined: bool,
+    /// Whether anyone may append options with `AddOption` while the poll is live.
+    pub allow_new_options: bool,
+    /// Wallets whose signed results `SubmitAttestation` accepts once the poll is closed.
+    pub attestors: Vec<Pubkey>,
+    /// Slot in which the poll was closed; `None` while open or if closed
+    /// before this was recorded.
+    pub closed_at_slot: Option<u64>,
+    /// Voting mode. Polls created before modes existed decode as `Plurality`.
+    pub poll_type: PollType,
+    /// Whether `Tally` has counted a ranked-choice poll's ballots.
+    pub is_tallied: bool,
+    /// Winner found by `Tally`, if any ballot ranked an option.
+    pub winner: Option<u32>,
+    /// Key allowed to withdraw each option with `WithdrawOption`, by option
+    /// index. Options past the end of the list have no candidate.
+    pub candidates: Vec<Option<Pubkey>>,
+    /// Indices of options their candidates have withdrawn.
+    pub withdrawn_options: Vec<u32>,
+    /// Ballots deliberately spoiled with `SpoilBallot`. They count toward
+    /// turnout but not toward any option.
+    pub spoiled_count: u64,
+    /// Share of turnout, in basis points, that spoiled ballots must exceed for
+    /// `ClosePoll` to void the poll; 0 never voids.
+    pub max_spoiled_bps: u16,
+    /// Whether `ClosePoll` voided the poll because too many ballots were spoiled.
+    pub is_void: bool,
+    /// Mint whose balance weighs each vote; `None` for one vote per wallet.
+    pub weight_mint: Option<Pubkey>,
+    /// Summed vote weight per option on weighted polls, points on
+    /// cumulative polls and scores on Borda polls; empty otherwise. Kept apart from `vote_counts`,
+    /// which still counts ballots, so the account layout of existing polls
+    /// is unchanged.
+    pub vote_weights: Vec<u128>,
+    /// Length of the blackout before `end_time`, in seconds; 0 for none.
+    /// Ballots cast in the blackout are held in `pending_counts` and
+    /// `pending_weights` and only added to the readable tallies at close.
+    pub blackout_secs: u64,
+    /// Per-option ballot changes made during the blackout; empty until then.
+    pub pending_counts: Vec<i64>,
+    /// Per-option weight added during the blackout; empty until then.
+    pub pending_weights: Vec<u128>,
+    /// Most options a ballot may select. Above 1 the poll is multi-select and
+    /// takes `VoteMulti` instead of `Vote`; 0 (older polls) and 1 mean one.
+    pub max_selections: u8,
+    /// Voters who took part with `Abstain`. They count toward turnout but
+    /// toward no option.
+    pub abstain_count: u64,
+    /// Hash of the terms each voter accepts with their first ballot; `None`
+    /// for no terms.
+    pub terms_hash: Option<Hash>,
+    /// Weight plugin program whose `VoterWeightRecord`s weigh each vote, for
+    /// polls not weighted by `weight_mint`; `None` for none.
+    pub weight_program: Option<Pubkey>,
+    /// Whether the question is registered in a `QuestionRecord`, and so
+    /// may not change.
+    pub unique_question: bool,
+    /// Least participation for the poll to count: total weight cast on
+    /// weighted polls, total points on cumulative polls, turnout otherwise.
+    /// `None` for no quorum.
+    pub quorum: Option<u64>,
+    /// Result recorded by `FinalizePoll`; `None` until then.
+    pub outcome: Option<PollOutcome>,
+    /// Share of the votes for options, in basis points, the leading option
+    /// must reach to pass; 0 for any outright lead.
+    pub approval_threshold_bps: u16,
+    /// Options tied for first when the poll was finalized, in index order;
+    /// empty without a tie.
+    pub tied_options: Vec<u32>,
+    /// How a tie for first is settled.
+    pub tie_break: TieBreak,
+    /// Who may cast ballots.
+    pub eligibility: EligibilityRule,
+    /// Longest delegation chain, in steps, along which a vote also casts
+    /// the ballots of the voter's delegators; 0 to count none.
+    pub max_delegation_depth: u8,
+    /// Floor, cap and transform applied to weights on weighted polls.
+    pub weight_curve: WeightCurve,
+    /// Points each voter distributes across options with `VoteCumulative`;
+    /// 0 for polls that take other ballots.
+    pub points_per_voter: u64,
+    /// On Condorcet polls, the number of ballots preferring option `a` to
+    /// option `b` at `pairwise[a][b]`; empty on other polls.
+    pub pairwise: Vec<Vec<u64>>,
+    /// Number of options a ranked-choice poll elects by single transferable
+    /// vote; 0 or 1 for a single winner by instant runoff.
+    pub seats: u8,
+    /// On polls with more than one seat, the options `Tally` elected, in the
+    /// order they were elected; empty until tallied.
+    pub elected: Vec<u32>,
+    /// Whether the poll is a yes/no proposal created with `CreateProposal`,
+    /// which passes only if Yes wins.
+    pub is_proposal: bool,
+    /// Seconds the on-chain clock may drift past a voting-window boundary and
+    /// still count as on the near side of it. Every window rule below applies
+    /// it, so it widens each boundary alike; 0 keeps them exact.
+    pub clock_tolerance_secs: u64,
+}
+
+impl Poll {
+    /// Checks the invariants every well-formed poll satisfies.
+    ///
+    /// A partially written or tampered account can still decode, so this runs
+    /// on every load to stop such a poll from silently miscounting.
+    pub fn check_integrity(&self) -> ProgramResult {
+        if self.vote_counts.len() != self.options.len() {
+            msg!(
+                "Corrupt poll: {} vote counts for {} options.",
+                self.vote_counts.len(),
+                self.options.len()
+            );
+            return Err(VotingError::CorruptPollState.into());
+        }
+        if self.has_vote_weights() && self.vote_weights.len() != self.options.len() {
+            msg!(
+                "Corrupt poll: {} vote weights for {} options.",
+                self.vote_weights.len(),
+                self.options.len()
+            );
+            return Err(VotingError::CorruptPollState.into());
+        }
+        let square = self.pairwise.len() == self.options.len()
+            && self.pairwise.iter().all(|row| row.len() == self.options.len());
+        if self.poll_type == PollType::Condorcet && !square {
+            msg!("Corrupt poll: pairwise matrix does not match {} options.", self.options.len());
+            return Err(VotingError::CorruptPollState.into());
+        }
+        if self.is_proposal && self.options != PROPOSAL_OPTIONS {
+            msg!("Corrupt poll: proposal options are not {:?}.", PROPOSAL_OPTIONS);
+            return Err(VotingError::CorruptPollState.into());
+        }
+        let pending_lens = [self.pending_counts.len(), self.pending_weights.len()];
+        if pending_lens.iter().any(|&len| len != 0 && len != self.options.len()) {
+            msg!("Corrupt poll: pending tallies do not match {} options.", self.options.len());
+            return Err(VotingError::CorruptPollState.into());
+        }
+        if self.start_time > self.end_time {
+            msg!("Corrupt poll: voting window ends before it starts.");
+            return Err(VotingError::CorruptPollState.into());
+        }
+        if self.is_closed && self.is_cancelled {
+            msg!("Corrupt poll: both closed and cancelled.");
+            return Err(VotingError::CorruptPollState.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless the poll is open and unix time `now` is inside its voting
+    /// window.
+    pub fn ensure_accepting_votes(&self, now: u64) -> ProgramResult {
+        if self.is_cancelled {
+            msg!("Poll was cancelled.");
+            return Err(VotingError::PollCancelled.into());
+        }
+        if self.is_closed {
+            msg!("Poll is closed.");
+            return Err(VotingError::PollClosed.into());
+        }
+        if !self.voting_started(now) || self.voting_ended(now) {
+            msg!("Voting period is not active.");
+            return Err(VotingError::VotingNotActive.into());
+        }
+        Ok(())
+    }
+
+    /// Whether voting has opened by unix time `now`.
+    pub fn voting_started(&self, now: u64) -> bool {
+        is_after_with_tolerance(now, self.start_time, self.clock_tolerance_secs)
+    }
+
+    /// Whether voting is over at unix time `now`: votes are accepted up to and
+    /// including `end_time`.
+    pub fn voting_ended(&self, now: u64) -> bool {
+        !is_before_with_tolerance(now, self.end_time, self.clock_tolerance_secs)
+    }
+
+    /// Whether the creator may close the poll at unix time `now`. Closing is
+    /// allowed from the last second that still accepts votes.
+    pub fn can_close(&self, now: u64) -> bool {
+        now >= self.end_time.saturating_add(self.clock_tolerance_secs)
+    }
+
+    /// Whether any ballot has been recorded, including on polls created
+    /// before `first_vote_slot` existed.
+    pub fn has_votes(&self) -> bool {
+        self.first_vote_slot.is_some() || self.vote_counts.iter().any(|&count| count > 0)
+    }
+
+    /// Candidate key of option `option_index`, if it has one.
+    pub fn candidate(&self, option_index: usize) -> Option<&Pubkey> {
+        self.candidates.get(option_index).and_then(Option::as_ref)
+    }
+
+    /// Whether option `option_index` has been withdrawn.
+    pub fn is_withdrawn(&self, option_index: usize) -> bool {
+        self.withdrawn_options.iter().any(|&withdrawn| withdrawn as usize == option_index)
+    }
+
+    /// Fails if option `option_index` has been withdrawn.
+    pub fn ensure_not_withdrawn(&self, option_index: usize) -> ProgramResult {
+        if self.is_withdrawn(option_index) {
+            msg!("Option {} has been withdrawn.", option_index);
+            return Err(VotingError::OptionWithdrawn.into());
+        }
+        Ok(())
+    }
+
+    /// Votes cast for options that were later withdrawn. They stay in
+    /// `vote_counts` but should be reported apart from the standings.
+    pub fn withdrawn_vote_count(&self) -> u64 {
+        self.vote_counts
+            .iter()
+            .enumerate()
+            .filter(|&(option_index, _)| self.is_withdrawn(option_index))
+            .map(|(_, &count)| count)
+            .sum()
+    }
+
+    /// Whether unix time `now` falls in the blackout before `end_time`. Like
+    /// the voting window, the blackout may begin up to `clock_tolerance_secs`
+    /// early.
+    pub fn in_blackout(&self, now: u64) -> bool {
+        let start = self.end_time.saturating_sub(self.blackout_secs);
+        self.blackout_secs > 0 && is_after_with_tolerance(now, start, self.clock_tolerance_secs)
+    }
+
+    /// Adds `delta` ballots for option `option_index`, to `pending_counts`
+    /// during the blackout and to `vote_counts` otherwise.
+    pub fn add_ballots(&mut self, option_index: usize, delta: i64, now: u64) -> ProgramResult {
+        if self.in_blackout(now) {
+            if self.pending_counts.is_empty() {
+                self.pending_counts = vec![0; self.options.len()];
+            }
+            let pending = &mut self.pending_counts[option_index];
+            *pending = pending.checked_add(delta).ok_or(VotingError::Overflow)?;
+        } else {
+            let count = &mut self.vote_counts[option_index];
+            *count = count.checked_add_signed(delta).ok_or(VotingError::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `weight` for option `option_index`, to `pending_weights` during
+    /// the blackout and to `vote_weights` otherwise.
+    pub fn add_weight(&mut self, option_index: usize, weight: u128, now: u64) -> ProgramResult {
+        let weights = if self.in_blackout(now) {
+            if self.pending_weights.is_empty() {
+                self.pending_weights = vec![0; self.options.len()];
+            }
+            &mut self.pending_weights
+        } else {
+            &mut self.vote_weights
+        };
+        weights[option_index] =
+            weights[option_index].checked_add(weight).ok_or(VotingError::Overflow)?;
+        Ok(())
+    }
+
+    /// Moves ballots and weight held back by the blackout into the readable
+    /// tallies.
+    pub fn reveal_pending(&mut self) -> ProgramResult {
+        for (count, pending) in self.vote_counts.iter_mut().zip(self.pending_counts.drain(..)) {
+            *count = count.checked_add_signed(pending).ok_or(VotingError::Overflow)?;
+        }
+        for (weight, pending) in self.vote_weights.iter_mut().zip(self.pending_weights.drain(..)) {
+            *weight = weight.checked_add(pending).ok_or(VotingError::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if the poll has terms and the ballot does not accept them.
+    pub fn ensure_terms_accepted(&self, accept_terms: bool) -> ProgramResult {
+        if self.terms_hash.is_some() && !accept_terms {
+            msg!("The poll's terms must be accepted to vote.");
+            return Err(VotingError::TermsNotAccepted.into());
+        }
+        Ok(())
+    }
+
+    /// Whether votes carry a weight, from a token mint or a weight plugin.
+    pub fn is_weighted(&self) -> bool {
+        self.weight_mint.is_some() || self.weight_program.is_some()
+    }
+
+    /// Whether voters distribute points across options with `VoteCumulative`.
+    pub fn is_cumulative(&self) -> bool {
+        self.points_per_voter > 0
+    }
+
+    /// Whether `vote_weights` holds per-option totals: weight on weighted
+    /// polls, points on cumulative ones, scores on Borda polls and normalized
+    /// allocations on budget polls.
+    pub fn has_vote_weights(&self) -> bool {
+        self.is_weighted() || self.is_cumulative() || self.poll_type == PollType::Borda
+    }
+
+    /// Whether ballots select several options with `VoteMulti`.
+    pub fn is_multi_select(&self) -> bool {
+        self.max_selections > 1
+    }
+
+    /// Ballots cast, including spoiled ones and abstentions. On multi-select
+    /// polls a ballot counts once for each option it selects.
+    pub fn turnout(&self) -> u64 {
+        self.vote_counts.iter().sum::<u64>() + self.spoiled_count + self.abstain_count
+    }
+
+    /// Whether spoiled ballots exceed `max_spoiled_bps` of turnout.
+    pub fn spoiled_share_exceeded(&self) -> bool {
+        self.max_spoiled_bps > 0
+            && u128::from(self.spoiled_count) * u128::from(BPS_DENOMINATOR)
+                > u128::from(self.max_spoiled_bps) * u128::from(self.turnout())
+    }
+
+    /// Participation measured against `quorum`: total weight cast on
+    /// weighted polls, total points on cumulative polls, ballots on budget
+    /// polls, turnout otherwise (including Borda polls, whose scores grow
+    /// with the number of options).
+    pub fn participation(&self) -> u128 {
+        if self.poll_type == PollType::Budget {
+            u128::from(self.budget_allocation().ballots)
+        } else if self.is_weighted() || self.is_cumulative() {
+            self.vote_weights.iter().sum()
+        } else {
+            u128::from(self.turnout())
+        }
+    }
+
+    /// Whether participation reaches `quorum`; always true without one.
+    pub fn quorum_met(&self) -> bool {
+        match self.quorum {
+            Some(quorum) => self.participation() >= u128::from(quorum),
+            None => true,
+        }
+    }
+
+    /// Votes for option `option_index`: its weight on weighted polls, its
+    /// points on cumulative polls, its score on Borda polls, its count
+    /// otherwise.
+    pub(crate) fn option_score(&self, option_index: usize) -> u128 {
+        if self.has_vote_weights() {
+            self.vote_weights[option_index]
+        } else {
+            u128::from(self.vote_counts[option_index])
+        }
+    }
+
+    /// The options not withdrawn that share the most votes (weight, points
+    /// or score on weighted, cumulative or Borda polls), in index order;
+    /// empty with no votes.
+    pub fn leading_options(&self) -> Vec<u32> {
+        let mut leaders = Vec::new();
+        let mut best = 0u128;
+        for option_index in (0..self.options.len()).filter(|&index| !self.is_withdrawn(index)) {
+            let score = self.option_score(option_index);
+            if score > best {
+                best = score;
+                leaders.clear();
+            }
+            if score == best && score > 0 {
+                leaders.push(option_index as u32);
+            }
+        }
+        leaders
+    }
+
+    /// The option with strictly the most votes (weight, points or score on
+    /// weighted, cumulative or Borda polls) among those not withdrawn; `None`
+    /// on a tie for first or with no votes.
+    pub fn plurality_leader(&self) -> Option<u32> {
+        match self.leading_options()[..] {
+            [leader] => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// Whether option `option_index` holds at least `approval_threshold_bps`
+    /// of the votes for all options, with its share rounded up to a whole
+    /// basis point so that 6667 admits exactly two-thirds.
+    pub fn meets_approval_threshold(&self, option_index: u32) -> bool {
+        let total: u128 = (0..self.options.len()).map(|index| self.option_score(index)).sum();
+        if total == 0 {
+            return self.approval_threshold_bps == 0;
+        }
+        let share_bps = (self.option_score(option_index as usize) * u128::from(BPS_DENOMINATOR))
+            .div_ceil(total);
+        share_bps >= u128::from(self.approval_threshold_bps)
+    }
+
+    /// Average allocation of a budget poll's ballots, in basis points of the
+    /// budget per option.
+    pub fn budget_allocation(&self) -> BudgetAllocation {
+        budget_allocation(&self.vote_weights)
+    }
+
+    /// Decides the closed poll, recording its `outcome`.
+    ///
+    /// Plurality and Borda polls also record the leading option as
+    /// `winner`, and Condorcet polls the Condorcet or Schulze winner (see
+    /// `tally::condorcet_winners`), settling a tie for first by `tie_break`
+    /// and listing the tied options in `tied_options`. Ranked-choice polls
+    /// keep the winner, or with several seats the options `elected`, recorded
+    /// by `Tally`, and budget polls record no winner. The outcome is then
+    /// `QuorumNotMet` if participation fell short, and otherwise whether the
+    /// winner cleared the approval threshold, any option was elected or, on a
+    /// budget poll, any ballot allocated the budget. A proposal fails unless
+    /// its winner is Yes.
+    pub fn finalize(&mut self) -> PollOutcome {
+        let leaders = match self.poll_type {
+            PollType::Plurality | PollType::Borda => Some(self.leading_options()),
+            PollType::Condorcet => {
+                Some(condorcet_winners(&self.pairwise, &self.withdrawn_options).winners)
+            },
+            PollType::RankedChoice | PollType::Budget => None,
+        };
+        if let Some(leaders) = leaders {
+            self.winner = match leaders[..] {
+                [] => None,
+                [leader] => Some(leader),
+                _ => match self.tie_break {
+                    TieBreak::Fail | TieBreak::Rerun => None,
+                    TieBreak::EarliestOption => Some(leaders[0]),
+                },
+            };
+            if leaders.len() > 1 {
+                self.tied_options = leaders;
+            }
+        }
+
+        let outcome = if !self.quorum_met() {
+            PollOutcome::QuorumNotMet
+        } else {
+            let tied = !self.tied_options.is_empty();
+            match self.winner {
+                _ if self.is_void => PollOutcome::Failed,
+                Some(winner) if self.is_proposal && winner != PROPOSAL_YES => PollOutcome::Failed,
+                Some(winner) if self.meets_approval_threshold(winner) => PollOutcome::Passed,
+                None if !self.elected.is_empty() => PollOutcome::Passed,
+                None if self.poll_type == PollType::Budget && self.participation() > 0 => {
+                    PollOutcome::Passed
+                },
+                None if tied && self.tie_break == TieBreak::Rerun => PollOutcome::Tied,
+                _ => PollOutcome::Failed,
+            }
+        };
+        self.outcome = Some(outcome);
+        outcome
+    }
+
+    /// Fails unless the poll uses `poll_type`.
+    pub fn ensure_poll_type(&self, poll_type: PollType) -> ProgramResult {
+        if self.poll_type != poll_type {
+            msg!("Poll is {:?}, not {:?}.", self.poll_type, poll_type);
+            return Err(VotingError::WrongPollType.into());
+        }
+        Ok(())
+    }
+
+    /// Fails once any ballot has been recorded.
+    ///
+    /// Every code path that changes `options` (labels, metadata, merges) must
+    /// call this first so existing ballots keep meaning what they meant.
+    /// Appending an option leaves existing indices alone and is exempt.
+    pub fn ensure_options_mutable(&self) -> ProgramResult {
+        if let Some(slot) = self.first_vote_slot {
+            msg!("Options are frozen since the first vote in slot {}.", slot);
+            return Err(VotingError::OptionsFrozen.into());
+        }
+        Ok(())
+    }
+}
+
+/// Proof that `voter` has voted on `poll`, stored in its own account.
+///
+/// The receipt address is derived from the poll and the voter, so the account
+/// existing at all is what blocks a second vote. A spoiled ballot records
+/// `SPOILED_BALLOT` as its option, and an abstention `ABSTAINED_BALLOT`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct VoteReceipt {
+    /// Poll the vote was cast on.
+    pub poll: Pubkey,
+    /// Wallet whose vote this is.
+    pub voter: Pubkey,
+    /// Option voted for.
+    pub option_index: u32,
+    /// Slot in which the vote was cast.
+    pub slot: u64,
+    /// Whether the voter accepted the poll's `terms_hash` when voting.
+    pub accepted_terms: bool,
+    /// Wallet that cast the vote for `voter`: their delegate, or the voter
+    /// their delegation chain leads to. `None` if the voter cast it.
+    pub delegate: Option<Pubkey>,
+    /// Delegators' ballots this vote also cast, each in a receipt naming
+    /// `voter` as its delegate. A vote that carried any cannot be changed.
+    pub delegated_ballots: u32,
+}
+
+/// A voter's ranking of a ranked-choice poll's options, stored in its own
+/// account.
+///
+/// Like a [`VoteReceipt`], the ballot's existence blocks a second vote.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct RankedBallot {
+    /// Poll the ballot was cast on.
+    pub poll: Pubkey,
+    /// Wallet that cast the ballot.
+    pub voter: Pubkey,
+    /// Option indices from most to least preferred; empty for a spoiled ballot
+    /// and `[ABSTAINED_BALLOT]` for an abstention.
+    pub ranking: Vec<u32>,
+    /// Slot in which the ballot was cast.
+    pub slot: u64,
+    /// Whether the voter accepted the poll's `terms_hash` when voting.
+    pub accepted_terms: bool,
+}
+
+/// The options a voter selected on a multi-select poll, stored in its own
+/// account at the ballot address.
+///
+/// Like a [`VoteReceipt`], the ballot's existence blocks a second vote.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct MultiBallot {
+    /// Poll the ballot was cast on.
+    pub poll: Pubkey,
+    /// Wallet that cast the ballot.
+    pub voter: Pubkey,
+    /// Distinct option indices selected; empty for a spoiled ballot and
+    /// `[ABSTAINED_BALLOT]` for an abstention.
+    pub selections: Vec<u32>,
+    /// Slot in which the ballot was cast.
+    pub slot: u64,
+    /// Whether the voter accepted the poll's `terms_hash` when voting.
+    pub accepted_terms: bool,
+}
+
+/// The points a voter gave each option on a cumulative poll, stored in its
+/// own account at the ballot address.
+///
+/// Like a [`VoteReceipt`], the ballot's existence blocks a second vote.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct CumulativeBallot {
+    /// Poll the ballot was cast on.
+    pub poll: Pubkey,
+    /// Wallet that cast the ballot.
+    pub voter: Pubkey,
+    /// Distinct option indices, each with the points given to it; empty for
+    /// a spoiled ballot and `[(ABSTAINED_BALLOT, 0)]` for an abstention.
+    pub allocations: Vec<(u32, u64)>,
+    /// Slot in which the ballot was cast.
+    pub slot: u64,
+    /// Whether the voter accepted the poll's `terms_hash` when voting.
+    pub accepted_terms: bool,
+}
+
+/// The fields of a token account a weighted vote reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBalance {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+impl TokenBalance {
+    /// Reads an initialized token account: mint, owner and amount lead the
+    /// account, and the state byte (1 initialized, 2 frozen) follows the
+    /// delegate.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != TOKEN_ACCOUNT_LEN || !matches!(data[108], 1 | 2) {
+            return None;
+        }
+        let pubkey = |range: std::ops::Range<usize>| {
+            Pubkey::new_from_array(data[range].try_into().expect("range is 32 bytes"))
+        };
+        Some(Self {
+            mint: pubkey(0..32),
+            owner: pubkey(32..64),
+            amount: u64::from_le_bytes(data[64..72].try_into().expect("range is 8 bytes")),
+        })
+    }
+}
+
+/// An attestor's signed result for a closed poll, stored in its own account.
+///
+/// The program checks that the attestor signed the submitting transaction,
+/// not the signature itself; relayers verify `signature` over
+/// `attestation.message()` against `attestor`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct AttestationRecord {
+    /// The attested result.
+    pub attestation: ResultAttestation,
+    /// Attestor that signed it.
+    pub attestor: Pubkey,
+    /// Attestor's signature over `attestation.message()`.
+    pub signature: [u8; 64],
+}
+
+/// A poll's readable tallies at one point in time, stored in its own account
+/// by `SnapshotResult` and never changed afterwards.
+///
+/// Ballots held back by a blackout are not readable yet, so they are not
+/// included.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResultSnapshot {
+    /// Poll the tallies belong to.
+    pub poll: Pubkey,
+    /// Label the snapshot was taken under, such as "midpoint".
+    pub label: String,
+    /// Slot the snapshot was taken in.
+    pub slot: u64,
+    /// Unix time the snapshot was taken at.
+    pub unix_timestamp: u64,
+    /// The poll's `vote_counts`.
+    pub vote_counts: Vec<u64>,
+    /// The poll's `vote_weights`, empty if unweighted.
+    pub vote_weights: Vec<u128>,
+    /// The poll's `spoiled_count`.
+    pub spoiled_count: u64,
+    /// The poll's `abstain_count`.
+    pub abstain_count: u64,
+}
+
+impl ResultSnapshot {
+    /// Snapshot of `poll`'s current tallies, taken at `slot` and `unix_timestamp`.
+    pub fn new(
+        poll_key: &Pubkey,
+        poll: &Poll,
+        label: String,
+        slot: u64,
+        unix_timestamp: u64,
+    ) -> Self {
+        Self {
+            poll: *poll_key,
+            label,
+            slot,
+            unix_timestamp,
+            vote_counts: poll.vote_counts.clone(),
+            vote_weights: poll.vote_weights.clone(),
+            spoiled_count: poll.spoiled_count,
+            abstain_count: poll.abstain_count,
+        }
+    }
+}
+
+/// A creator's registration of a question, stored in its own account so a
+/// second poll with the same question can be rejected.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct QuestionRecord {
+    /// Poll that registered the question.
+    pub poll: Pubkey,
+}
+
+/// A voter's registration with the program, created by `RegisterVoter`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VoterRecord {
+    /// Wallet that registered.
+    pub voter: Pubkey,
+    /// Unix time of registration.
+    pub registered_at: u64,
+    /// Slot of registration.
+    pub registered_slot: u64,
+    /// Polls the voter has cast a ballot on while the poll's eligibility rule
+    /// read this record.
+    pub polls_participated: u64,
+    /// Wallet the voter names to act for them; `None` for nobody.
+    pub delegate: Option<Pubkey>,
+}
+
+/// Program-wide settings, created by `InitializeConfig` and changed by their
+/// `admin`. Until it is created every setting reads as its default.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramConfig {
+    /// Wallet that may change the settings.
+    pub admin: Pubkey,
+    /// Whether only administrative instructions signed by `admin` run (see
+    /// `VotingInstruction::runs_when_read_only`); the rest fail with
+    /// `VotingError::ReadOnlyMode`.
+    pub read_only_mode: bool,
+    /// Instructions that may not be reached from just anywhere, by tag (the
+    /// first byte of their data), each at most once. Instructions missing
+    /// here are `CallPolicy::Any`.
+    pub call_policies: Vec<(u8, CallPolicy)>,
+    /// Programs that may call instructions under `CallPolicy::AllowlistedCpi`.
+    pub cpi_allowlist: Vec<Pubkey>,
+}
+
+impl ProgramConfig {
+    /// How the instruction tagged `tag` may be reached.
+    pub fn call_policy(&self, tag: u8) -> CallPolicy {
+        self.call_policies
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map_or(CallPolicy::Any, |(_, policy)| *policy)
+    }
+
+    /// Sets how the instruction tagged `tag` may be reached;
+    /// `CallPolicy::Any` drops its entry.
+    pub fn set_call_policy(&mut self, tag: u8, policy: CallPolicy) {
+        self.call_policies.retain(|(t, _)| *t != tag);
+        if policy != CallPolicy::Any {
+            self.call_policies.push((tag, policy));
+        }
+    }
+}
+
+/// How an instruction may be reached, checked before it runs.
+///
+/// Guarded instructions read the instructions sysvar, which callers pass
+/// after the instruction's own accounts, to find the transaction's top-level
+/// instruction, and the runtime's stack height: a call from another
+/// top-level program, or any call below the top level, came through CPI. A
+/// call through CPI comes straight from the top-level program only one level
+/// down, so `AllowlistedCpi` accepts no deeper calls, and no program an
+/// allowlisted one invokes can call in turn.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPolicy {
+    /// Callable at the top level or through CPI from any program.
+    Any,
+    /// Callable only as a top-level instruction, not through CPI.
+    TopLevelOnly,
+    /// Callable only through CPI made directly by a top-level program in
+    /// the config's `cpi_allowlist`.
+    AllowlistedCpi,
+}
@@ -0,0 +1,362 @@
+// src/stv.rs
+
+//! Single transferable vote for multi-seat ranked-choice polls.
+//!
+//! A ranked-choice poll with more than one seat elects that many options
+//! with [`single_transferable_vote`]. The count uses the Droop quota,
+//! `floor(valid / (seats + 1)) + 1` ballots, where `valid` counts the ballots
+//! ranking at least one option not withdrawn. Each ballot starts with its
+//! highest-ranked option at a value of one vote, and every round then does
+//! one of three things:
+//!
+//! * The option with the most votes, if it reaches the quota, is elected. It
+//!   keeps the quota, and every ballot it holds moves on to its next
+//!   preference still in the race at `surplus / total` of its value, so the
+//!   surplus is shared out in proportion to where those ballots go next.
+//! * Once the options still in the race are no more than the seats left, the
+//!   one with the most votes is elected without a transfer.
+//! * Otherwise the option with the fewest votes is excluded and its ballots
+//!   move on at their current value.
+//!
+//! A ballot with no preference left is exhausted. Values are fixed-point,
+//! [`VOTE_SCALE`] units to a ballot, and transfer values round down, so the
+//! count is the same wherever it runs at the cost of at most one unit per
+//! ballot and transfer. Ties for most votes go to the lowest index and ties
+//! for fewest votes exclude the highest index, as in `tally::instant_runoff`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// Units of value in one ballot.
+pub const VOTE_SCALE: u128 = 1_000_000;
+
+/// What one round of a count did.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum StvEvent {
+    /// `option` was elected, passing on `surplus` units of value.
+    Elected { option: u32, surplus: u128 },
+    /// `option` was excluded and its ballots passed on.
+    Excluded { option: u32 },
+}
+
+/// One round of a count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StvRound {
+    /// Value per option at the start of the round, in `VOTE_SCALE` units.
+    /// Elected options hold what they kept; excluded and withdrawn options
+    /// hold nothing.
+    pub tallies: Vec<u128>,
+    pub event: StvEvent,
+}
+
+/// Outcome of a count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StvResult {
+    /// The Droop quota in ballots; 0 if no ballot ranked an option in the
+    /// race.
+    pub quota: u64,
+    /// Elected options in the order they were elected. Fewer than the seats
+    /// only when fewer options stand, and empty without valid ballots.
+    pub elected: Vec<u32>,
+    pub rounds: Vec<StvRound>,
+}
+
+/// A count in progress: what its rounds have decided so far.
+///
+/// This is enough to find which option holds any ballot and at what value
+/// without keeping the ballots themselves, so a count can also run in
+/// passes over ballots streamed in batches: each round sums the value of
+/// every ballot by its holder and passes the sums to [`StvCount::count_round`].
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StvCount {
+    /// The Droop quota in ballots, set by the first round.
+    pub quota: u64,
+    /// Value each elected option kept, in `VOTE_SCALE` units.
+    pub kept: Vec<u128>,
+    /// What each round did, in order.
+    pub events: Vec<StvEvent>,
+    /// Whether every seat is filled, or no round is left to count.
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Standing {
+    Hopeful,
+    Elected,
+    Excluded,
+}
+
+/// First option in `ranking` still in the race.
+fn next_hopeful(ranking: &[u32], standing: &[Standing]) -> Option<usize> {
+    ranking
+        .iter()
+        .map(|&option| option as usize)
+        .find(|&option| standing.get(option) == Some(&Standing::Hopeful))
+}
+
+impl StvCount {
+    /// A count of `option_count` options with no round counted yet.
+    pub fn new(option_count: usize) -> Self {
+        Self { quota: 0, kept: vec![0; option_count], events: Vec::new(), finished: false }
+    }
+
+    /// Options elected so far, in the order they were elected.
+    pub fn elected(&self) -> Vec<u32> {
+        self.events
+            .iter()
+            .filter_map(|event| match *event {
+                StvEvent::Elected { option, .. } => Some(option),
+                StvEvent::Excluded { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Standing of each option before any round, with `withdrawn` out.
+    fn initial_standing(&self, withdrawn: &[u32]) -> Vec<Standing> {
+        let mut standing = vec![Standing::Hopeful; self.kept.len()];
+        for &option in withdrawn {
+            if let Some(standing) = standing.get_mut(option as usize) {
+                *standing = Standing::Excluded;
+            }
+        }
+        standing
+    }
+
+    /// The option holding a ballot ranking `ranking` and the ballot's value,
+    /// or `None` once it is exhausted.
+    ///
+    /// Replays the ballot through every round so far: it moves on when its
+    /// holder is excluded, and when its holder is elected, at `surplus /
+    /// total` of its value. An option elected without a transfer kept its
+    /// whole total and passes on a surplus of 0, so its ballots move on
+    /// worth nothing.
+    pub fn holder(&self, withdrawn: &[u32], ranking: &[u32]) -> Option<(usize, u128)> {
+        let mut standing = self.initial_standing(withdrawn);
+        let mut holder = next_hopeful(ranking, &standing);
+        let mut value = VOTE_SCALE;
+        for event in &self.events {
+            match *event {
+                StvEvent::Elected { option, surplus } => {
+                    let option = option as usize;
+                    standing[option] = Standing::Elected;
+                    if holder == Some(option) {
+                        let total = self.kept[option] + surplus;
+                        value = (value * surplus).checked_div(total).unwrap_or(0);
+                        holder = next_hopeful(ranking, &standing);
+                    }
+                },
+                StvEvent::Excluded { option } => {
+                    let option = option as usize;
+                    standing[option] = Standing::Excluded;
+                    if holder == Some(option) {
+                        holder = next_hopeful(ranking, &standing);
+                    }
+                },
+            }
+        }
+        holder.map(|holder| (holder, value))
+    }
+
+    /// Counts the next round for `seats` seats, from `values`, the value of
+    /// the ballots each option holds. Returns the round, or `None` once the
+    /// count is over.
+    ///
+    /// The first round sets the quota from the ballots it counts, since every
+    /// ballot with an option in the race is then worth one vote.
+    pub fn count_round(
+        &mut self,
+        seats: usize,
+        withdrawn: &[u32],
+        values: &[u128],
+    ) -> Option<StvRound> {
+        if self.finished {
+            return None;
+        }
+        if self.events.is_empty() {
+            let valid = (values.iter().sum::<u128>() / VOTE_SCALE) as u64;
+            if valid == 0 || seats == 0 {
+                self.finished = true;
+                return None;
+            }
+            self.quota = valid / (seats as u64 + 1) + 1;
+        }
+        let quota_value = u128::from(self.quota) * VOTE_SCALE;
+
+        let mut standing = self.initial_standing(withdrawn);
+        for event in &self.events {
+            match *event {
+                StvEvent::Elected { option, .. } => standing[option as usize] = Standing::Elected,
+                StvEvent::Excluded { option } => standing[option as usize] = Standing::Excluded,
+            }
+        }
+        let elected = self.elected().len();
+        let tallies: Vec<u128> =
+            self.kept.iter().zip(values).map(|(kept, value)| kept + value).collect();
+        let hopeful: Vec<usize> =
+            (0..self.kept.len()).filter(|&option| standing[option] == Standing::Hopeful).collect();
+        let leader = match hopeful
+            .iter()
+            .copied()
+            .max_by_key(|&option| (tallies[option], usize::MAX - option))
+        {
+            Some(leader) => leader,
+            None => {
+                self.finished = true;
+                return None;
+            },
+        };
+
+        let event = if hopeful.len() + elected <= seats {
+            // Every option left takes a seat, so nothing needs to move.
+            self.kept[leader] = tallies[leader];
+            StvEvent::Elected { option: leader as u32, surplus: 0 }
+        } else if tallies[leader] >= quota_value {
+            self.kept[leader] = quota_value;
+            StvEvent::Elected { option: leader as u32, surplus: tallies[leader] - quota_value }
+        } else {
+            let last = hopeful
+                .iter()
+                .copied()
+                .min_by_key(|&option| (tallies[option], usize::MAX - option))
+                .expect("the leader is hopeful");
+            StvEvent::Excluded { option: last as u32 }
+        };
+        self.events.push(event.clone());
+        if matches!(event, StvEvent::Elected { .. }) && elected + 1 == seats {
+            self.finished = true;
+        }
+        Some(StvRound { tallies, event })
+    }
+}
+
+/// Elects `seats` of `option_count` options by single transferable vote
+/// over distinct rankings, each paired with the number of ballots that cast
+/// it. Indices outside `0..option_count` are ignored, and options listed in
+/// `withdrawn` are out of the race from the first round.
+pub fn single_transferable_vote<B: AsRef<[u32]>>(
+    option_count: usize,
+    seats: usize,
+    withdrawn: &[u32],
+    groups: &[(B, u64)],
+) -> StvResult {
+    let mut count = StvCount::new(option_count);
+    let mut rounds = Vec::new();
+    loop {
+        let mut values = vec![0u128; option_count];
+        for (ranking, ballots) in groups {
+            if let Some((holder, value)) = count.holder(withdrawn, ranking.as_ref()) {
+                values[holder] += u128::from(*ballots) * value;
+            }
+        }
+        match count.count_round(seats, withdrawn, &values) {
+            Some(round) => rounds.push(round),
+            None => break,
+        }
+    }
+    StvResult { quota: count.quota, elected: count.elected(), rounds }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::Path};
+
+    /// A vote value in whole ballots, with up to six decimal places.
+    fn votes(value: u128) -> String {
+        let (whole, fraction) = (value / VOTE_SCALE, value % VOTE_SCALE);
+        if fraction == 0 {
+            return whole.to_string();
+        }
+        format!("{}.{:06}", whole, fraction).trim_end_matches('0').to_string()
+    }
+
+    /// Runs the election described by a golden file's input section and
+    /// renders the count.
+    ///
+    /// The input names the options, the seats and any withdrawn options,
+    /// then lists rankings as a ballot count followed by option names.
+    fn run_golden(input: &str) -> String {
+        let (mut options, mut seats, mut withdrawn, mut groups) =
+            (Vec::new(), 0, Vec::new(), Vec::new());
+        let index = |options: &[&str], name: &str| {
+            options.iter().position(|option| *option == name).expect("known option") as u32
+        };
+        for line in input.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut words = line.split_whitespace();
+            match words.next().expect("non-empty line") {
+                "options" => options = words.collect(),
+                "seats" => seats = words.next().expect("seat count").parse().expect("number"),
+                "withdrawn" => withdrawn = words.map(|name| index(&options, name)).collect(),
+                count => groups.push((
+                    words.map(|name| index(&options, name)).collect::<Vec<u32>>(),
+                    count.parse::<u64>().expect("ballot count"),
+                )),
+            }
+        }
+
+        let result = single_transferable_vote(options.len(), seats, &withdrawn, &groups);
+        let mut output = format!("quota {}\n", result.quota);
+        for (round, StvRound { tallies, event }) in result.rounds.iter().enumerate() {
+            let standings: Vec<String> = options
+                .iter()
+                .zip(tallies)
+                .map(|(option, &tally)| format!("{} {}", option, votes(tally)))
+                .collect();
+            let event = match *event {
+                StvEvent::Elected { option, surplus } => {
+                    format!("elect {}, surplus {}", options[option as usize], votes(surplus))
+                },
+                StvEvent::Excluded { option } => format!("exclude {}", options[option as usize]),
+            };
+            output += &format!("round {}: {} -> {}\n", round + 1, standings.join(", "), event);
+        }
+        let elected: Vec<&str> =
+            result.elected.iter().map(|&option| options[option as usize]).collect();
+        output + &format!("elected {}\n", elected.join(", "))
+    }
+
+    /// Checks every golden file in `testdata/stv`: the count of its input,
+    /// above the `---` line, must render as the expected output below it.
+    /// Run with `UPDATE_GOLDEN=1` to rewrite the expected outputs.
+    #[test]
+    fn test_golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/stv");
+        let mut checked = 0;
+        for entry in fs::read_dir(&dir).expect("golden directory") {
+            let path = entry.expect("directory entry").path();
+            let contents = fs::read_to_string(&path).expect("golden file");
+            let (input, expected) = contents.split_once("---\n").expect("separator line");
+            let actual = run_golden(input);
+            if env::var_os("UPDATE_GOLDEN").is_some() {
+                fs::write(&path, format!("{}---\n{}", input, actual)).expect("writable file");
+            } else {
+                assert_eq!(actual, expected, "{}", path.display());
+            }
+            checked += 1;
+        }
+        assert!(checked >= 3);
+    }
+
+    #[test]
+    fn test_stv_edge_cases() {
+        let empty: [(Vec<u32>, u64); 0] = [];
+        let result = single_transferable_vote(3, 2, &[], &empty);
+        assert_eq!(result, StvResult { quota: 0, elected: Vec::new(), rounds: Vec::new() });
+
+        // Only ballots for a withdrawn option or unknown indices.
+        let groups = [(vec![1, 7], 4)];
+        assert!(single_transferable_vote(3, 2, &[1], &groups).elected.is_empty());
+
+        // More seats than options standing elects every option standing,
+        // in order of their votes.
+        let groups = [(vec![2, 0], 3), (vec![0], 2)];
+        let result = single_transferable_vote(3, 3, &[1], &groups);
+        assert_eq!(result.elected, vec![2, 0]);
+        assert_eq!(result.quota, 2);
+
+        // One seat matches instant-runoff.
+        let groups = [(vec![0], 4), (vec![1, 2], 3), (vec![2, 1], 2)];
+        let result = single_transferable_vote(3, 1, &[], &groups);
+        let runoff = crate::tally::instant_runoff_grouped(3, &[], &groups);
+        assert_eq!(result.elected, vec![runoff.winner.unwrap()]);
+    }
+}
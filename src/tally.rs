@@ -0,0 +1,383 @@
+// src/tally.rs
+
+//! Counting rules for ranked ballots.
+//!
+//! These are pure functions over ballots so they can run both in the `Tally`
+//! instruction and in clients that want to show the rounds. Borda polls keep
+//! running scores as ballots are cast, using [`borda_points`];
+//! [`borda_count`] recomputes them from the ballots. Condorcet polls likewise
+//! keep a pairwise preference matrix, built with [`add_pairwise`], which
+//! [`condorcet_winners`] decides at finalization.
+
+/// Outcome of an instant-runoff count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunoffResult {
+    /// Winning option, or `None` if no ballot ranked any option.
+    pub winner: Option<u32>,
+    /// Votes per option in each round. Eliminated options count zero.
+    pub rounds: Vec<Vec<u64>>,
+}
+
+/// Runs instant-runoff voting over `ballots`, each a ranking of option
+/// indices from most to least preferred.
+///
+/// Each round counts every ballot for its highest-ranked option still in the
+/// race. An option with more than half of the ballots that still rank a
+/// remaining option wins; otherwise the option with the fewest votes is
+/// eliminated and the count repeats. Ties for last place eliminate the
+/// option with the highest index, so earlier-listed options survive.
+/// Indices outside `0..option_count` are ignored, and options listed in
+/// `withdrawn` are out of the race from the first round.
+pub fn instant_runoff<B: AsRef<[u32]>>(
+    option_count: usize,
+    withdrawn: &[u32],
+    ballots: &[B],
+) -> RunoffResult {
+    let groups: Vec<(&[u32], u64)> = ballots.iter().map(|ballot| (ballot.as_ref(), 1)).collect();
+    instant_runoff_grouped(option_count, withdrawn, &groups)
+}
+
+/// Runs `instant_runoff` over distinct rankings, each paired with the number
+/// of ballots that cast it.
+pub fn instant_runoff_grouped<B: AsRef<[u32]>>(
+    option_count: usize,
+    withdrawn: &[u32],
+    groups: &[(B, u64)],
+) -> RunoffResult {
+    let mut remaining = runoff_remaining(option_count, withdrawn, &[]);
+    let mut rounds = Vec::new();
+
+    loop {
+        let mut counts = vec![0u64; option_count];
+        for (ranking, ballots) in groups {
+            if let Some(option) = runoff_choice(ranking.as_ref(), &remaining) {
+                counts[option] += ballots;
+            }
+        }
+        let step = runoff_step(&counts, &remaining);
+        rounds.push(counts);
+        match step {
+            RunoffStep::Decided(winner) => return RunoffResult { winner, rounds },
+            RunoffStep::Eliminate(last) => remaining[last as usize] = false,
+        }
+    }
+}
+
+/// What one round of an instant-runoff count decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunoffStep {
+    /// The count is over, won by this option, or by none if no ballot ranks
+    /// an option still in the race.
+    Decided(Option<u32>),
+    /// This option is eliminated, and the count repeats without it.
+    Eliminate(u32),
+}
+
+/// Which of `option_count` options are still in an instant-runoff count
+/// once those in `withdrawn` and `eliminated` are out.
+pub fn runoff_remaining(option_count: usize, withdrawn: &[u32], eliminated: &[u32]) -> Vec<bool> {
+    let mut remaining = vec![true; option_count];
+    for &option in withdrawn.iter().chain(eliminated) {
+        if let Some(remaining) = remaining.get_mut(option as usize) {
+            *remaining = false;
+        }
+    }
+    remaining
+}
+
+/// The option a ballot ranking `ranking` counts for in an instant-runoff
+/// round: its highest-ranked option still `remaining`, if any.
+pub fn runoff_choice(ranking: &[u32], remaining: &[bool]) -> Option<usize> {
+    ranking
+        .iter()
+        .map(|&option| option as usize)
+        .find(|&option| remaining.get(option) == Some(&true))
+}
+
+/// Decides an instant-runoff round from `counts`, the ballots counting for
+/// each option, of which `remaining` are still in the race.
+pub fn runoff_step(counts: &[u64], remaining: &[bool]) -> RunoffStep {
+    let continuing: u64 = counts.iter().sum();
+    if continuing == 0 {
+        return RunoffStep::Decided(None);
+    }
+    let active: Vec<usize> = (0..counts.len()).filter(|&option| remaining[option]).collect();
+    let leader = active
+        .iter()
+        .copied()
+        .max_by_key(|&option| (counts[option], usize::MAX - option))
+        .expect("a continuing ballot ranks a remaining option");
+    if counts[leader] * 2 > continuing || active.len() == 1 {
+        return RunoffStep::Decided(Some(leader as u32));
+    }
+
+    // Ties for last place go to the highest index.
+    let last = active
+        .iter()
+        .copied()
+        .min_by_key(|&option| (counts[option], usize::MAX - option))
+        .expect("at least two options remain");
+    RunoffStep::Eliminate(last as u32)
+}
+
+/// Borda points for ranking position `position` on a ballot ranking `ranked`
+/// options: none for last place and one more for each place above it.
+pub fn borda_points(ranked: usize, position: usize) -> u128 {
+    ranked.saturating_sub(position + 1) as u128
+}
+
+/// Borda scores per option over `ballots`, each a ranking of option indices
+/// from most to least preferred. Indices outside `0..option_count` are
+/// ignored, but still take up their place in the ranking.
+pub fn borda_count<B: AsRef<[u32]>>(option_count: usize, ballots: &[B]) -> Vec<u128> {
+    let mut scores = vec![0u128; option_count];
+    for ballot in ballots {
+        let ranking = ballot.as_ref();
+        for (position, &option) in ranking.iter().enumerate() {
+            if let Some(score) = scores.get_mut(option as usize) {
+                *score += borda_points(ranking.len(), position);
+            }
+        }
+    }
+    scores
+}
+
+/// Adds one ballot ranking `ranking` (most preferred first) to `pairwise`,
+/// where `pairwise[a][b]` counts ballots preferring option `a` to option `b`.
+/// Each ranked option is preferred to every option ranked below it and to
+/// every option left unranked. Indices outside the matrix are ignored.
+pub fn add_pairwise(pairwise: &mut [Vec<u64>], ranking: &[u32]) {
+    let option_count = pairwise.len();
+    let mut above = vec![false; option_count];
+    for &option in ranking {
+        let option = option as usize;
+        if option >= option_count || above[option] {
+            continue;
+        }
+        above[option] = true;
+        for (other, count) in pairwise[option].iter_mut().enumerate() {
+            if !above[other] {
+                *count += 1;
+            }
+        }
+    }
+}
+
+/// Outcome of a Condorcet count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CondorcetResult {
+    /// The option that beats every other head to head, if there is one.
+    pub condorcet_winner: Option<u32>,
+    /// The winning options in index order: the Condorcet winner alone, or
+    /// without one, the Schulze winners. Empty if no ballot prefers any
+    /// option to another.
+    pub winners: Vec<u32>,
+}
+
+/// Decides a Condorcet count from its pairwise preference matrix, among the
+/// options not listed in `withdrawn`.
+///
+/// The Condorcet winner, preferred to each other option by more ballots
+/// than prefer that option to it, wins outright. When preferences cycle and
+/// there is none, the Schulze method decides: the strength of a path of
+/// pairwise wins is its weakest win, and an option wins if its strongest
+/// path to every other option is at least as strong as the reverse. There
+/// is always at least one Schulze winner, and several only when their paths
+/// tie exactly.
+pub fn condorcet_winners(pairwise: &[Vec<u64>], withdrawn: &[u32]) -> CondorcetResult {
+    let standing: Vec<usize> =
+        (0..pairwise.len()).filter(|&option| !withdrawn.contains(&(option as u32))).collect();
+    let beats = |a: usize, b: usize| pairwise[a][b] > pairwise[b][a];
+    let no_preferences =
+        standing.iter().all(|&a| standing.iter().all(|&b| a == b || pairwise[a][b] == 0));
+    if no_preferences {
+        return CondorcetResult { condorcet_winner: None, winners: Vec::new() };
+    }
+
+    let condorcet_winner = standing
+        .iter()
+        .copied()
+        .find(|&a| standing.iter().all(|&b| a == b || beats(a, b)))
+        .map(|winner| winner as u32);
+    if let Some(winner) = condorcet_winner {
+        return CondorcetResult { condorcet_winner, winners: vec![winner] };
+    }
+
+    // Strongest paths, by a widest-path Floyd-Warshall over the wins.
+    let option_count = pairwise.len();
+    let mut strength = vec![vec![0u64; option_count]; option_count];
+    for &a in &standing {
+        for &b in &standing {
+            if a != b && beats(a, b) {
+                strength[a][b] = pairwise[a][b];
+            }
+        }
+    }
+    for &via in &standing {
+        for &a in &standing {
+            for &b in &standing {
+                if a != via && b != via && a != b {
+                    let through = strength[a][via].min(strength[via][b]);
+                    strength[a][b] = strength[a][b].max(through);
+                }
+            }
+        }
+    }
+    let winners = standing
+        .iter()
+        .copied()
+        .filter(|&a| standing.iter().all(|&b| a == b || strength[a][b] >= strength[b][a]))
+        .map(|winner| winner as u32)
+        .collect();
+    CondorcetResult { condorcet_winner: None, winners }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_majority_in_first_round() {
+        let ballots = [vec![0, 1], vec![0], vec![1, 0]];
+        let result = instant_runoff(2, &[], &ballots);
+        assert_eq!(result.winner, Some(0));
+        assert_eq!(result.rounds, vec![vec![2, 1]]);
+    }
+
+    #[test]
+    fn test_transfers_after_elimination() {
+        // Option 2 is eliminated first and its ballots move to option 1.
+        let ballots = [vec![0], vec![0], vec![0], vec![1], vec![1], vec![2, 1], vec![2, 1]];
+        let result = instant_runoff(3, &[], &ballots);
+        assert_eq!(result.winner, Some(1));
+        assert_eq!(result.rounds, vec![vec![3, 2, 2], vec![3, 4, 0]]);
+    }
+
+    #[test]
+    fn test_exhausted_ballots_and_ties() {
+        // The ballot ranking only option 2 is exhausted once it is eliminated.
+        let ballots = [vec![0], vec![1], vec![2]];
+        let result = instant_runoff(3, &[], &ballots);
+        assert_eq!(result.rounds, vec![vec![1, 1, 1], vec![1, 1, 0], vec![1, 0, 0]]);
+        assert_eq!(result.winner, Some(0));
+
+        let empty: [Vec<u32>; 0] = [];
+        assert_eq!(
+            instant_runoff(2, &[], &empty),
+            RunoffResult { winner: None, rounds: vec![vec![0, 0]] }
+        );
+        // Out-of-range rankings are skipped.
+        assert_eq!(instant_runoff(2, &[], &[vec![5, 1]]).winner, Some(1));
+    }
+
+    #[test]
+    fn test_withdrawn_options_are_skipped() {
+        // Ballots ranking withdrawn option 0 first count for their next choice.
+        let ballots = [vec![0, 2], vec![0, 2], vec![1], vec![1], vec![2]];
+        let result = instant_runoff(3, &[0], &ballots);
+        assert_eq!(result.rounds, vec![vec![0, 2, 3]]);
+        assert_eq!(result.winner, Some(2));
+    }
+
+    #[test]
+    fn test_grouped_rankings_count_once_per_ballot() {
+        let ballots = [vec![0], vec![0], vec![0], vec![1], vec![1], vec![2, 1], vec![2, 1]];
+        let groups = [(vec![0], 3), (vec![1], 2), (vec![2, 1], 2)];
+        assert_eq!(instant_runoff_grouped(3, &[], &groups), instant_runoff(3, &[], &ballots));
+    }
+
+    #[test]
+    fn test_borda_count() {
+        // Memphis (0), Nashville (1), Chattanooga (2), Knoxville (3), with 42,
+        // 26, 15 and 17 voters putting each first. By hand: Memphis 42 * 3 =
+        // 126, Nashville 42 * 2 + 26 * 3 + 15 + 17 = 194, Chattanooga 42 +
+        // 26 * 2 + 15 * 3 + 17 * 2 = 173, Knoxville 26 + 15 * 2 + 17 * 3 = 107.
+        let mut ballots = Vec::new();
+        for (ranking, voters) in [
+            (vec![0, 1, 2, 3], 42),
+            (vec![1, 2, 3, 0], 26),
+            (vec![2, 3, 1, 0], 15),
+            (vec![3, 2, 1, 0], 17),
+        ] {
+            ballots.extend(std::iter::repeat_n(ranking, voters));
+        }
+        assert_eq!(borda_count(4, &ballots), vec![126, 194, 173, 107]);
+        // Instant runoff elects Knoxville from the same ballots.
+        assert_eq!(instant_runoff(4, &[], &ballots).winner, Some(3));
+
+        // A shorter ranking scores from its own length, and an unknown option
+        // still takes up its place.
+        assert_eq!(borda_count(3, &[vec![2, 0], vec![1, 5, 0]]), vec![0, 2, 1]);
+        assert_eq!((borda_points(3, 0), borda_points(3, 2), borda_points(0, 0)), (2, 0, 0));
+    }
+
+    /// Pairwise matrix of `groups` of identical rankings over `option_count`
+    /// options.
+    fn pairwise_of(option_count: usize, groups: &[(&[u32], u64)]) -> Vec<Vec<u64>> {
+        let mut pairwise = vec![vec![0; option_count]; option_count];
+        for (ranking, ballots) in groups {
+            for _ in 0..*ballots {
+                add_pairwise(&mut pairwise, ranking);
+            }
+        }
+        pairwise
+    }
+
+    #[test]
+    fn test_condorcet_winner() {
+        // The ballots of `test_borda_count`: Nashville (1) beats Memphis 58 to
+        // 42 and Chattanooga and Knoxville 68 to 32 each.
+        let groups: [(&[u32], u64); 4] =
+            [(&[0, 1, 2, 3], 42), (&[1, 2, 3, 0], 26), (&[2, 3, 1, 0], 15), (&[3, 2, 1, 0], 17)];
+        let pairwise = pairwise_of(4, &groups);
+        assert_eq!((pairwise[1][0], pairwise[0][1]), (58, 42));
+        assert_eq!((pairwise[1][2], pairwise[1][3]), (68, 68));
+        let result = condorcet_winners(&pairwise, &[]);
+        assert_eq!(result, CondorcetResult { condorcet_winner: Some(1), winners: vec![1] });
+
+        // Without Nashville, Chattanooga beats Knoxville 83 to 17 and Memphis
+        // 58 to 42.
+        assert_eq!(condorcet_winners(&pairwise, &[1]).condorcet_winner, Some(2));
+
+        // Unranked options lose to ranked ones and tie among themselves.
+        let pairwise = pairwise_of(3, &[(&[2], 1), (&[9, 1], 1)]);
+        assert_eq!(pairwise, vec![vec![0, 0, 0], vec![1, 0, 1], vec![1, 1, 0]]);
+        // Neither 1 nor 2 beats the other, so Schulze leaves them tied.
+        assert_eq!(condorcet_winners(&pairwise, &[]).winners, vec![1, 2]);
+        assert_eq!(condorcet_winners(&pairwise_of(3, &[]), &[]).winners, Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_schulze_fallback() {
+        // The 45-voter example from Schulze's paper, A to E as 0 to 4. No
+        // option beats every other, and by hand the strongest paths make E
+        // the only Schulze winner.
+        let groups: [(&[u32], u64); 8] = [
+            (&[0, 2, 1, 4, 3], 5),
+            (&[0, 3, 4, 2, 1], 5),
+            (&[1, 4, 3, 0, 2], 8),
+            (&[2, 0, 1, 4, 3], 3),
+            (&[2, 0, 4, 1, 3], 7),
+            (&[2, 1, 0, 3, 4], 2),
+            (&[3, 2, 4, 1, 0], 7),
+            (&[4, 1, 0, 3, 2], 8),
+        ];
+        let pairwise = pairwise_of(5, &groups);
+        assert_eq!(
+            pairwise,
+            vec![
+                vec![0, 20, 26, 30, 22],
+                vec![25, 0, 16, 33, 18],
+                vec![19, 29, 0, 17, 24],
+                vec![15, 12, 28, 0, 14],
+                vec![23, 27, 21, 31, 0],
+            ]
+        );
+        let result = condorcet_winners(&pairwise, &[]);
+        assert_eq!(result, CondorcetResult { condorcet_winner: None, winners: vec![4] });
+
+        // A three-way cycle with equal margins leaves all three tied.
+        let cycle: [(&[u32], u64); 3] = [(&[0, 1, 2], 1), (&[1, 2, 0], 1), (&[2, 0, 1], 1)];
+        assert_eq!(condorcet_winners(&pairwise_of(3, &cycle), &[]).winners, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,414 @@
+// src/test_utils.rs
+
+//! Fixtures for tests of this program and of programs built on it.
+//!
+//! Compiled for this crate's own tests and, for downstream crates, behind the
+//! `test-utils` feature.
+
+use arch_program::{
+    account_info::AccountInfo, clock::Clock, program_error::ProgramError, pubkey::Pubkey,
+};
+use borsh::BorshSerialize;
+
+use crate::{
+    eligibility::{Collection, EligibilityRule, NftMetadata},
+    merkle::Hash,
+    processor::TimeProvider,
+    state::{Poll, PollType, TieBreak, MAX_POLL_ACCOUNT_LEN, PROPOSAL_OPTIONS, TOKEN_ACCOUNT_LEN},
+    weight::WeightCurve,
+};
+
+/// Clock pinned to a fixed slot and unix time, for use with
+/// `process_instruction_with_clock`.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock {
+    pub slot: u64,
+    pub unix_timestamp: u64,
+}
+
+impl TimeProvider for FixedClock {
+    fn clock(&self, _clock_account: &AccountInfo) -> Result<Clock, ProgramError> {
+        Ok(Clock {
+            slot: self.slot,
+            unix_timestamp: self.unix_timestamp as i64,
+            ..Clock::default()
+        })
+    }
+}
+
+/// Data of an initialized token account holding `amount` of `mint` for `owner`.
+pub fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+    data[0..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1;
+    data
+}
+
+/// Data of a metadata account placing `mint` in `collection`.
+pub fn nft_metadata_data(mint: &Pubkey, collection: &Pubkey, verified: bool) -> Vec<u8> {
+    NftMetadata { mint: *mint, collection: Some(Collection { verified, key: *collection }) }
+        .try_to_vec()
+        .expect("serializing to a Vec cannot fail")
+}
+
+/// Data of the instructions sysvar for a transaction whose top-level
+/// instructions call `programs` in order, with no accounts or data, while
+/// instruction `current` runs.
+pub fn instructions_sysvar_data(programs: &[Pubkey], current: u16) -> Vec<u8> {
+    let mut data = (programs.len() as u16).to_le_bytes().to_vec();
+    let first = 2 + 2 * programs.len();
+    for index in 0..programs.len() {
+        data.extend_from_slice(&((first + index * 36) as u16).to_le_bytes());
+    }
+    for program in programs {
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(program.as_ref());
+        data.extend_from_slice(&0u16.to_le_bytes());
+    }
+    data.extend_from_slice(&current.to_le_bytes());
+    data
+}
+
+/// Writable, non-executable mock account owned by `owner`.
+pub fn account_info<'a>(
+    key: &'a Pubkey,
+    owner: &'a Pubkey,
+    is_signer: bool,
+    data: &'a mut [u8],
+) -> AccountInfo<'a> {
+    AccountInfo::new(key, is_signer, true, data, owner, false, 0)
+}
+
+/// Builds poll states for tests.
+///
+/// Starts from an open two-option poll ("Rust", "Go") running from
+/// 1_619_999_000 to 1_620_001_000 with no votes, created by `[1; 32]` and
+/// moderated by `[2; 32]`. Setting the options resets
+/// the vote counts to zero, so call `vote_counts` afterwards.
+pub struct PollBuilder {
+    poll: Poll,
+}
+
+impl PollBuilder {
+    pub fn new() -> Self {
+        Self {
+            poll: Poll {
+                creator: Pubkey::new_from_array([1u8; 32]),
+                question: "Best programming language?".to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                vote_counts: vec![0, 0],
+                start_time: 1_619_999_000,
+                end_time: 1_620_001_000,
+                is_closed: false,
+                voters: Vec::new(),
+                created_at_slot: 0,
+                created_at_time: 0,
+                first_vote_slot: None,
+                poll_id: 0,
+                is_cancelled: false,
+                moderator: Pubkey::new_from_array([2u8; 32]),
+                is_quarantined: false,
+                allow_new_options: false,
+                attestors: Vec::new(),
+                closed_at_slot: None,
+                poll_type: PollType::Plurality,
+                is_tallied: false,
+                winner: None,
+                candidates: Vec::new(),
+                withdrawn_options: Vec::new(),
+                spoiled_count: 0,
+                max_spoiled_bps: 0,
+                is_void: false,
+                weight_mint: None,
+                vote_weights: Vec::new(),
+                blackout_secs: 0,
+                pending_counts: Vec::new(),
+                pending_weights: Vec::new(),
+                max_selections: 0,
+                abstain_count: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: false,
+                quorum: None,
+                outcome: None,
+                approval_threshold_bps: 0,
+                tied_options: Vec::new(),
+                tie_break: TieBreak::Fail,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                pairwise: Vec::new(),
+                seats: 0,
+                elected: Vec::new(),
+                is_proposal: false,
+                clock_tolerance_secs: 0,
+            },
+        }
+    }
+
+    pub fn creator(mut self, creator: Pubkey) -> Self {
+        self.poll.creator = creator;
+        self
+    }
+
+    pub fn question(mut self, question: &str) -> Self {
+        self.poll.question = question.to_string();
+        self
+    }
+
+    /// Sets the option labels, with a zero count for each.
+    pub fn options(mut self, options: &[&str]) -> Self {
+        self.poll.options = options.iter().map(|option| option.to_string()).collect();
+        self.poll.vote_counts = vec![0; options.len()];
+        self
+    }
+
+    pub fn vote_counts(mut self, vote_counts: &[u64]) -> Self {
+        self.poll.vote_counts = vote_counts.to_vec();
+        self
+    }
+
+    /// Sets the voting window (unix timestamps in seconds).
+    pub fn window(mut self, start_time: u64, end_time: u64) -> Self {
+        self.poll.start_time = start_time;
+        self.poll.end_time = end_time;
+        self
+    }
+
+    pub fn closed(mut self) -> Self {
+        self.poll.is_closed = true;
+        self
+    }
+
+    pub fn cancelled(mut self) -> Self {
+        self.poll.is_cancelled = true;
+        self
+    }
+
+    pub fn moderator(mut self, moderator: Pubkey) -> Self {
+        self.poll.moderator = moderator;
+        self
+    }
+
+    pub fn quarantined(mut self) -> Self {
+        self.poll.is_quarantined = true;
+        self
+    }
+
+    pub fn allow_new_options(mut self) -> Self {
+        self.poll.allow_new_options = true;
+        self
+    }
+
+    /// Sets the poll type, with no score yet on Borda polls and no
+    /// preferences yet on Condorcet polls.
+    pub fn poll_type(mut self, poll_type: PollType) -> Self {
+        self.poll.poll_type = poll_type;
+        let option_count = self.poll.options.len();
+        match poll_type {
+            PollType::Borda | PollType::Budget => self.poll.vote_weights = vec![0; option_count],
+            PollType::Condorcet => self.poll.pairwise = vec![vec![0; option_count]; option_count],
+            _ => {},
+        }
+        self
+    }
+
+    /// Sets the pairwise preference matrix; call after `poll_type`.
+    pub fn pairwise(mut self, pairwise: &[&[u64]]) -> Self {
+        self.poll.pairwise = pairwise.iter().map(|row| row.to_vec()).collect();
+        self
+    }
+
+    /// Sets the candidate key of each option, by index.
+    pub fn candidates(mut self, candidates: &[Option<Pubkey>]) -> Self {
+        self.poll.candidates = candidates.to_vec();
+        self
+    }
+
+    pub fn withdrawn_options(mut self, withdrawn_options: &[u32]) -> Self {
+        self.poll.withdrawn_options = withdrawn_options.to_vec();
+        self
+    }
+
+    pub fn spoiled_count(mut self, spoiled_count: u64) -> Self {
+        self.poll.spoiled_count = spoiled_count;
+        self
+    }
+
+    /// Sets the spoiled share, in basis points, above which closing voids the poll.
+    pub fn max_spoiled_bps(mut self, max_spoiled_bps: u16) -> Self {
+        self.poll.max_spoiled_bps = max_spoiled_bps;
+        self
+    }
+
+    pub fn abstain_count(mut self, abstain_count: u64) -> Self {
+        self.poll.abstain_count = abstain_count;
+        self
+    }
+
+    /// Weighs votes by records of the weight plugin `program`, with no
+    /// weight cast yet.
+    pub fn weight_program(mut self, program: Pubkey) -> Self {
+        self.poll.weight_program = Some(program);
+        self.poll.vote_weights = vec![0; self.poll.options.len()];
+        self
+    }
+
+    /// Requires voters to accept terms hashing to `terms_hash`.
+    pub fn terms_hash(mut self, terms_hash: Hash) -> Self {
+        self.poll.terms_hash = Some(terms_hash);
+        self
+    }
+
+    /// Weighs votes by balance of `mint`, with no weight cast yet.
+    pub fn weight_mint(mut self, mint: Pubkey) -> Self {
+        self.poll.weight_mint = Some(mint);
+        self.poll.vote_weights = vec![0; self.poll.options.len()];
+        self
+    }
+
+    /// Sets the weight cast per option; call after `weight_mint` or
+    /// `weight_program`.
+    pub fn vote_weights(mut self, vote_weights: &[u128]) -> Self {
+        self.poll.vote_weights = vote_weights.to_vec();
+        self
+    }
+
+    /// Holds back ballots cast in the last `blackout_secs` of the window.
+    pub fn blackout_secs(mut self, blackout_secs: u64) -> Self {
+        self.poll.blackout_secs = blackout_secs;
+        self
+    }
+
+    /// Lets each ballot select up to `max_selections` options.
+    pub fn max_selections(mut self, max_selections: u8) -> Self {
+        self.poll.max_selections = max_selections;
+        self
+    }
+
+    /// Marks the question as registered, so it may not change.
+    pub fn unique_question(mut self) -> Self {
+        self.poll.unique_question = true;
+        self
+    }
+
+    /// Sets the participation `FinalizePoll` requires.
+    pub fn quorum(mut self, quorum: u64) -> Self {
+        self.poll.quorum = Some(quorum);
+        self
+    }
+
+    /// Sets the share of the votes, in basis points, the leader needs to pass.
+    pub fn approval_threshold_bps(mut self, approval_threshold_bps: u16) -> Self {
+        self.poll.approval_threshold_bps = approval_threshold_bps;
+        self
+    }
+
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.poll.tie_break = tie_break;
+        self
+    }
+
+    /// Sets who may cast ballots.
+    pub fn eligibility(mut self, eligibility: EligibilityRule) -> Self {
+        self.poll.eligibility = eligibility;
+        self
+    }
+
+    /// Counts delegators' ballots along chains of up to `depth` steps.
+    pub fn max_delegation_depth(mut self, depth: u8) -> Self {
+        self.poll.max_delegation_depth = depth;
+        self
+    }
+
+    /// Sets the floor, cap and transform of vote weights.
+    pub fn weight_curve(mut self, weight_curve: WeightCurve) -> Self {
+        self.poll.weight_curve = weight_curve;
+        self
+    }
+
+    /// Makes the poll cumulative, giving each voter `points` to distribute,
+    /// with no points cast yet.
+    pub fn points_per_voter(mut self, points: u64) -> Self {
+        self.poll.points_per_voter = points;
+        self.poll.vote_weights = vec![0; self.poll.options.len()];
+        self
+    }
+
+    /// Makes the poll a yes/no proposal, with no votes cast yet.
+    pub fn proposal(mut self) -> Self {
+        self.poll.options = PROPOSAL_OPTIONS.iter().map(|option| option.to_string()).collect();
+        self.poll.vote_counts = vec![0; PROPOSAL_OPTIONS.len()];
+        self.poll.is_proposal = true;
+        self
+    }
+
+    /// Sets the number of options the poll elects.
+    pub fn seats(mut self, seats: u8) -> Self {
+        self.poll.seats = seats;
+        self
+    }
+
+    /// Widens every voting-window boundary by `secs` seconds.
+    pub fn clock_tolerance(mut self, secs: u64) -> Self {
+        self.poll.clock_tolerance_secs = secs;
+        self
+    }
+
+    pub fn attestors(mut self, attestors: &[Pubkey]) -> Self {
+        self.poll.attestors = attestors.to_vec();
+        self
+    }
+
+    /// Marks the poll closed in `slot`.
+    pub fn closed_at_slot(mut self, slot: u64) -> Self {
+        self.poll.is_closed = true;
+        self.poll.closed_at_slot = Some(slot);
+        self
+    }
+
+    /// Sets the legacy inline voter list.
+    pub fn voters(mut self, voters: &[Pubkey]) -> Self {
+        self.poll.voters = voters.to_vec();
+        self
+    }
+
+    pub fn first_vote_slot(mut self, slot: u64) -> Self {
+        self.poll.first_vote_slot = Some(slot);
+        self
+    }
+
+    pub fn poll_id(mut self, poll_id: u64) -> Self {
+        self.poll.poll_id = poll_id;
+        self
+    }
+
+    pub fn build(self) -> Poll {
+        self.poll
+    }
+
+    /// Serialized poll padded to `MAX_POLL_ACCOUNT_LEN`, the size `CreatePoll`
+    /// allocates.
+    pub fn account_data(&self) -> Vec<u8> {
+        self.account_data_with_len(MAX_POLL_ACCOUNT_LEN)
+    }
+
+    /// Serialized poll padded to `len` bytes.
+    ///
+    /// Panics if the poll does not fit.
+    pub fn account_data_with_len(&self, len: usize) -> Vec<u8> {
+        let mut data = self.poll.try_to_vec().expect("serializing to a Vec cannot fail");
+        assert!(data.len() <= len, "poll needs {} bytes, got {}", data.len(), len);
+        data.resize(len, 0);
+        data
+    }
+}
+
+impl Default for PollBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,458 @@
+// src/vectors.rs
+
+//! Canonical test vectors for client implementations.
+//!
+//! Each vector pairs an input built from the fixed keys below with what this
+//! crate produces for it: instruction data, program addresses, hashes and
+//! the commitments voters and attestors rely on. Byte strings are lower-case
+//! hex. Clients written against this program in other languages or crates
+//! can check their encoders against these values, and this crate's own tests
+//! check every vector against the code, so a change to any wire format shows
+//! up here first.
+
+use arch_program::pubkey::Pubkey;
+
+use crate::{
+    attestation::{ResultAttestation, ATTESTATION_VERSION},
+    cursor::{CursorTask, CURSOR_SEED},
+    eligibility::EligibilityRule,
+    instruction::VotingInstruction,
+    state::{
+        PollType, TieBreak, ATTESTATION_SEED, BALLOT_SEED, CONFIG_SEED, POLL_SEED, QUESTION_SEED,
+        RECEIPT_SEED, SNAPSHOT_SEED, VOTER_SEED,
+    },
+    weight::WeightCurve,
+};
+
+/// Program id the address vectors are derived under.
+pub const PROGRAM_ID: Pubkey = Pubkey::new_from_array([0x11; 32]);
+/// Creator of the vector poll.
+pub const CREATOR: Pubkey = Pubkey::new_from_array([0x01; 32]);
+/// Voter in the ballot and receipt vectors.
+pub const VOTER: Pubkey = Pubkey::new_from_array([0x03; 32]);
+/// Attestor in the attestation vectors.
+pub const ATTESTOR: Pubkey = Pubkey::new_from_array([0x05; 32]);
+/// Id of the vector poll.
+pub const POLL_ID: u64 = 7;
+/// Question of the vector poll.
+pub const QUESTION: &str = "Best programming language?";
+/// Address of the vector poll, `find_poll_address(PROGRAM_ID, CREATOR,
+/// POLL_ID)`.
+pub const POLL: Pubkey = Pubkey::new_from_array([
+    0x22, 0x79, 0x42, 0xa1, 0x10, 0x52, 0x04, 0xa8, 0x9c, 0xda, 0x87, 0xb2, 0x0a, 0x40, 0x78, 0x7f,
+    0x59, 0x80, 0x13, 0x5b, 0x6c, 0x19, 0xca, 0x07, 0x5e, 0x72, 0x7f, 0x3c, 0xa8, 0xe2, 0x02, 0x4c,
+]);
+
+/// Borsh-serialized instruction data.
+#[derive(Debug, PartialEq)]
+pub struct InstructionVector {
+    pub name: &'static str,
+    pub instruction: VotingInstruction,
+    /// Instruction data, in hex.
+    pub data: &'static str,
+}
+
+/// A program address under `PROGRAM_ID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressVector {
+    pub name: &'static str,
+    /// Seeds, without the bump seed.
+    pub seeds: Vec<Vec<u8>>,
+    /// Derived address, in hex.
+    pub address: &'static str,
+    /// Bump seed found by `find_program_address`.
+    pub bump: u8,
+}
+
+/// A SHA-256 hash the program computes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashVector {
+    pub name: &'static str,
+    /// Exact bytes hashed, domain tags included, in hex.
+    pub preimage: &'static str,
+    /// SHA-256 of `preimage`, in hex.
+    pub hash: &'static str,
+}
+
+/// An allowlist commitment: the Merkle root polls store and the proof a
+/// member passes with their ballot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowlistVector {
+    /// Members, each committed as `merkle::hash_leaf` of their key.
+    pub members: Vec<Pubkey>,
+    /// Root of the standard tree over the members' leaves, in hex.
+    pub root: &'static str,
+    /// Proof for `members[0]`, siblings from the leaf up, in hex.
+    pub proof: Vec<&'static str>,
+}
+
+/// A result attestation and the message attestors sign for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationVector {
+    pub attestation: ResultAttestation,
+    /// `ResultAttestation::message`, in hex.
+    pub message: &'static str,
+}
+
+/// Instruction data vectors, one per encoding feature: a plain enum tag,
+/// integers, options, vectors, strings and nested enums.
+pub fn instructions() -> Vec<InstructionVector> {
+    vec![
+        InstructionVector {
+            name: "get_version",
+            instruction: VotingInstruction::GetVersion,
+            data: "03",
+        },
+        InstructionVector {
+            name: "close_poll",
+            instruction: VotingInstruction::ClosePoll,
+            data: "02",
+        },
+        InstructionVector {
+            name: "vote",
+            instruction: VotingInstruction::Vote {
+                option_index: 1,
+                accept_terms: true,
+                eligibility_proof: vec![[0xAB; 32]],
+            },
+            data: concat!(
+                "01010000000101000000abababababababababababababababababababababab",
+                "abababababababababab",
+            ),
+        },
+        InstructionVector {
+            name: "ranked_vote",
+            instruction: VotingInstruction::RankedVote {
+                ranking: vec![2, 0, 1],
+                accept_terms: false,
+                eligibility_proof: Vec::new(),
+            },
+            data: "0b030000000200000000000000010000000000000000",
+        },
+        InstructionVector {
+            name: "vote_cumulative",
+            instruction: VotingInstruction::VoteCumulative {
+                allocations: vec![(0, 7), (1, 3)],
+                accept_terms: false,
+                eligibility_proof: Vec::new(),
+            },
+            data: concat!(
+                "1702000000000000000700000000000000010000000300000000000000000000",
+                "0000",
+            ),
+        },
+        InstructionVector {
+            name: "create_poll",
+            instruction: VotingInstruction::CreatePoll {
+                poll_id: POLL_ID,
+                question: QUESTION.to_string(),
+                options: vec!["Rust".to_string(), "Go".to_string()],
+                start_time: 1_620_000_000,
+                end_time: 1_620_086_400,
+                moderator: CREATOR,
+                allow_new_options: false,
+                attestors: vec![ATTESTOR],
+                poll_type: PollType::Plurality,
+                candidates: Vec::new(),
+                max_spoiled_bps: 0,
+                weight_mint: None,
+                blackout_secs: 0,
+                max_selections: 0,
+                terms_hash: None,
+                weight_program: None,
+                unique_question: true,
+                quorum: Some(10),
+                approval_threshold_bps: 5_001,
+                tie_break: TieBreak::EarliestOption,
+                eligibility: EligibilityRule::Open,
+                max_delegation_depth: 0,
+                weight_curve: WeightCurve::LINEAR,
+                points_per_voter: 0,
+                seats: 0,
+                clock_tolerance_secs: 0,
+            },
+            data: concat!(
+                "0007000000000000001a000000426573742070726f6772616d6d696e67206c61",
+                "6e67756167653f02000000040000005275737402000000476f003d8f60000000",
+                "00808e9060000000000101010101010101010101010101010101010101010101",
+                "0101010101010101010001000000050505050505050505050505050505050505",
+                "0505050505050505050505050505000000000000000000000000000000000000",
+                "0001010a00000000000000891301000000000000000000000000000000000000",
+                "0000000000000000000000",
+            ),
+        },
+        InstructionVector {
+            name: "create_proposal",
+            instruction: VotingInstruction::CreateProposal {
+                poll_id: POLL_ID,
+                question: QUESTION.to_string(),
+                start_time: 1_620_000_000,
+                end_time: 1_620_086_400,
+                quorum: Some(10),
+                approval_threshold_bps: 5_001,
+            },
+            data: concat!(
+                "1807000000000000001a000000426573742070726f6772616d6d696e67206c61",
+                "6e67756167653f003d8f6000000000808e906000000000010a00000000000000",
+                "8913",
+            ),
+        },
+    ]
+}
+
+/// Address vectors for every kind of program account, derived from the
+/// vector poll, `VOTER` and `ATTESTOR`.
+pub fn addresses() -> Vec<AddressVector> {
+    let seeds = |parts: &[&[u8]]| parts.iter().map(|part| part.to_vec()).collect();
+    let question_hash = crate::state::question_hash(QUESTION);
+    vec![
+        AddressVector {
+            name: "poll",
+            seeds: seeds(&[POLL_SEED, CREATOR.as_ref(), &POLL_ID.to_le_bytes()]),
+            address: "227942a1105204a89cda87b20a40787f5980135b6c19ca075e727f3ca8e2024c",
+            bump: 249,
+        },
+        AddressVector {
+            name: "receipt",
+            seeds: seeds(&[RECEIPT_SEED, POLL.as_ref(), VOTER.as_ref()]),
+            address: "c164f75adf40588a793b0eb34e1c25c8bd02cae6607e733abd31c7c9d0502498",
+            bump: 255,
+        },
+        AddressVector {
+            name: "ballot",
+            seeds: seeds(&[BALLOT_SEED, POLL.as_ref(), VOTER.as_ref()]),
+            address: "1934f64cc47ba854104845c78ccf2fbf1c0b30461811a32b575da1f67fb29fee",
+            bump: 254,
+        },
+        AddressVector {
+            name: "attestation",
+            seeds: seeds(&[ATTESTATION_SEED, POLL.as_ref(), ATTESTOR.as_ref()]),
+            address: "7cdb56ffd3f9a1799cf0804dfec9fceaab9a616b646c1960cbe1dccc7232135b",
+            bump: 255,
+        },
+        AddressVector {
+            name: "snapshot",
+            seeds: seeds(&[SNAPSHOT_SEED, POLL.as_ref(), b"midpoint"]),
+            address: "811d16d0f14ed0478450640098245c8ce67687a4e1e57b52dbec0497bd099eb1",
+            bump: 254,
+        },
+        AddressVector {
+            name: "question",
+            seeds: seeds(&[QUESTION_SEED, CREATOR.as_ref(), &question_hash]),
+            address: "c83db19a027b6fc54ea55cb6eb6258bd37be6cd4acaf4b5d413c7232522a9181",
+            bump: 254,
+        },
+        AddressVector {
+            name: "voter_record",
+            seeds: seeds(&[VOTER_SEED, VOTER.as_ref()]),
+            address: "3868aa0278a39e1fe690a4ce57d5187601c89858849c926b62ed1aebf1ad7f05",
+            bump: 253,
+        },
+        AddressVector {
+            name: "tally_cursor",
+            seeds: seeds(&[CURSOR_SEED, POLL.as_ref(), &[CursorTask::Tally.seed()]]),
+            address: "e5e79bc8c139ef57fd7530e02b7be6469eacf52d0bb41cbe91018db3be1b688c",
+            bump: 252,
+        },
+        AddressVector {
+            name: "config",
+            seeds: seeds(&[CONFIG_SEED]),
+            address: "cb4fbd8643f848851e83bc3859dacdc1e5292fb0871cfe46f7fe0f80b3a640b6",
+            bump: 255,
+        },
+    ]
+}
+
+/// Hash vectors: a question hash, an allowlist leaf and node, and a result
+/// hash.
+pub fn hashes() -> Vec<HashVector> {
+    vec![
+        // `state::question_hash(QUESTION)`.
+        HashVector {
+            name: "question",
+            preimage: "426573742070726f6772616d6d696e67206c616e67756167653f",
+            hash: "572ba0fc475a0a3e5534847088aed3f7516265a9dd17198584624cbc6f85fa80",
+        },
+        // `merkle::hash_leaf(VOTER)`.
+        HashVector {
+            name: "allowlist_leaf",
+            preimage: concat!(
+                "0003030303030303030303030303030303030303030303030303030303030303",
+                "03",
+            ),
+            hash: "acaa04663a8547a2f70c60cc18f9378796b13c4f9a08f70d6adae662365b30c6",
+        },
+        // `merkle::hash_node` of `[0x22; 32]` and `[0x33; 32]`.
+        HashVector {
+            name: "merkle_node",
+            preimage: concat!(
+                "0122222222222222222222222222222222222222222222222222222222222222",
+                "2233333333333333333333333333333333333333333333333333333333333333",
+                "33",
+            ),
+            hash: "0fa1595eca476fc8e07193e15f27e6c442d948365b58526faa8764f759d29ccb",
+        },
+        // `attestation::result_hash` of a poll asking `QUESTION`, with
+        // options "Rust" and "Go" counted 3 and 1 and no weights.
+        HashVector {
+            name: "result",
+            preimage: concat!(
+                "766f74696e672d726573756c741a000000426573742070726f6772616d6d696e",
+                "67206c616e67756167653f02000000040000005275737402000000476f020000",
+                "000300000000000000010000000000000000000000",
+            ),
+            hash: "9c0e6034f077932fc8a1b51ea844a33b60a61b108d6dacceacebb79fd1e8f448",
+        },
+    ]
+}
+
+/// An allowlist of `VOTER`, `CREATOR` and `ATTESTOR`.
+pub fn allowlist() -> AllowlistVector {
+    AllowlistVector {
+        members: vec![VOTER, CREATOR, ATTESTOR],
+        root: "038cee0d27e40fb9daa903f788a4ab57a732a470788970ebcb6504e57f621663",
+        proof: vec![
+            "dcffe786ded16d283c663846ad0c4ff26558fccde36ca9d30b2ea19eade9fc0e",
+            "f3ab555d06a67b08ab25039fdbe2a6fcb305c83bc165492ce81d3dea13ec1fbf",
+        ],
+    }
+}
+
+/// The attestation of the `result` hash vector for the vector poll, closed
+/// in slot 90_000_000.
+pub fn attestation() -> AttestationVector {
+    AttestationVector {
+        attestation: ResultAttestation {
+            version: ATTESTATION_VERSION,
+            program_id: PROGRAM_ID,
+            poll: POLL,
+            result_hash: [
+                0x9c, 0x0e, 0x60, 0x34, 0xf0, 0x77, 0x93, 0x2f, 0xc8, 0xa1, 0xb5, 0x1e, 0xa8, 0x44,
+                0xa3, 0x3b, 0x60, 0xa6, 0x1b, 0x10, 0x8d, 0x6d, 0xac, 0xce, 0xac, 0xeb, 0xb7, 0x9f,
+                0xd1, 0xe8, 0xf4, 0x48,
+            ],
+            slot: 90_000_000,
+        },
+        message: concat!(
+            "0111111111111111111111111111111111111111111111111111111111111111",
+            "11227942a1105204a89cda87b20a40787f5980135b6c19ca075e727f3ca8e202",
+            "4c9c0e6034f077932fc8a1b51ea844a33b60a61b108d6dacceacebb79fd1e8f4",
+            "48804a5d0500000000",
+        ),
+    }
+}
+
+/// Decodes a hex vector field, or returns `None` if it is not hex.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        attestation::result_hash,
+        cursor::find_cursor_address,
+        merkle::{compute_root, hash_leaf, hash_node, verify_proof, Hash},
+        state::{
+            find_attestation_address, find_ballot_address, find_config_address, find_poll_address,
+            find_question_address, find_receipt_address, find_snapshot_address,
+            find_voter_record_address, question_hash,
+        },
+        test_utils::PollBuilder,
+    };
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_instruction_vectors() {
+        for vector in instructions() {
+            let data = vector.instruction.try_to_vec().unwrap();
+            assert_eq!(to_hex(&data), vector.data, "{}", vector.name);
+            let decoded = decode_hex(vector.data).unwrap();
+            assert_eq!(VotingInstruction::try_from_slice(&decoded).unwrap(), vector.instruction);
+        }
+    }
+
+    #[test]
+    fn test_address_vectors() {
+        let (poll, _) = find_poll_address(&PROGRAM_ID, &CREATOR, POLL_ID);
+        assert_eq!(poll, POLL);
+        let (cursor, _) = find_cursor_address(&PROGRAM_ID, &POLL, CursorTask::Tally);
+        let derived = [
+            find_poll_address(&PROGRAM_ID, &CREATOR, POLL_ID),
+            find_receipt_address(&PROGRAM_ID, &POLL, &VOTER),
+            find_ballot_address(&PROGRAM_ID, &POLL, &VOTER),
+            find_attestation_address(&PROGRAM_ID, &POLL, &ATTESTOR),
+            find_snapshot_address(&PROGRAM_ID, &POLL, "midpoint"),
+            find_question_address(&PROGRAM_ID, &CREATOR, &question_hash(QUESTION)),
+            find_voter_record_address(&PROGRAM_ID, &VOTER),
+            find_cursor_address(&PROGRAM_ID, &POLL, CursorTask::Tally),
+            find_config_address(&PROGRAM_ID),
+        ];
+        assert_eq!(derived[7].0, cursor);
+        let vectors = addresses();
+        assert_eq!(vectors.len(), derived.len());
+        for (vector, (address, bump)) in vectors.iter().zip(derived) {
+            assert_eq!((to_hex(address.as_ref()), bump), (vector.address.to_string(), vector.bump));
+            // The seeds alone reproduce the address.
+            let mut seeds: Vec<&[u8]> = vector.seeds.iter().map(Vec::as_slice).collect();
+            let bump_seed = [vector.bump];
+            seeds.push(&bump_seed);
+            assert_eq!(Pubkey::create_program_address(&seeds, &PROGRAM_ID), Ok(address));
+        }
+    }
+
+    #[test]
+    fn test_hash_vectors() {
+        let poll = PollBuilder::new().vote_counts(&[3, 1]).build();
+        let computed: [Hash; 4] = [
+            question_hash(QUESTION),
+            hash_leaf(VOTER.as_ref()),
+            hash_node(&[0x33; 32], &[0x22; 32]),
+            result_hash(&poll),
+        ];
+        let vectors = hashes();
+        assert_eq!(vectors.len(), computed.len());
+        for (vector, hash) in vectors.iter().zip(computed) {
+            let digest: Hash = Sha256::digest(decode_hex(vector.preimage).unwrap()).into();
+            assert_eq!(to_hex(&digest), vector.hash, "{}", vector.name);
+            assert_eq!(to_hex(&hash), vector.hash, "{}", vector.name);
+        }
+    }
+
+    #[test]
+    fn test_commitment_vectors() {
+        let vector = allowlist();
+        let leaves: Vec<Hash> =
+            vector.members.iter().map(|member| hash_leaf(member.as_ref())).collect();
+        let root = compute_root(&leaves).unwrap();
+        assert_eq!(to_hex(&root), vector.root);
+        let proof: Vec<Hash> = vector
+            .proof
+            .iter()
+            .map(|sibling| decode_hex(sibling).unwrap().try_into().unwrap())
+            .collect();
+        assert!(verify_proof(&root, &leaves[0], &proof));
+
+        let vector = attestation();
+        assert_eq!(to_hex(&vector.attestation.message()), vector.message);
+        let result = hashes().into_iter().find(|hash| hash.name == "result").unwrap();
+        assert_eq!(to_hex(&vector.attestation.result_hash), result.hash);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff1a"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(decode_hex("0"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+}
@@ -0,0 +1,351 @@
+// src/verify.rs
+
+//! Independent recount of a poll from its per-voter records.
+//!
+//! Observers fetch a poll and every `VoteReceipt` or `RankedBallot` for it
+//! (for example with `getProgramAccounts`), then check the stored counts
+//! against a recount here. Fetching the accounts and signing the report is
+//! up to the client.
+//!
+//! Votes from polls created before receipts sit only in the poll's legacy
+//! `voters` list, with no record of the option chosen, so such polls show a
+//! count discrepancy that the recount cannot resolve.
+
+use arch_program::pubkey::Pubkey;
+
+use crate::{
+    state::{Poll, PollType, RankedBallot, VoteReceipt, ABSTAINED_BALLOT, SPOILED_BALLOT},
+    stv::single_transferable_vote,
+    tally::{borda_count, instant_runoff},
+};
+
+/// A difference between a poll's stored results and the recount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// A record left out of the recount: it belongs to another poll, repeats a
+    /// voter already counted, or names an option the poll does not have.
+    RejectedRecord { voter: Pubkey },
+    /// An option's stored count differs from the recount.
+    VoteCount { option_index: u32, stored: u64, recomputed: u64 },
+    /// An option's stored Borda score differs from the recount.
+    BordaScore { option_index: u32, stored: u128, recomputed: u128 },
+    /// The stored spoiled-ballot count differs from the recount.
+    SpoiledCount { stored: u64, recomputed: u64 },
+    /// The stored abstention count differs from the recount.
+    AbstainCount { stored: u64, recomputed: u64 },
+    /// The winner recorded by `Tally` differs from a fresh instant-runoff count.
+    Winner { stored: Option<u32>, recomputed: Option<u32> },
+    /// The options elected by `Tally` on a poll with more than one seat
+    /// differ from a fresh single-transferable-vote count.
+    Elected { stored: Vec<u32>, recomputed: Vec<u32> },
+}
+
+/// Outcome of recounting one poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// Poll account that was recounted.
+    pub poll: Pubkey,
+    /// Records counted, including spoiled ballots and abstentions.
+    pub ballots: u64,
+    /// Everything that did not match, in the order found.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl VerificationReport {
+    /// Whether the recount matched the stored results exactly.
+    pub fn is_verified(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Recounts a plurality poll from its vote receipts.
+pub fn verify_receipts(
+    poll_key: &Pubkey,
+    poll: &Poll,
+    receipts: &[VoteReceipt],
+) -> VerificationReport {
+    let mut recount = Recount::new(poll_key, poll);
+    for receipt in receipts {
+        let choice = match receipt.option_index {
+            SPOILED_BALLOT => Some(Choice::Spoiled),
+            ABSTAINED_BALLOT => Some(Choice::Abstained),
+            option_index if (option_index as usize) < poll.options.len() => {
+                Some(Choice::Option(option_index))
+            },
+            _ => None,
+        };
+        recount.add(&receipt.poll, &receipt.voter, choice);
+    }
+    recount.finish(None, None)
+}
+
+/// Recounts a ranked-choice or Borda poll from its ballots, including the
+/// winner or the options elected if a ranked-choice poll has been tallied and
+/// the scores of a Borda poll.
+pub fn verify_ballots(
+    poll_key: &Pubkey,
+    poll: &Poll,
+    ballots: &[RankedBallot],
+) -> VerificationReport {
+    let mut recount = Recount::new(poll_key, poll);
+    let mut rankings = Vec::new();
+    for ballot in ballots {
+        let valid = ballot.ranking.iter().enumerate().all(|(position, option_index)| {
+            (*option_index as usize) < poll.options.len()
+                && !ballot.ranking[..position].contains(option_index)
+        });
+        let choice = if ballot.ranking == [ABSTAINED_BALLOT] {
+            Some(Choice::Abstained)
+        } else if valid {
+            Some(ballot.ranking.first().map_or(Choice::Spoiled, |&first| Choice::Option(first)))
+        } else {
+            None
+        };
+        if recount.add(&ballot.poll, &ballot.voter, choice) {
+            rankings.push(ballot.ranking.clone());
+        }
+    }
+
+    let tallied = poll.poll_type == PollType::RankedChoice && poll.is_tallied;
+    let winner = (tallied && poll.seats <= 1)
+        .then(|| instant_runoff(poll.options.len(), &poll.withdrawn_options, &rankings).winner);
+    let scores =
+        (poll.poll_type == PollType::Borda).then(|| borda_count(poll.options.len(), &rankings));
+    let mut report = recount.finish(winner, scores);
+    if tallied && poll.seats > 1 {
+        let groups: Vec<(&[u32], u64)> = rankings.iter().map(|ranking| (&ranking[..], 1)).collect();
+        let seats = usize::from(poll.seats);
+        let recomputed =
+            single_transferable_vote(poll.options.len(), seats, &poll.withdrawn_options, &groups)
+                .elected;
+        if poll.elected != recomputed {
+            report
+                .discrepancies
+                .push(Discrepancy::Elected { stored: poll.elected.clone(), recomputed });
+        }
+    }
+    report
+}
+
+/// What one record counts toward.
+enum Choice {
+    Option(u32),
+    Spoiled,
+    Abstained,
+}
+
+/// Running recount shared by both record kinds.
+struct Recount<'a> {
+    poll_key: &'a Pubkey,
+    poll: &'a Poll,
+    voters: Vec<Pubkey>,
+    counts: Vec<u64>,
+    spoiled: u64,
+    abstained: u64,
+    discrepancies: Vec<Discrepancy>,
+}
+
+impl<'a> Recount<'a> {
+    fn new(poll_key: &'a Pubkey, poll: &'a Poll) -> Self {
+        Self {
+            poll_key,
+            poll,
+            voters: Vec::new(),
+            counts: vec![0; poll.options.len()],
+            spoiled: 0,
+            abstained: 0,
+            discrepancies: Vec::new(),
+        }
+    }
+
+    /// Counts one record, or rejects it if `choice` is `None`. Returns whether
+    /// the record was counted.
+    fn add(&mut self, poll: &Pubkey, voter: &Pubkey, choice: Option<Choice>) -> bool {
+        let choice = match choice {
+            Some(choice) if poll == self.poll_key && !self.voters.contains(voter) => choice,
+            _ => {
+                self.discrepancies.push(Discrepancy::RejectedRecord { voter: *voter });
+                return false;
+            },
+        };
+        self.voters.push(*voter);
+        match choice {
+            Choice::Option(option_index) => self.counts[option_index as usize] += 1,
+            Choice::Spoiled => self.spoiled += 1,
+            Choice::Abstained => self.abstained += 1,
+        }
+        true
+    }
+
+    fn finish(
+        mut self,
+        winner: Option<Option<u32>>,
+        scores: Option<Vec<u128>>,
+    ) -> VerificationReport {
+        for (option_index, (&stored, &recomputed)) in
+            self.poll.vote_counts.iter().zip(&self.counts).enumerate()
+        {
+            if stored != recomputed {
+                self.discrepancies.push(Discrepancy::VoteCount {
+                    option_index: option_index as u32,
+                    stored,
+                    recomputed,
+                });
+            }
+        }
+        for (option_index, (&stored, recomputed)) in
+            self.poll.vote_weights.iter().zip(scores.unwrap_or_default()).enumerate()
+        {
+            if stored != recomputed {
+                self.discrepancies.push(Discrepancy::BordaScore {
+                    option_index: option_index as u32,
+                    stored,
+                    recomputed,
+                });
+            }
+        }
+        if self.poll.spoiled_count != self.spoiled {
+            self.discrepancies.push(Discrepancy::SpoiledCount {
+                stored: self.poll.spoiled_count,
+                recomputed: self.spoiled,
+            });
+        }
+        if self.poll.abstain_count != self.abstained {
+            self.discrepancies.push(Discrepancy::AbstainCount {
+                stored: self.poll.abstain_count,
+                recomputed: self.abstained,
+            });
+        }
+        if let Some(recomputed) = winner {
+            if self.poll.winner != recomputed {
+                self.discrepancies
+                    .push(Discrepancy::Winner { stored: self.poll.winner, recomputed });
+            }
+        }
+
+        VerificationReport {
+            poll: *self.poll_key,
+            ballots: self.voters.len() as u64,
+            discrepancies: self.discrepancies,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PollBuilder;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn receipt(voter: u8, option_index: u32) -> VoteReceipt {
+        VoteReceipt {
+            poll: key(9),
+            voter: key(voter),
+            option_index,
+            slot: 0,
+            accepted_terms: false,
+            delegate: None,
+            delegated_ballots: 0,
+        }
+    }
+
+    #[test]
+    fn test_verify_receipts() {
+        let poll =
+            PollBuilder::new().vote_counts(&[2, 1]).spoiled_count(1).abstain_count(1).build();
+        let receipts = [
+            receipt(1, 0),
+            receipt(2, 0),
+            receipt(3, 1),
+            receipt(4, SPOILED_BALLOT),
+            receipt(6, ABSTAINED_BALLOT),
+        ];
+        let report = verify_receipts(&key(9), &poll, &receipts);
+        assert!(report.is_verified());
+        assert_eq!(report.ballots, 5);
+
+        // A repeated voter and a receipt for another poll are left out, and the
+        // missing vote shows up against the stored counts.
+        let mut foreign = receipt(5, 1);
+        foreign.poll = key(8);
+        let receipts = [receipt(1, 0), receipt(1, 0), foreign, receipt(4, SPOILED_BALLOT)];
+        let report = verify_receipts(&key(9), &poll, &receipts);
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::RejectedRecord { voter: key(1) },
+                Discrepancy::RejectedRecord { voter: key(5) },
+                Discrepancy::VoteCount { option_index: 0, stored: 2, recomputed: 1 },
+                Discrepancy::VoteCount { option_index: 1, stored: 1, recomputed: 0 },
+                Discrepancy::AbstainCount { stored: 1, recomputed: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_ballots() {
+        let ballot = |voter: u8, ranking: &[u32]| RankedBallot {
+            poll: key(9),
+            voter: key(voter),
+            ranking: ranking.to_vec(),
+            slot: 0,
+            accepted_terms: false,
+        };
+        let ballots = [ballot(1, &[0]), ballot(2, &[1]), ballot(3, &[2, 1]), ballot(4, &[])];
+        let builder = || {
+            PollBuilder::new()
+                .options(&["Rust", "Go", "Zig"])
+                .vote_counts(&[1, 1, 1])
+                .spoiled_count(1)
+                .poll_type(PollType::RankedChoice)
+        };
+
+        let mut poll = builder().closed().build();
+        poll.is_tallied = true;
+        poll.winner = Some(1);
+        assert!(verify_ballots(&key(9), &poll, &ballots).is_verified());
+
+        poll.winner = Some(0);
+        assert_eq!(
+            verify_ballots(&key(9), &poll, &ballots).discrepancies,
+            vec![Discrepancy::Winner { stored: Some(0), recomputed: Some(1) }]
+        );
+
+        // Polls with several seats are checked against a fresh STV count,
+        // electing Go on first preferences and then Rust on Zig's exclusion.
+        let mut multi_seat = builder().seats(2).closed().build();
+        multi_seat.is_tallied = true;
+        multi_seat.elected = vec![1, 0];
+        assert!(verify_ballots(&key(9), &multi_seat, &ballots).is_verified());
+        multi_seat.elected = vec![1, 2];
+        assert_eq!(
+            verify_ballots(&key(9), &multi_seat, &ballots).discrepancies,
+            vec![Discrepancy::Elected { stored: vec![1, 2], recomputed: vec![1, 0] }]
+        );
+
+        // Rankings that repeat an option are rejected.
+        let report = verify_ballots(&key(9), &builder().build(), &[ballot(1, &[0, 0])]);
+        assert_eq!(report.discrepancies[0], Discrepancy::RejectedRecord { voter: key(1) });
+
+        // Borda scores are recounted too: 2, 1 and 0 points by place.
+        let ballots = [ballot(1, &[0, 1, 2]), ballot(2, &[2, 0, 1])];
+        let borda = PollBuilder::new()
+            .options(&["Rust", "Go", "Zig"])
+            .vote_counts(&[1, 0, 1])
+            .poll_type(PollType::Borda)
+            .vote_weights(&[3, 1, 2])
+            .build();
+        assert!(verify_ballots(&key(9), &borda, &ballots).is_verified());
+        assert_eq!(
+            verify_ballots(&key(9), &borda, &ballots[..1]).discrepancies,
+            vec![
+                Discrepancy::VoteCount { option_index: 2, stored: 1, recomputed: 0 },
+                Discrepancy::BordaScore { option_index: 0, stored: 3, recomputed: 2 },
+                Discrepancy::BordaScore { option_index: 2, stored: 2, recomputed: 0 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+// src/weight.rs
+
+//! Where the weight of a vote on a weighted poll comes from.
+//!
+//! A weighted vote passes one extra account after the system program, and the
+//! poll's [`VoterWeightSource`] reads the weight from it. Polls weighted by a
+//! token mint read the voter's token account. Polls that name a
+//! `weight_program` instead read a [`VoterWeightRecord`] owned by that
+//! program, in the manner of SPL governance voter-weight plugins: the plugin
+//! computes stake-, NFT- or reputation-based weights however it likes and
+//! writes them to a record, usually in an earlier instruction of the same
+//! transaction, and this program only checks and reads the record.
+//!
+//! Before a weight counts, the poll's [`WeightCurve`] clamps it between the
+//! poll's floor and cap and can take its square root, so that the largest
+//! holders cannot outvote everyone else. The curve is applied once, when the
+//! vote is cast, so `vote_weights`, quorum and finalization all count the
+//! same curved weights.
+
+use arch_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    error::VotingError,
+    state::{Poll, TokenBalance, TOKEN_PROGRAM_ID},
+};
+
+/// First bytes of every [`VoterWeightRecord`].
+pub const VOTER_WEIGHT_RECORD_TAG: [u8; 8] = *b"vtrwght1";
+
+/// A voter's weight on one poll, written by a weight plugin program.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VoterWeightRecord {
+    /// `VOTER_WEIGHT_RECORD_TAG`.
+    pub tag: [u8; 8],
+    /// Poll the weight applies to.
+    pub poll: Pubkey,
+    /// Voter the weight belongs to.
+    pub voter: Pubkey,
+    /// Weight of the voter's ballot.
+    pub weight: u64,
+    /// Last slot in which the record may be used; `None` if it does not expire.
+    pub expiry_slot: Option<u64>,
+}
+
+/// Reads a voter's weight from the account passed with their vote.
+pub trait VoterWeightSource {
+    /// Weight `voter` casts on the poll at `poll_key` in `slot`, read from
+    /// `account`. Fails if the account is not the voter's or the weight is 0.
+    fn voter_weight(
+        &self,
+        poll_key: &Pubkey,
+        voter: &Pubkey,
+        account: &AccountInfo,
+        slot: u64,
+    ) -> Result<u64, ProgramError>;
+}
+
+/// Weight is the voter's balance of `mint` at the time of the vote.
+pub struct TokenBalanceWeight {
+    pub mint: Pubkey,
+}
+
+impl VoterWeightSource for TokenBalanceWeight {
+    fn voter_weight(
+        &self,
+        _poll_key: &Pubkey,
+        voter: &Pubkey,
+        token_account: &AccountInfo,
+        _slot: u64,
+    ) -> Result<u64, ProgramError> {
+        if *token_account.owner != TOKEN_PROGRAM_ID {
+            msg!("Token account is not owned by the token program.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let balance = TokenBalance::unpack(&token_account.data.borrow())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if balance.mint != self.mint || balance.owner != *voter {
+            msg!("Token account is not the voter's account for the weight mint.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        if balance.amount == 0 {
+            msg!("Voter holds no tokens of the weight mint.");
+            return Err(VotingError::NoVotingWeight.into());
+        }
+        Ok(balance.amount)
+    }
+}
+
+/// Weight comes from a [`VoterWeightRecord`] owned by `program`.
+pub struct PluginRecordWeight {
+    pub program: Pubkey,
+}
+
+impl VoterWeightSource for PluginRecordWeight {
+    fn voter_weight(
+        &self,
+        poll_key: &Pubkey,
+        voter: &Pubkey,
+        record_account: &AccountInfo,
+        slot: u64,
+    ) -> Result<u64, ProgramError> {
+        if *record_account.owner != self.program {
+            msg!("Voter weight record is not owned by the poll's weight program.");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let record = VoterWeightRecord::deserialize(&mut &record_account.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if record.tag != VOTER_WEIGHT_RECORD_TAG {
+            msg!("Account is not a voter weight record.");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if record.poll != *poll_key || record.voter != *voter {
+            msg!("Voter weight record is for another poll or voter.");
+            return Err(VotingError::AccountMismatch.into());
+        }
+        if let Some(expiry_slot) = record.expiry_slot.filter(|&expiry_slot| slot > expiry_slot) {
+            msg!("Voter weight record expired in slot {}.", expiry_slot);
+            return Err(VotingError::VoterWeightExpired.into());
+        }
+        if record.weight == 0 {
+            msg!("Voter weight record carries no weight.");
+            return Err(VotingError::NoVotingWeight.into());
+        }
+        Ok(record.weight)
+    }
+}
+
+/// How a clamped weight is turned into the weight a vote carries.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightTransform {
+    /// The weight counts as is.
+    Linear,
+    /// The weight counts as its square root, rounded down, so 100 tokens
+    /// carry 10 votes and 10_000 tokens carry 100.
+    SquareRoot,
+}
+
+/// The floor, cap and transform applied to every weight a weighted poll
+/// counts.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightCurve {
+    /// Least weight a vote counts as before the transform; 0 for no floor.
+    pub min_weight: u64,
+    /// Most weight a vote counts as before the transform; `None` for no cap.
+    pub max_weight: Option<u64>,
+    pub transform: WeightTransform,
+}
+
+impl WeightCurve {
+    /// Counts every weight as is.
+    pub const LINEAR: WeightCurve =
+        WeightCurve { min_weight: 0, max_weight: None, transform: WeightTransform::Linear };
+
+    /// Fails if the cap is zero or below the floor.
+    pub fn validate(&self) -> ProgramResult {
+        if let Some(max_weight) = self.max_weight {
+            if max_weight == 0 || max_weight < self.min_weight {
+                msg!("Weight cap {} is zero or below the floor {}.", max_weight, self.min_weight);
+                return Err(VotingError::InvalidWeightCurve.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The weight a vote with source weight `weight` carries.
+    pub fn apply(&self, weight: u64) -> u64 {
+        let clamped = weight.max(self.min_weight).min(self.max_weight.unwrap_or(u64::MAX));
+        match self.transform {
+            WeightTransform::Linear => clamped,
+            WeightTransform::SquareRoot => integer_sqrt(clamped),
+        }
+    }
+}
+
+/// The square root of `value`, rounded down.
+fn integer_sqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from at or above the root converges to its floor.
+    let mut root = value;
+    let mut next = value / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    root
+}
+
+/// The weight source of `poll`, or `None` for one vote per wallet.
+pub fn weight_source(poll: &Poll) -> Option<Box<dyn VoterWeightSource>> {
+    match (poll.weight_mint, poll.weight_program) {
+        (Some(mint), _) => Some(Box::new(TokenBalanceWeight { mint })),
+        (None, Some(program)) => Some(Box::new(PluginRecordWeight { program })),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::account_info;
+
+    #[test]
+    fn test_weight_curve() {
+        assert_eq!(WeightCurve::LINEAR.apply(1_000_000), 1_000_000);
+        let roots = WeightCurve { transform: WeightTransform::SquareRoot, ..WeightCurve::LINEAR };
+        let cases = [(1, 1), (2, 1), (3, 1), (4, 2), (99, 9), (100, 10), (u64::MAX, 4_294_967_295)];
+        for (weight, root) in cases {
+            assert_eq!(roots.apply(weight), root);
+        }
+
+        // The floor and cap apply before the transform.
+        let bounded = WeightCurve { min_weight: 16, max_weight: Some(10_000), ..roots };
+        assert_eq!([bounded.apply(1), bounded.apply(400), bounded.apply(1_000_000)], [4, 20, 100]);
+        assert!(bounded.validate().is_ok());
+        for max_weight in [0, 15] {
+            assert_eq!(
+                WeightCurve { max_weight: Some(max_weight), ..bounded }.validate(),
+                Err(VotingError::InvalidWeightCurve.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_plugin_record_weight() {
+        let plugin_key = Pubkey::new_from_array([50; 32]);
+        let poll_key = Pubkey::new_from_array([2; 32]);
+        let voter_key = Pubkey::new_from_array([3; 32]);
+        let record_key = Pubkey::new_from_array([51; 32]);
+        let source = PluginRecordWeight { program: plugin_key };
+        let record = |weight, expiry_slot| VoterWeightRecord {
+            tag: VOTER_WEIGHT_RECORD_TAG,
+            poll: poll_key,
+            voter: voter_key,
+            weight,
+            expiry_slot,
+        };
+        let weigh = |record: &VoterWeightRecord, owner: &Pubkey, slot| {
+            let mut data = record.try_to_vec().unwrap();
+            let account = account_info(&record_key, owner, false, &mut data);
+            source.voter_weight(&poll_key, &voter_key, &account, slot)
+        };
+
+        assert_eq!(weigh(&record(40, None), &plugin_key, 7), Ok(40));
+        assert_eq!(weigh(&record(40, Some(7)), &plugin_key, 7), Ok(40));
+        assert_eq!(
+            weigh(&record(40, Some(7)), &plugin_key, 8),
+            Err(VotingError::VoterWeightExpired.into())
+        );
+        assert_eq!(
+            weigh(&record(0, None), &plugin_key, 7),
+            Err(VotingError::NoVotingWeight.into())
+        );
+        assert_eq!(
+            weigh(&record(40, None), &TOKEN_PROGRAM_ID, 7),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        let mut other_voter = record(40, None);
+        other_voter.voter = poll_key;
+        assert_eq!(weigh(&other_voter, &plugin_key, 7), Err(VotingError::AccountMismatch.into()));
+        let mut untagged = record(40, None);
+        untagged.tag = [0; 8];
+        assert_eq!(weigh(&untagged, &plugin_key, 7), Err(ProgramError::InvalidAccountData));
+    }
+}